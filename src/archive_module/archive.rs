@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use chrono::{DateTime, Utc};
+use actix_web::http::StatusCode;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::api_key;
+use crate::audit;
+use crate::config;
+use crate::contest;
+use crate::job;
+use crate::team;
+use crate::user;
+use crate::storage;
+use crate::storage::Counter;
+use crate::function_post_jobs;
+
+// version of the archive format, archives of newer versions are rejected
+// version 2 added the credentials and the API keys of the users
+pub const ARCHIVE_VERSION: u32 = 2;
+
+// the whole state of the server: users, teams, contests with their ranklists, jobs with their source code and the id counters
+// best case times are left out, they are rebuilt from the jobs when the archive is imported
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Archive
+{
+    pub version: u32,
+    pub exported_time: DateTime<Utc>,
+
+    pub users: Vec<user::User>,
+    pub user_id_count: u32,
+
+    pub contests: Vec<contest::Contest>,
+    pub contest_id_count: u32,
+
+    pub jobs: Vec<job::ResponseContent>,
+    pub job_id_count: u32,
+
+    // archives exported before teams existed have none
+    #[serde(default)]
+    pub teams: Vec<team::Team>,
+
+    // archives of version 1 have none, their users cannot log in once imported
+    #[serde(default)]
+    pub credentials: Vec<Credentials>,
+    #[serde(default)]
+    pub api_keys: Vec<ArchivedApiKey>,
+}
+
+// how a user logs in and what it may do, kept out of the users since it is never shown by the API
+// users without a role have the default one, see permission::role_of
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Credentials
+{
+    pub user_id: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>, // argon2, see auth::hash_password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<config::Role>,
+}
+
+// an API key with the hash of its key, which responses leave out
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchivedApiKey
+{
+    #[serde(flatten)]
+    pub api_key: api_key::ApiKey,
+    pub key_hash: String,
+}
+
+impl ArchivedApiKey
+{
+    fn restore(&self) -> api_key::ApiKey
+    {
+        let mut api_key = self.api_key.clone();
+        api_key.key_hash = self.key_hash.clone();
+        return api_key;
+    }
+}
+
+// what an import added to the storage
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportSummary
+{
+    pub merged: bool,
+    pub users: u32,
+    pub contests: u32,
+    pub jobs: u32,
+}
+
+// copies the storage into an archive
+// the caller holds Storage::lock, so that the lists do not change in between
+pub fn export(storage: &dyn storage::Storage) -> Result<Archive, storage::Error>
+{
+    let users = storage.users().clone();
+    let mut credentials: Vec<Credentials> = vec![];
+    for user in users.iter()
+    {
+        let user_credentials = Credentials { user_id: user.id, password_hash: storage.password_hash(user.id)?, role: storage.role(user.id)? };
+        if user_credentials.password_hash.is_some() || user_credentials.role.is_some() {credentials.push(user_credentials);}
+    }
+    Ok(Archive
+    {
+        version: ARCHIVE_VERSION,
+        exported_time: Utc::now(),
+        users,
+        user_id_count: *storage.counter(Counter::User),
+        contests: storage.contests().clone(),
+        contest_id_count: *storage.counter(Counter::Contest),
+        jobs: storage.query_jobs(&storage::JobQuery { with_source: true, ..Default::default() })?,
+        job_id_count: *storage.counter(Counter::Job),
+        teams: storage.teams()?,
+        credentials,
+        api_keys: storage.api_keys(None)?.into_iter().map(|api_key| ArchivedApiKey { key_hash: api_key.key_hash.clone(), api_key }).collect(),
+    })
+}
+
+// reads an archive from its JSON
+// the version is checked first, since archives of newer versions may have other fields
+// fields added since the version of an older archive are left empty
+pub fn parse(value: Value) -> Result<Archive, String>
+{
+    match value["version"].as_u64()
+    {
+        Some(version) if version >= 1 && version <= ARCHIVE_VERSION as u64 => {}
+        Some(version) => return Err(format!("archive version {} is not supported, expected at most {}", version, ARCHIVE_VERSION)),
+        None => return Err("archive has no version".to_string()),
+    }
+    serde_json::from_value(value).map_err(|err| format!("invalid archive: {}", err))
+}
+
+pub fn read(path: &str) -> Result<Archive, String>
+{
+    let file = File::open(path).map_err(|err| format!("cannot open {}: {}", path, err))?;
+    let value: Value = serde_json::from_reader(BufReader::new(file)).map_err(|err| format!("{} is not JSON: {}", path, err))?;
+    parse(value)
+}
+
+pub fn write(path: &str, archive: &Archive) -> Result<(), String>
+{
+    let file = File::create(path).map_err(|err| format!("cannot create {}: {}", path, err))?;
+    serde_json::to_writer_pretty(BufWriter::new(file), archive).map_err(|err| format!("cannot write {}: {}", path, err))
+}
+
+// checks that ids are the positions in their lists, as the storage expects, and that every reference exists
+// users are only in order of their ids, since deleted users leave gaps
+fn validate(archive: &Archive) -> Result<(), String>
+{
+    for (index, user) in archive.users.iter().enumerate()
+    {
+        if index > 0 && user.id <= archive.users[index - 1].id {return Err(format!("user {} is out of order", user.id));}
+    }
+    for (index, contest) in archive.contests.iter().enumerate()
+    {
+        if contest.id as usize != index {return Err(format!("contest {} is out of order", contest.id));}
+    }
+    if archive.users.is_empty() || archive.contests.is_empty()
+    {
+        return Err("archive has no root user or no contest 0".to_string());
+    }
+    let user_count = archive.users.last().map(|user| user.id + 1).unwrap_or(0);
+    if archive.user_id_count < user_count || (archive.contest_id_count as usize) < archive.contests.len()
+    {
+        return Err("id counts are smaller than the lists".to_string());
+    }
+    let exists = |user_id: &u32| archive.users.binary_search_by_key(user_id, |user| user.id).is_ok();
+
+    for (index, team) in archive.teams.iter().enumerate()
+    {
+        if team.id as usize != index {return Err(format!("team {} is out of order", team.id));}
+        for user_id in team.member_ids.iter().chain(team.coach_id.iter())
+        {
+            if !exists(user_id) {return Err(format!("team {} has unknown user {}", team.id, user_id));}
+        }
+    }
+    for contest in archive.contests.iter()
+    {
+        for user_id in contest.user_ids.iter().chain(contest.users.iter().map(|rank_info| &rank_info.user.id))
+        {
+            if !exists(user_id) {return Err(format!("contest {} has unknown user {}", contest.id, user_id));}
+        }
+        for team_id in contest.team_ids.iter()
+        {
+            if *team_id as usize >= archive.teams.len() {return Err(format!("contest {} has unknown team {}", contest.id, team_id));}
+        }
+    }
+    // every user takes part in contest 0
+    for user in archive.users.iter()
+    {
+        if !archive.contests[0].users.iter().any(|rank_info| rank_info.user.id == user.id)
+        {
+            return Err(format!("user {} is not in contest 0", user.id));
+        }
+    }
+
+    for (index, credentials) in archive.credentials.iter().enumerate()
+    {
+        if !exists(&credentials.user_id) {return Err(format!("credentials of unknown user {}", credentials.user_id));}
+        if archive.credentials[..index].iter().any(|other| other.user_id == credentials.user_id)
+        {
+            return Err(format!("credentials of user {} are repeated", credentials.user_id));
+        }
+    }
+
+    for (index, archived) in archive.api_keys.iter().enumerate()
+    {
+        if !exists(&archived.api_key.user_id) {return Err(format!("API key {} has unknown user {}", archived.api_key.id, archived.api_key.user_id));}
+        if archive.api_keys[..index].iter().any(|other| other.api_key.id == archived.api_key.id || other.key_hash == archived.key_hash)
+        {
+            return Err(format!("API key {} is repeated", archived.api_key.id));
+        }
+    }
+
+    let mut job_ids: Vec<u32> = archive.jobs.iter().map(|job| job.id).collect();
+    job_ids.sort();
+    job_ids.dedup();
+    if job_ids.len() != archive.jobs.len() {return Err("job ids are repeated".to_string());}
+    for job in archive.jobs.iter()
+    {
+        if job.id >= archive.job_id_count {return Err(format!("job {} is not below the job id count", job.id));}
+        if !exists(&job.submission.user_id) {return Err(format!("job {} has unknown user {}", job.id, job.submission.user_id));}
+        if job.submission.contest_id as usize >= archive.contests.len()
+        {
+            return Err(format!("job {} has unknown contest {}", job.id, job.submission.contest_id));
+        }
+    }
+    Ok(())
+}
+
+// loads an archive into the storage
+// without merge everything in the storage is replaced, and ids are kept as they are in the archive
+// with merge, users and teams are matched by name, and other contests and jobs are added with ids after the existing ones
+// the caller holds Storage::lock
+pub fn import(archive: Archive, merge: bool, config: &config::Config, storage: &dyn storage::Storage)
+    -> Result<ImportSummary, (StatusCode, job::Error)>
+{
+    validate(&archive).map_err(|message| (StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message)))?;
+    let summary = match merge
+    {
+        true => merge_archive(archive, config, storage),
+        false => replace(archive, config, storage),
+    };
+    return summary.map_err(storage::internal);
+}
+
+fn replace(mut archive: Archive, config: &config::Config, storage: &dyn storage::Storage) -> Result<ImportSummary, storage::Error>
+{
+    for contest in archive.contests.iter_mut()
+    {
+        contest.case_times = contest::empty_case_times(&config.problems);
+        function_post_jobs::rebuild_case_times(contest, &archive.jobs, &config.problems);
+    }
+
+    // rows are saved in the order they reference each other
+    storage.transaction(&mut || {
+        storage.clear()?;
+        for user in archive.users.iter() {storage.save_user(user)?;}
+        save_credentials(&archive.credentials, &|user_id| user_id, storage)?;
+        for archived in archive.api_keys.iter() {storage.save_api_key(&archived.restore())?;}
+        for team in archive.teams.iter() {storage.save_team(team)?;}
+        for contest in archive.contests.iter() {storage.save_contest(contest)?;}
+        for job in archive.jobs.iter() {storage.save_job(job)?;}
+        storage.save_counter(Counter::User, archive.user_id_count)?;
+        storage.save_counter(Counter::Contest, archive.contest_id_count)?;
+        storage.save_counter(Counter::Job, archive.job_id_count)?;
+
+        // the audit log is kept, the archive is written to it as if it was created from scratch
+        storage.append_event(audit::ACTOR_ADMIN, audit::Change::state_cleared)?;
+        for user in archive.users.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::user_created { user: user.clone() })?;}
+        for credentials in archive.credentials.iter()
+        {
+            let user = archive.users.iter().find(|user| user.id == credentials.user_id);
+            if let (Some(role), Some(user)) = (credentials.role, user)
+            {
+                storage.append_event(audit::ACTOR_ADMIN, audit::Change::user_role_changed { user: user.clone(), role })?;
+            }
+        }
+        for archived in archive.api_keys.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::api_key_created { api_key: archived.api_key.clone() })?;}
+        for team in archive.teams.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::team_created { team: team.clone() })?;}
+        for contest in archive.contests.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::contest_created { contest: contest.clone() })?;}
+        for job in archive.jobs.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::job_submitted { job: job.clone() })?;}
+        Ok(())
+    })?;
+
+    let summary = ImportSummary
+    {
+        merged: false,
+        users: archive.users.len() as u32,
+        contests: archive.contests.len() as u32,
+        jobs: archive.jobs.len() as u32,
+    };
+    *storage.users() = archive.users;
+    *storage.counter(Counter::User) = archive.user_id_count;
+    *storage.contests() = archive.contests;
+    *storage.counter(Counter::Contest) = archive.contest_id_count;
+    storage.jobs().clear();
+    for job in archive.jobs.iter() {storage.keep_job(job);}
+    *storage.counter(Counter::Job) = archive.job_id_count;
+    return Ok(summary);
+}
+
+// saves the credentials of the archive for the users with the ids given by user_id
+fn save_credentials(credentials: &[Credentials], user_id: &dyn Fn(u32) -> u32, storage: &dyn storage::Storage) -> Result<(), storage::Error>
+{
+    for credentials in credentials.iter()
+    {
+        if let Some(password_hash) = &credentials.password_hash {storage.save_password(user_id(credentials.user_id), password_hash)?;}
+        if let Some(role) = credentials.role {storage.save_role(user_id(credentials.user_id), role)?;}
+    }
+    Ok(())
+}
+
+// the lists and the counters are changed on copies, which replace them once everything is saved
+fn merge_archive(archive: Archive, config: &config::Config, storage: &dyn storage::Storage) -> Result<ImportSummary, storage::Error>
+{
+    let mut lock_user_list = storage.users();
+    let mut lock_user_id_count = storage.counter(Counter::User);
+    let mut lock_contest_list = storage.contests();
+    let mut lock_contest_id_count = storage.counter(Counter::Contest);
+    let mut lock_job_id_count = storage.counter(Counter::Job);
+
+    let mut user_list = lock_user_list.clone();
+    let mut user_id_count = *lock_user_id_count;
+    let mut contest_list = lock_contest_list.clone();
+    let mut contest_id_count = *lock_contest_id_count;
+    let mut job_id_count = *lock_job_id_count;
+
+    // users with the same name are the same user, names differing only in case are the same name like in user::name_taken
+    let mut user_ids: HashMap<u32, u32> = HashMap::new();
+    let mut new_users: Vec<user::User> = vec![];
+    for user in archive.users.iter()
+    {
+        let name = user.name.to_lowercase();
+        if let Some(existing) = user_list.iter().find(|existing| existing.name.to_lowercase() == name)
+        {
+            user_ids.insert(user.id, existing.id);
+            continue;
+        }
+        let new_user = user::User { id: user_id_count, name: user.name.clone(), profile: user.profile.clone(), deactivated: user.deactivated };
+        user_id_count += 1;
+        user_ids.insert(user.id, new_user.id);
+        user_list.push(new_user.clone());
+        new_users.push(new_user);
+    }
+
+    // existing users keep their credentials
+    let new_credentials: Vec<Credentials> = archive.credentials.iter()
+        .filter(|credentials| new_users.iter().any(|user| user.id == user_ids[&credentials.user_id])).cloned().collect();
+
+    // API keys of new users get ids after the existing ones, keys that are already in the storage are left out
+    let mut api_key_id = storage.api_keys(None)?.iter().map(|api_key| api_key.id + 1).max().unwrap_or(0);
+    let mut new_api_keys: Vec<api_key::ApiKey> = vec![];
+    for archived in archive.api_keys.iter()
+    {
+        let user_id = user_ids[&archived.api_key.user_id];
+        if !new_users.iter().any(|user| user.id == user_id) || storage.api_key(&archived.key_hash)?.is_some() {continue;}
+        let mut new_api_key = archived.restore();
+        new_api_key.id = api_key_id;
+        new_api_key.user_id = user_id;
+        api_key_id += 1;
+        new_api_keys.push(new_api_key);
+    }
+
+    // teams with the same name are the same team, regardless of case like in team::name_taken
+    // new teams get the ids of their users in the storage
+    let mut team_list = storage.teams()?;
+    let mut team_ids: HashMap<u32, u32> = HashMap::new();
+    let mut new_teams: Vec<team::Team> = vec![];
+    for team in archive.teams.iter()
+    {
+        let name = team.name.to_lowercase();
+        if let Some(existing) = team_list.iter().find(|existing| existing.name.to_lowercase() == name)
+        {
+            team_ids.insert(team.id, existing.id);
+            continue;
+        }
+        let new_team = team::Team
+        {
+            id: team_list.len() as u32,
+            name: team.name.clone(),
+            member_ids: team.member_ids.iter().map(|id| user_ids[id]).collect(),
+            coach_id: team.coach_id.map(|id| user_ids[&id]),
+        };
+        team_ids.insert(team.id, new_team.id);
+        team_list.push(new_team.clone());
+        new_teams.push(new_team);
+    }
+
+    // contest 0 stays contest 0, the other contests are added as new ones
+    let mut contest_ids: HashMap<u32, u32> = HashMap::from([(0, 0)]);
+    let mut changed_contests: Vec<usize> = vec![0];
+    for contest in archive.contests.iter().skip(1)
+    {
+        let mut new_contest = contest.clone();
+        new_contest.id = contest_id_count;
+        contest_id_count += 1;
+        new_contest.user_ids = contest.user_ids.iter().map(|id| user_ids[id]).collect();
+        new_contest.team_ids = contest.team_ids.iter().map(|id| team_ids[id]).collect();
+        for rank_info in new_contest.users.iter_mut()
+        {
+            rank_info.user.id = user_ids[&rank_info.user.id];
+        }
+        new_contest.case_times = contest::empty_case_times(&config.problems);
+
+        contest_ids.insert(contest.id, new_contest.id);
+        changed_contests.push(new_contest.id as usize);
+        contest_list.push(new_contest);
+    }
+
+    // users new to contest 0 join it with their imported progress, the progress of existing users is kept
+    let root_contest = &mut contest_list[0];
+    for rank_info in archive.contests[0].users.iter()
+    {
+        let user_id = user_ids[&rank_info.user.id];
+        if root_contest.users.iter().any(|existing| existing.user.id == user_id) {continue;}
+
+        let mut rank_info = rank_info.clone();
+        rank_info.user.id = user_id;
+        root_contest.user_ids.push(user_id);
+        root_contest.users.push(rank_info);
+    }
+
+    // jobs are added in the order they were created
+    let mut new_jobs: Vec<job::ResponseContent> = archive.jobs.clone();
+    new_jobs.sort_by_key(|job| job.id);
+    for job in new_jobs.iter_mut()
+    {
+        job.id = job_id_count;
+        job_id_count += 1;
+        job.submission.user_id = user_ids[&job.submission.user_id];
+        job.submission.contest_id = contest_ids[&job.submission.contest_id];
+    }
+
+    for index in changed_contests.iter()
+    {
+        function_post_jobs::rebuild_case_times(&mut contest_list[*index], &new_jobs, &config.problems);
+    }
+
+    storage.transaction(&mut || {
+        for user in new_users.iter() {storage.save_user(user)?;}
+        save_credentials(&new_credentials, &|user_id| user_ids[&user_id], storage)?;
+        for api_key in new_api_keys.iter() {storage.save_api_key(api_key)?;}
+        for team in new_teams.iter() {storage.save_team(team)?;}
+        for index in changed_contests.iter() {storage.save_contest(&contest_list[*index])?;}
+        for job in new_jobs.iter() {storage.save_job(job)?;}
+        storage.save_counter(Counter::User, user_id_count)?;
+        storage.save_counter(Counter::Contest, contest_id_count)?;
+        storage.save_counter(Counter::Job, job_id_count)?;
+
+        for user in new_users.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::user_created { user: user.clone() })?;}
+        for credentials in new_credentials.iter()
+        {
+            let user = new_users.iter().find(|user| user.id == user_ids[&credentials.user_id]);
+            if let (Some(role), Some(user)) = (credentials.role, user)
+            {
+                storage.append_event(audit::ACTOR_ADMIN, audit::Change::user_role_changed { user: user.clone(), role })?;
+            }
+        }
+        for api_key in new_api_keys.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::api_key_created { api_key: api_key.clone() })?;}
+        for team in new_teams.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::team_created { team: team.clone() })?;}
+        for index in changed_contests.iter()
+        {
+            let contest = contest_list[*index].clone();
+            let change = match *index
+            {
+                0 => audit::Change::contest_updated { contest },
+                _ => audit::Change::contest_created { contest },
+            };
+            storage.append_event(audit::ACTOR_ADMIN, change)?;
+        }
+        for job in new_jobs.iter() {storage.append_event(audit::ACTOR_ADMIN, audit::Change::job_submitted { job: job.clone() })?;}
+        Ok(())
+    })?;
+    *lock_user_list = user_list;
+    *lock_user_id_count = user_id_count;
+    *lock_contest_list = contest_list;
+    *lock_contest_id_count = contest_id_count;
+    *lock_job_id_count = job_id_count;
+    for job in new_jobs.iter() {storage.keep_job(job);}
+
+    Ok(ImportSummary
+    {
+        merged: true,
+        users: new_users.len() as u32,
+        contests: changed_contests.len() as u32 - 1,
+        jobs: new_jobs.len() as u32,
+    })
+}
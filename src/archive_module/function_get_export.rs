@@ -0,0 +1,24 @@
+use actix_web::{get, Responder, HttpResponse, web};
+
+use crate::archive;
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+// exports users, contests, jobs and id counters as an archive, see archive::Archive
+// the export waits for other writers, so it runs on the blocking thread pool
+#[get("/admin/export", wrap = "permission::Require(permission::Permission::administer)")]
+async fn get_admin_export(data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let result = web::block(move || {
+        let _lock = data_storage.lock();
+        archive::export(data_storage.get_ref()).map_err(storage::unreadable)
+    }).await;
+    match result
+    {
+        Ok(Ok(archive)) => HttpResponse::Ok().json(archive),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Archive could not be exported.".to_string())),
+    }
+}
@@ -0,0 +1,60 @@
+use actix_web::{post, Responder, HttpResponse, web};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::archive;
+use crate::job;
+use crate::config;
+use crate::storage;
+use crate::permission;
+use crate::Arc;
+use crate::Mutex;
+
+// used to obtain query data
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery
+{
+    merge: Option<bool>,
+}
+
+fn invalid_archive(message: String) -> HttpResponse
+{
+    HttpResponse::BadRequest().json(job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message))
+}
+
+// imports an archive, replacing the storage or merging into it with ?merge=true
+// archives are larger than the default limit of JSON bodies, so the body is read as it comes
+#[post("/admin/import", wrap = "permission::Require(permission::Permission::administer)")]
+async fn post_admin_import(mut payload: web::Payload, info: web::Query<ImportQuery>, data_config: web::Data<Arc<Mutex<config::Config>>>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await
+    {
+        match chunk
+        {
+            Ok(chunk) => body.extend_from_slice(&chunk),
+            Err(err) => return invalid_archive(err.to_string()),
+        }
+    }
+    let archive = match serde_json::from_slice::<Value>(&body).map_err(|err| err.to_string()).and_then(archive::parse)
+    {
+        Ok(archive) => archive,
+        Err(message) => return invalid_archive(message),
+    };
+
+    let config = data_config.lock().unwrap().clone();
+    let merge = info.merge.unwrap_or(false);
+    let result = web::block(move || {
+        let _lock = data_storage.lock();
+        archive::import(archive, merge, &config, data_storage.get_ref())
+    }).await;
+    match result
+    {
+        Ok(Ok(summary)) => HttpResponse::Ok().json(summary),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Archive could not be imported.".to_string())),
+    }
+}
@@ -0,0 +1,3 @@
+pub mod archive;
+pub mod function_get_export;
+pub mod function_post_import;
\ No newline at end of file
@@ -0,0 +1,264 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::api_key;
+use crate::archive;
+use crate::config;
+use crate::contest;
+use crate::job;
+use crate::team;
+use crate::user;
+use crate::deletion;
+use crate::storage;
+use crate::recovery;
+use crate::function_post_jobs;
+
+// who made a change, requests that do not say who sent them are anonymous
+pub const ACTOR_SYSTEM: &str = "system";
+pub const ACTOR_ADMIN: &str = "admin";
+pub const ACTOR_ANONYMOUS: &str = "anonymous";
+
+pub fn user_actor(user_id: u32) -> String
+{
+    format!("user:{}", user_id)
+}
+
+pub fn worker_actor(worker_id: u32) -> String
+{
+    format!("worker:{}", worker_id)
+}
+
+// a change to the state of the server, with the whole user, contest or job as it was after the change
+// state_cleared brings the state back to a new server, e.g. when the storage is reset or replaced by an archive
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", content = "data")]
+#[allow(non_camel_case_types)]
+pub enum Change
+{
+    state_cleared,
+    user_created { user: user::User },
+    user_renamed { user: user::User },
+    user_updated { user: user::User }, // PATCH /users/{id}, which may change the name and the profile
+    user_role_changed { user: user::User, role: config::Role },
+    user_deleted { user_id: u32, jobs: deletion::JobPolicy }, // see deletion::cascade
+    api_key_created { api_key: api_key::ApiKey },
+    api_key_revoked { api_key: api_key::ApiKey },
+    usage_reset { user_id: u32, reset_time: DateTime<Utc> }, // see quota
+    team_created { team: team::Team },
+    team_updated { team: team::Team },
+    contest_created { contest: contest::Contest },
+    contest_updated { contest: contest::Contest },
+    job_submitted { job: job::ResponseContent },
+    job_rejudged { job: job::ResponseContent },
+    job_requeued { job: job::ResponseContent },
+    job_judged { job: job::ResponseContent },
+}
+
+impl Change
+{
+    // the name stored with the change, e.g. "job_judged"
+    pub fn kind(&self) -> String
+    {
+        let value = serde_json::to_value(self).expect("error serializing change");
+        return value["kind"].as_str().unwrap_or_default().to_string();
+    }
+}
+
+// an entry of the audit log, ids are given in the order entries are appended
+// entries are never changed or removed once appended
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Event
+{
+    pub id: u64,
+    pub time: DateTime<Utc>,
+    pub actor: String,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+// which entries of the audit log are listed, always in the order they were appended
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery
+{
+    pub kind: Option<String>,
+    pub actor: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub after_id: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+impl EventQuery
+{
+    pub fn matches(&self, event: &Event) -> bool
+    {
+        if let Some(kind) = &self.kind
+        {
+            if event.change.kind() != *kind {return false;}
+        }
+        if let Some(actor) = &self.actor
+        {
+            if event.actor != *actor {return false;}
+        }
+        if let Some(from) = self.from
+        {
+            if event.time < from {return false;}
+        }
+        if let Some(to) = self.to
+        {
+            if event.time > to {return false;}
+        }
+        if let Some(after_id) = self.after_id
+        {
+            if event.id <= after_id {return false;}
+        }
+        return true;
+    }
+}
+
+// ids of the users deleted so far, from the user_deleted entries
+pub fn deleted_user_ids(storage: &dyn storage::Storage) -> Result<Vec<u32>, storage::Error>
+{
+    let query = EventQuery { kind: Some("user_deleted".to_string()), ..Default::default() };
+    return Ok(storage.query_events(&query)?.iter().filter_map(|event| match event.change
+    {
+        Change::user_deleted { user_id, .. } => Some(user_id),
+        _ => None,
+    }).collect());
+}
+
+// leaves deleted users out of entries as they are listed or replayed, also entries from before the deletion
+// their names and profiles are those of deletion::placeholder, the stored entries are not changed
+pub fn redact(events: &mut [Event], deleted_user_ids: &[u32])
+{
+    let redact_user = |user: &mut user::User|
+    {
+        if !deleted_user_ids.contains(&user.id) {return;}
+        let placeholder = deletion::placeholder(user.id);
+        user.name = placeholder.name;
+        user.profile = placeholder.profile;
+    };
+    for event in events.iter_mut()
+    {
+        match &mut event.change
+        {
+            Change::user_created { user } | Change::user_renamed { user } | Change::user_updated { user }
+                | Change::user_role_changed { user, .. } => redact_user(user),
+            Change::contest_created { contest } | Change::contest_updated { contest } =>
+            {
+                for rank_info in contest.users.iter_mut() {redact_user(&mut rank_info.user);}
+            }
+            _ => {}
+        }
+    }
+}
+
+// replaces the element with the same id, or adds it at the end
+fn upsert<T>(list: &mut Vec<T>, element: T, id: impl Fn(&T) -> u32)
+{
+    match list.iter().position(|other| id(other) == id(&element))
+    {
+        Some(position) => list[position] = element,
+        None => list.push(element),
+    }
+}
+
+// rebuilds the state the server had after the given events, as an archive that can be imported
+// the progress of each user and the best case times are computed again from the jobs
+pub fn replay(events: &Vec<Event>, config: &config::Config) -> archive::Archive
+{
+    let mut users: Vec<user::User> = vec![storage::root_user()];
+    let mut contests: Vec<contest::Contest> = vec![storage::root_contest(config)];
+    let mut jobs: Vec<job::ResponseContent> = vec![];
+    let mut teams: Vec<team::Team> = vec![];
+    let mut credentials: Vec<archive::Credentials> = vec![];
+    // ids of deleted users are not given again
+    let mut user_id_count: u32 = 1;
+
+    for event in events.iter()
+    {
+        match &event.change
+        {
+            Change::state_cleared =>
+            {
+                users = vec![storage::root_user()];
+                contests = vec![storage::root_contest(config)];
+                jobs = vec![];
+                teams = vec![];
+                credentials = vec![];
+                user_id_count = 1;
+            }
+            Change::user_created { user } | Change::user_renamed { user } | Change::user_updated { user } =>
+            {
+                upsert(&mut users, user.clone(), |user| user.id);
+                user_id_count = user_id_count.max(user.id + 1);
+                for contest in contests.iter_mut()
+                {
+                    for rank_info in contest.users.iter_mut()
+                    {
+                        if rank_info.user.id == user.id {rank_info.user = user.summary();}
+                    }
+                }
+
+                // every user takes part in contest 0
+                let root_contest = &mut contests[0];
+                if !root_contest.users.iter().any(|rank_info| rank_info.user.id == user.id)
+                {
+                    root_contest.user_ids.push(user.id);
+                    root_contest.users.push(contest::RankInfo::new(user.clone(), root_contest.problem_ids.len()));
+                }
+            }
+            Change::user_deleted { user_id, jobs: policy } =>
+            {
+                deletion::cascade(*user_id, *policy, &mut users, &mut contests, &mut teams, &mut jobs, &config.problems);
+                credentials.retain(|credentials| credentials.user_id != *user_id);
+            }
+            // passwords are not in the audit log, so only the roles are rebuilt
+            Change::user_role_changed { user, role } =>
+            {
+                let role = archive::Credentials { user_id: user.id, password_hash: None, role: Some(*role) };
+                upsert(&mut credentials, role, |credentials| credentials.user_id);
+            }
+            // the hashes of API keys are not in the audit log, and usage resets are not part of an archive
+            Change::api_key_created { .. } | Change::api_key_revoked { .. } | Change::usage_reset { .. } => {}
+            Change::team_created { team } | Change::team_updated { team } =>
+            {
+                upsert(&mut teams, team.clone(), |team| team.id);
+            }
+            Change::contest_created { contest } | Change::contest_updated { contest } =>
+            {
+                upsert(&mut contests, contest.clone(), |contest| contest.id);
+            }
+            Change::job_submitted { job } | Change::job_rejudged { job } | Change::job_requeued { job } | Change::job_judged { job } =>
+            {
+                upsert(&mut jobs, job.clone(), |job| job.id);
+            }
+        }
+    }
+
+    for contest in contests.iter_mut()
+    {
+        let user_ids: Vec<u32> = contest.users.iter().map(|rank_info| rank_info.user.id).collect();
+        for user_id in user_ids
+        {
+            recovery::reconcile_participant(contest, user_id, &jobs);
+        }
+        contest.case_times = contest::empty_case_times(&config.problems);
+        function_post_jobs::rebuild_case_times(contest, &jobs, &config.problems);
+    }
+
+    archive::Archive
+    {
+        version: archive::ARCHIVE_VERSION,
+        exported_time: Utc::now(),
+        user_id_count,
+        users,
+        contest_id_count: contests.len() as u32,
+        contests,
+        job_id_count: jobs.iter().map(|job| job.id + 1).max().unwrap_or(0),
+        jobs,
+        teams,
+        credentials,
+        api_keys: vec![],
+    }
+}
@@ -0,0 +1,99 @@
+use actix_web::{get, Responder, HttpResponse, web};
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+
+use crate::audit;
+use crate::archive;
+use crate::config;
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+// used to obtain query data
+#[derive(Debug, Deserialize)]
+pub struct AuditRequest
+{
+    kind: Option<String>,
+    actor: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    after_id: Option<u64>,
+    limit: Option<u32>,
+}
+
+// used to obtain query data
+#[derive(Debug, Deserialize)]
+pub struct ReplayRequest
+{
+    at: Option<DateTime<Utc>>,
+}
+
+// lists the entries of the audit log that satisfy query requirements, in the order they were appended
+// pages of at most limit entries are listed by passing the id of the last entry as after_id
+// deleted users are redacted, see audit::redact
+#[get("/audit", wrap = "permission::Require(permission::Permission::administer)")]
+async fn get_audit(info: web::Query<AuditRequest>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    if info.limit == Some(0)
+    {
+        return HttpResponse::BadRequest().json(job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), "Limit must be positive.".to_string()));
+    }
+    let info = info.into_inner();
+    let query = audit::EventQuery
+    {
+        kind: info.kind,
+        actor: info.actor,
+        from: info.from,
+        to: info.to,
+        after_id: info.after_id,
+        limit: info.limit,
+    };
+
+    // the storage may wait for a free database connection
+    match web::block(move || -> Result<Vec<audit::Event>, storage::Error>
+    {
+        let mut events = data_storage.query_events(&query)?;
+        audit::redact(&mut events, &audit::deleted_user_ids(data_storage.get_ref())?);
+        Ok(events)
+    }).await
+    {
+        Ok(Ok(events)) => HttpResponse::Ok().json(events),
+        Ok(Err(error)) =>
+        {
+            let (status, error) = storage::unreadable(error);
+            HttpResponse::build(status).json(error)
+        }
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Audit log could not be listed.".to_string())),
+    }
+}
+
+// rebuilds the state of the server at the given time from the audit log, as an archive, see audit::replay
+// without a time, the current state is rebuilt
+// users deleted since are redacted even at times before their deletion
+#[get("/audit/replay", wrap = "permission::Require(permission::Permission::administer)")]
+async fn get_audit_replay(info: web::Query<ReplayRequest>, data_config: web::Data<Arc<Mutex<config::Config>>>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let config = data_config.lock().unwrap().clone();
+    let query = audit::EventQuery { to: info.at, ..Default::default() };
+
+    match web::block(move || -> Result<archive::Archive, storage::Error>
+    {
+        let mut events = data_storage.query_events(&query)?;
+        audit::redact(&mut events, &audit::deleted_user_ids(data_storage.get_ref())?);
+        Ok(audit::replay(&events, &config))
+    }).await
+    {
+        Ok(Ok(archive)) => HttpResponse::Ok().json(archive),
+        Ok(Err(error)) =>
+        {
+            let (status, error) = storage::unreadable(error);
+            HttpResponse::build(status).json(error)
+        }
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Audit log could not be replayed.".to_string())),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,2 @@
+pub mod audit;
+pub mod function_get_audit;
\ No newline at end of file
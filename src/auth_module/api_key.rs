@@ -0,0 +1,93 @@
+use actix_web::http::StatusCode;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::auth;
+use crate::job;
+use crate::permission::Permission;
+use crate::storage;
+
+// prefix of every key, so that keys are recognized e.g. when they leak into logs
+const KEY_PREFIX: &str = "oj_";
+
+// what a key may be used for, on top of what the role of its user allows
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Scope
+{
+    submit,
+    read_jobs, // reading jobs and everything else that is public to contestants
+    manage_contests,
+}
+
+impl Scope
+{
+    pub fn permission(&self) -> Permission
+    {
+        match self
+        {
+            Scope::submit => Permission::submit,
+            Scope::read_jobs => Permission::view,
+            Scope::manage_contests => Permission::manage_contests,
+        }
+    }
+}
+
+// a key used by bots instead of a session, sent in the X-API-Key header
+// like session tokens, only the sha256 hash of the key is stored, the key itself is shown once when it is created
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiKey
+{
+    pub id: u32,
+    pub user_id: u32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_time: DateTime<Utc>,
+    pub expires_time: Option<DateTime<Utc>>,
+    pub last_used_time: Option<DateTime<Utc>>,
+    pub revoked: bool,
+    #[serde(skip)]
+    pub key_hash: String,
+}
+
+impl ApiKey
+{
+    pub fn is_active(&self) -> bool
+    {
+        return !self.revoked && self.expires_time.map_or(true, |expires_time| Utc::now() < expires_time);
+    }
+}
+
+// body of POST /api-keys, keys without expires_time do not expire
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostApiKey
+{
+    pub user_id: u32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub expires_time: Option<DateTime<Utc>>,
+}
+
+// response of POST /api-keys, the only one containing the key
+#[derive(Serialize, Debug, Clone)]
+pub struct NewApiKey
+{
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+pub fn new_key() -> String
+{
+    return format!("{}{}", KEY_PREFIX, auth::new_token());
+}
+
+// the key, if it is neither revoked nor expired
+pub fn find(key: &str, storage: &dyn storage::Storage) -> Result<ApiKey, (StatusCode, job::Error)>
+{
+    match storage.api_key(&auth::hash_token(key)).map_err(storage::unreadable)?
+    {
+        Some(api_key) if api_key.is_active() => Ok(api_key),
+        _ => Err((StatusCode::UNAUTHORIZED, auth::unauthorized("API key is invalid, revoked or has expired."))),
+    }
+}
\ No newline at end of file
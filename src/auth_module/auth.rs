@@ -0,0 +1,186 @@
+use actix_web::HttpRequest;
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::{PasswordHash, SaltString};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Sha256, Digest};
+
+use crate::api_key;
+use crate::job;
+use crate::storage;
+
+// passwords shorter than this are rejected by POST /register
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+// number of random bytes in a session token
+const TOKEN_BYTES: usize = 32;
+
+// characters of generated passwords, without those easily confused when printed such as 0, O, 1 and l
+const PASSWORD_ALPHABET: &[u8] = b"abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+// length of generated passwords
+const GENERATED_PASSWORD_LENGTH: usize = 12;
+
+// header carrying an API key, see api_key
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+// a login of a user, sent back as a bearer token
+// only the client knows the token, the storage keeps its sha256 hash
+#[derive(Debug, Clone)]
+pub struct Session
+{
+    pub token_hash: String,
+    pub user_id: u32,
+    pub created_time: DateTime<Utc>,
+    pub expires_time: DateTime<Utc>,
+    pub revoked: bool,
+}
+
+impl Session
+{
+    // a session for the token that expires after ttl seconds
+    pub fn new(token: &str, user_id: u32, ttl: u64) -> Session
+    {
+        let created_time = Utc::now();
+        Session
+        {
+            token_hash: hash_token(token),
+            user_id,
+            created_time,
+            expires_time: created_time + Duration::seconds(ttl as i64),
+            revoked: false,
+        }
+    }
+
+    pub fn is_active(&self) -> bool
+    {
+        return !self.revoked && Utc::now() < self.expires_time;
+    }
+}
+
+// hashes a password with argon2 and a random salt, in the PHC string format
+pub fn hash_password(password: &str) -> String
+{
+    let salt = SaltString::generate(&mut OsRng);
+    return Argon2::default().hash_password(password.as_bytes(), &salt).expect("error hashing password").to_string();
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool
+{
+    match PasswordHash::new(password_hash)
+    {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
+}
+
+// a new random password, e.g. for accounts created by a user import
+pub fn generate_password() -> String
+{
+    let mut password = String::new();
+    while password.len() < GENERATED_PASSWORD_LENGTH
+    {
+        // bytes past the last multiple of the alphabet length are skipped, so that every character is equally likely
+        let byte = (OsRng.next_u32() & 0xff) as usize;
+        if byte < 256 - 256 % PASSWORD_ALPHABET.len()
+        {
+            password.push(PASSWORD_ALPHABET[byte % PASSWORD_ALPHABET.len()] as char);
+        }
+    }
+    return password;
+}
+
+// a new random token, in hex
+pub fn new_token() -> String
+{
+    let mut bytes = [0u8; TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+pub fn hash_token(token: &str) -> String
+{
+    return format!("{:x}", Sha256::digest(token.as_bytes()));
+}
+
+// the token of an "Authorization: Bearer <token>" header, None if the request has none
+pub fn bearer_token(req: &HttpRequest) -> Option<String>
+{
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    return value.strip_prefix("Bearer ").map(|token| token.trim().to_string());
+}
+
+// what a request presents to be authenticated, the API key is used if both are present
+#[derive(Debug, Clone, Default)]
+pub struct Authorization
+{
+    pub token: Option<String>,
+    pub api_key: Option<String>,
+}
+
+pub fn authorization(req: &HttpRequest) -> Authorization
+{
+    let api_key = req.headers().get(API_KEY_HEADER).and_then(|value| value.to_str().ok()).map(|key| key.trim().to_string());
+    return Authorization { token: bearer_token(req), api_key };
+}
+
+// who a request is authenticated as
+// scopes is None for sessions, which may do everything the role of the user allows
+#[derive(Debug, Clone)]
+pub struct Principal
+{
+    pub user_id: u32,
+    pub scopes: Option<Vec<api_key::Scope>>,
+}
+
+pub fn unauthorized(message: &str) -> job::Error
+{
+    return job::Error::new(7, "ERR_UNAUTHORIZED".to_string(), message.to_string());
+}
+
+// the session of a token, if it is neither revoked nor expired
+pub fn find_session(token: &str, storage: &dyn storage::Storage) -> Result<Session, (StatusCode, job::Error)>
+{
+    match storage.session(&hash_token(token)).map_err(storage::unreadable)?
+    {
+        Some(session) if session.is_active() => Ok(session),
+        _ => Err((StatusCode::UNAUTHORIZED, unauthorized("Session is invalid or has expired."))),
+    }
+}
+
+// the user a request is signed in as, with its session or with an API key
+// requests without either are anonymous, requests with one that is not valid are rejected
+// sessions and API keys of deactivated users are not valid while they are deactivated
+// using an API key records when it was last used
+pub fn authenticate(authorization: &Authorization, storage: &dyn storage::Storage) -> Result<Option<Principal>, (StatusCode, job::Error)>
+{
+    let principal = if let Some(key) = &authorization.api_key
+    {
+        let api_key = api_key::find(key, storage)?;
+        // the last use is written like any change, so that it is not part of the transaction of another request
+        // the request goes on when it cannot be saved, it is only shown to the owner of the key
+        {
+            let _lock = storage.lock();
+            if let Err(error) = storage.touch_api_key(api_key.id, Utc::now())
+            {
+                log::warn!("Last use of API key {} could not be saved: {}", api_key.id, error);
+            }
+        }
+        Principal { user_id: api_key.user_id, scopes: Some(api_key.scopes) }
+    }
+    else
+    {
+        match &authorization.token
+        {
+            Some(token) => find_session(token, storage).map(|session| Principal { user_id: session.user_id, scopes: None })?,
+            None => return Ok(None),
+        }
+    };
+    match storage.users().iter().find(|user| user.id == principal.user_id)
+    {
+        Some(user) if !user.deactivated => Ok(Some(principal)),
+        _ => Err((StatusCode::UNAUTHORIZED, unauthorized("User is deactivated."))),
+    }
+}
\ No newline at end of file
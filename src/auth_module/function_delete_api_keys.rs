@@ -0,0 +1,45 @@
+use actix_web::{delete, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
+
+use crate::api_key;
+use crate::audit;
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+// revokes a key, which is kept so that it is still listed
+fn revoke_api_key(id: u32, caller: &permission::Caller, storage: &dyn storage::Storage) -> Result<api_key::ApiKey, (StatusCode, job::Error)>
+{
+    let _lock = storage.lock();
+    let mut api_key = match storage.api_keys(None).map_err(storage::unreadable)?.into_iter().find(|api_key| api_key.id == id)
+    {
+        Some(api_key) => api_key,
+        None => return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("API key {} not found.", id).to_string()))),
+    };
+
+    api_key.revoked = true;
+    let actor = caller.actor();
+    storage.transaction(&mut || {
+        storage.save_api_key(&api_key)?;
+        storage.append_event(&actor, audit::Change::api_key_revoked { api_key: api_key.clone() })
+    }).map_err(storage::internal)?;
+    log::info!("API key {} revoked", id);
+    return Ok(api_key);
+}
+
+// revokes an API key
+#[delete("/api-keys/{apiKeyId}", wrap = "permission::Require(permission::Permission::manage_users)")]
+#[allow(non_snake_case)]
+async fn delete_api_keys_apiKeyId(apiKeyId: web::Path<u32>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let id = apiKeyId.into_inner();
+    let caller = caller.into_inner();
+    match web::block(move || revoke_api_key(id, &caller, data_storage.get_ref())).await
+    {
+        Ok(Ok(api_key)) => HttpResponse::Ok().json(api_key),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), format!("API key {} could not be revoked.", id).to_string())),
+    }
+}
\ No newline at end of file
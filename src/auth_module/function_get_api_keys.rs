@@ -0,0 +1,27 @@
+use actix_web::{get, Responder, HttpResponse, web};
+use serde::Deserialize;
+
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+// used to obtain query data
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyQuery
+{
+    user_id: Option<u32>,
+}
+
+// lists the API keys, including revoked and expired ones, without the keys themselves
+#[get("/api-keys", wrap = "permission::Require(permission::Permission::manage_users)")]
+async fn get_api_keys(info: web::Query<ApiKeyQuery>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let user_id = info.user_id;
+    match web::block(move || data_storage.api_keys(user_id).map_err(storage::unreadable)).await
+    {
+        Ok(Ok(api_keys)) => HttpResponse::Ok().json(api_keys),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "API keys could not be listed.".to_string())),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use actix_web::{get, Responder, HttpResponse, HttpRequest, web};
+use actix_web::http::StatusCode;
+
+use crate::auth;
+use crate::job;
+use crate::user;
+use crate::storage;
+use crate::permission;
+
+// finds the user of a session or an API key
+fn current_user(authorization: auth::Authorization, storage: &dyn storage::Storage) -> Result<user::Account, (StatusCode, job::Error)>
+{
+    let principal = auth::authenticate(&authorization, storage)?
+        .ok_or((StatusCode::UNAUTHORIZED, auth::unauthorized("Authentication required.")))?;
+    let user = storage.users().iter().find(|user| user.id == principal.user_id).cloned();
+    let user = user.ok_or((StatusCode::UNAUTHORIZED, auth::unauthorized("Session is invalid or has expired.")))?;
+    return Ok(user::Account { role: permission::role_of(user.id, storage).map_err(storage::unreadable)?, user });
+}
+
+// returns the user signed in with the bearer token or the API key, with its role
+#[get("/me")]
+async fn get_me(req: HttpRequest, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let authorization = auth::authorization(&req);
+    match web::block(move || current_user(authorization, data_storage.get_ref())).await
+    {
+        Ok(Ok(user)) => HttpResponse::Ok().json(user),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Request could not be handled.".to_string())),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,77 @@
+use actix_web::{post, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
+use chrono::Utc;
+
+use crate::api_key;
+use crate::audit;
+use crate::auth;
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+fn invalid_argument(message: &str) -> (StatusCode, job::Error)
+{
+    (StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message.to_string()))
+}
+
+// creates a key for a user, the key can do what both its scopes and the role of the user allow
+fn create_api_key(body: api_key::PostApiKey, caller: &permission::Caller, storage: &dyn storage::Storage) -> Result<api_key::NewApiKey, (StatusCode, job::Error)>
+{
+    if body.scopes.is_empty()
+    {
+        return Err(invalid_argument("API key must have at least one scope."));
+    }
+    if body.expires_time.map_or(false, |expires_time| expires_time <= Utc::now())
+    {
+        return Err(invalid_argument("API key must expire in the future."));
+    }
+
+    let _lock = storage.lock();
+    if !storage.users().iter().any(|user| user.id == body.user_id)
+    {
+        return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", body.user_id).to_string())));
+    }
+
+    // keys are only removed with their user, so ids follow the highest one
+    let api_key_ids: Vec<u32> = storage.api_keys(None).map_err(storage::unreadable)?.iter().map(|api_key| api_key.id).collect();
+    let key = api_key::new_key();
+    let mut scopes: Vec<api_key::Scope> = vec![];
+    for scope in body.scopes.iter()
+    {
+        if !scopes.contains(scope) {scopes.push(*scope);}
+    }
+    let api_key = api_key::ApiKey
+    {
+        id: api_key_ids.iter().map(|id| id + 1).max().unwrap_or(0),
+        user_id: body.user_id,
+        name: body.name,
+        scopes,
+        created_time: Utc::now(),
+        expires_time: body.expires_time,
+        last_used_time: None,
+        revoked: false,
+        key_hash: auth::hash_token(&key),
+    };
+    let actor = caller.actor();
+    storage.transaction(&mut || {
+        storage.save_api_key(&api_key)?;
+        storage.append_event(&actor, audit::Change::api_key_created { api_key: api_key.clone() })
+    }).map_err(storage::internal)?;
+    log::info!("API key {} created for user {}", api_key.id, api_key.user_id);
+    return Ok(api_key::NewApiKey { api_key, key });
+}
+
+// creates an API key, the response is the only place the key is shown
+#[post("/api-keys", wrap = "permission::Require(permission::Permission::manage_users)")]
+async fn post_api_keys(body: web::Json<api_key::PostApiKey>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let caller = caller.into_inner();
+    match web::block(move || create_api_key(body.into_inner(), &caller, data_storage.get_ref())).await
+    {
+        Ok(Ok(output)) => HttpResponse::Ok().json(output),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "API key could not be created.".to_string())),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,158 @@
+use actix_web::{post, Responder, HttpResponse, HttpRequest, web};
+use actix_web::http::StatusCode;
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use crate::auth;
+use crate::audit;
+use crate::job;
+use crate::user;
+use crate::config;
+use crate::storage;
+use crate::permission;
+use crate::function_post_users;
+use crate::Arc;
+use crate::Mutex;
+
+// body of POST /register and POST /login
+#[derive(Debug, Deserialize)]
+pub struct Credentials
+{
+    name: String,
+    password: String,
+}
+
+// response of POST /login, the token is sent as "Authorization: Bearer <token>"
+#[derive(Debug, Serialize)]
+pub struct Login
+{
+    token: String,
+    expires_time: DateTime<Utc>,
+    user: user::User,
+}
+
+fn blocking_error() -> HttpResponse
+{
+    HttpResponse::InternalServerError().json(job::Error::new(6, "ERR_INTERNAL".to_string(), "Request could not be handled.".to_string()))
+}
+
+// creates a user with a password
+// the password is hashed before the storage is locked, since hashing is slow on purpose
+fn register(body: Credentials, storage: &dyn storage::Storage) -> Result<user::User, (StatusCode, job::Error)>
+{
+    if let Err(message) = user::check_name(&body.name)
+    {
+        return Err((StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message)));
+    }
+    if body.password.chars().count() < auth::MIN_PASSWORD_LENGTH
+    {
+        return Err((StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(),
+            format!("Password must have at least {} characters.", auth::MIN_PASSWORD_LENGTH).to_string())));
+    }
+    let password_hash = auth::hash_password(&body.password);
+
+    let _lock = storage.lock();
+    if user::name_taken(&storage.users(), &body.name, None)
+    {
+        return Err((StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(),
+            format!("User name '{}' already exists.", body.name).to_string())));
+    }
+
+    // the user and its password are stored together, the user is taken back if the password cannot be saved
+    let mut output: Option<user::User> = None;
+    let saved = storage.transaction(&mut || {
+        let user = function_post_users::create_user(body.name.clone(), user::Profile::default(), audit::ACTOR_ANONYMOUS, storage)?;
+        output = Some(user.clone());
+        storage.save_password(user.id, &password_hash)
+    });
+    if let Err(error) = saved
+    {
+        if let Some(user) = &output {function_post_users::forget_user(user, storage);}
+        return Err(storage::internal(error));
+    }
+    return Ok(output.unwrap());
+}
+
+// checks the password of a user and starts a session
+// unknown users and users without a password get the same error as a wrong password
+fn login(body: Credentials, config: &config::Config, storage: &dyn storage::Storage) -> Result<Login, (StatusCode, job::Error)>
+{
+    let user = storage.users().iter().find(|user| user.name == body.name).cloned();
+    let password_hash = match &user
+    {
+        Some(user) => storage.password_hash(user.id).map_err(storage::unreadable)?,
+        None => None,
+    };
+    let user = match (user, password_hash)
+    {
+        (Some(user), Some(password_hash)) if auth::verify_password(&body.password, &password_hash) => user,
+        _ => return Err((StatusCode::UNAUTHORIZED, auth::unauthorized("User name or password is incorrect."))),
+    };
+    if user.deactivated
+    {
+        return Err((StatusCode::FORBIDDEN, permission::forbidden("User is deactivated.")));
+    }
+
+    let token = auth::new_token();
+    let session = auth::Session::new(&token, user.id, config.auth.session_ttl);
+    {
+        let _lock = storage.lock();
+        storage.save_session(&session).map_err(storage::internal)?;
+    }
+    log::info!("User {} logged in", user.id);
+    return Ok(Login { token, expires_time: session.expires_time, user });
+}
+
+// revokes the session of the token, which cannot be used afterwards
+fn logout(token: Option<String>, storage: &dyn storage::Storage) -> Result<user::User, (StatusCode, job::Error)>
+{
+    let token = token.ok_or((StatusCode::UNAUTHORIZED, auth::unauthorized("Authentication required.")))?;
+    let mut session = auth::find_session(&token, storage)?;
+    session.revoked = true;
+    {
+        let _lock = storage.lock();
+        storage.save_session(&session).map_err(storage::internal)?;
+    }
+
+    let user = storage.users().iter().find(|user| user.id == session.user_id).cloned();
+    return user.ok_or((StatusCode::UNAUTHORIZED, auth::unauthorized("Session is invalid or has expired.")));
+}
+
+// registers a new user with a password
+#[post("/register")]
+async fn post_register(body: web::Json<Credentials>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    match web::block(move || register(body.into_inner(), data_storage.get_ref())).await
+    {
+        Ok(Ok(user)) => HttpResponse::Ok().json(user),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => blocking_error(),
+    }
+}
+
+// logs in with a name and a password, the session expires after config.auth.session_ttl seconds
+#[post("/login")]
+async fn post_login(body: web::Json<Credentials>, data_config: web::Data<Arc<Mutex<config::Config>>>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let config = data_config.lock().unwrap().clone();
+    match web::block(move || login(body.into_inner(), &config, data_storage.get_ref())).await
+    {
+        Ok(Ok(output)) => HttpResponse::Ok().json(output),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => blocking_error(),
+    }
+}
+
+// logs out, revoking the session of the bearer token
+#[post("/logout")]
+async fn post_logout(req: HttpRequest, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let token = auth::bearer_token(&req);
+    match web::block(move || logout(token, data_storage.get_ref())).await
+    {
+        Ok(Ok(user)) => HttpResponse::Ok().json(user),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => blocking_error(),
+    }
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+pub mod auth;
+pub mod api_key;
+pub mod permission;
+pub mod function_post_auth;
+pub mod function_get_me;
+pub mod function_post_api_keys;
+pub mod function_get_api_keys;
+pub mod function_delete_api_keys;
\ No newline at end of file
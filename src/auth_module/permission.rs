@@ -0,0 +1,191 @@
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use actix_web::{web, HttpMessage, HttpResponse};
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::StatusCode;
+use futures_util::future::LocalBoxFuture;
+
+use crate::auth;
+use crate::audit;
+use crate::config;
+use crate::config::Role;
+use crate::job;
+use crate::storage;
+
+// what a route needs, every guarded route asks for exactly one permission
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Permission
+{
+    view, // reading users, contests, jobs and their events
+    submit, // submitting jobs and custom runs
+    rejudge,
+    manage_contests,
+    manage_users, // renaming users, changing their profiles and their roles
+    edit_profile, // changing one's own profile
+    judge, // the protocol of oj-judge workers
+    administer, // export, import, the audit log, webhooks, usage quotas and stopping the server
+}
+
+// the permissions of each role, admins have every permission
+pub fn allows(role: Role, permission: Permission) -> bool
+{
+    match role
+    {
+        Role::admin => true,
+        Role::problem_setter => matches!(permission, Permission::view | Permission::edit_profile | Permission::submit | Permission::rejudge),
+        Role::contest_manager => matches!(permission, Permission::view | Permission::edit_profile | Permission::submit | Permission::manage_contests),
+        Role::contestant => matches!(permission, Permission::view | Permission::edit_profile | Permission::submit),
+        Role::observer => matches!(permission, Permission::view | Permission::edit_profile),
+    }
+}
+
+// root is an admin, other users are contestants until an admin gives them another role
+pub fn role_of(user_id: u32, storage: &dyn storage::Storage) -> Result<Role, storage::Error>
+{
+    Ok(match storage.role(user_id)?
+    {
+        Some(role) => role,
+        None if user_id == 0 => Role::admin,
+        None => Role::contestant,
+    })
+}
+
+pub fn forbidden(message: &str) -> job::Error
+{
+    return job::Error::new(8, "ERR_FORBIDDEN".to_string(), message.to_string());
+}
+
+// who a guarded request comes from, handlers of guarded routes get it with web::ReqData<permission::Caller>
+// principal is None for anonymous requests, which act with config.auth.anonymous_role
+#[derive(Debug, Clone)]
+pub struct Caller
+{
+    pub principal: Option<auth::Principal>,
+    pub role: Role,
+}
+
+impl Caller
+{
+    // API keys are also limited to the permissions of their scopes
+    pub fn has(&self, permission: Permission) -> bool
+    {
+        let scopes = self.principal.as_ref().and_then(|principal| principal.scopes.as_ref());
+        return allows(self.role, permission) && scopes.map_or(true, |scopes| scopes.iter().any(|scope| scope.permission() == permission));
+    }
+
+    // whether the request may change the user and see its private details, as the user itself or by managing users
+    pub fn acts_for(&self, user_id: u32) -> bool
+    {
+        return self.principal.as_ref().map_or(false, |principal| principal.user_id == user_id) || self.has(Permission::manage_users);
+    }
+    // who the request is written as in the audit log
+    pub fn actor(&self) -> String
+    {
+        return match &self.principal
+        {
+            Some(principal) => audit::user_actor(principal.user_id),
+            None => audit::ACTOR_ANONYMOUS.to_string(),
+        };
+    }
+}
+
+// checks the permission against the role a request acts with, from its session, its API key or config.auth
+fn check_request(permission: Permission, authorization: auth::Authorization, auth_config: &config::Auth,
+    storage: &dyn storage::Storage) -> Result<Caller, (StatusCode, job::Error)>
+{
+    let caller = match auth::authenticate(&authorization, storage)
+    {
+        Ok(Some(principal)) => Caller { role: role_of(principal.user_id, storage).map_err(storage::unreadable)?, principal: Some(principal) },
+        Ok(None) if auth_config.required => return Err((StatusCode::UNAUTHORIZED, auth::unauthorized("Authentication required."))),
+        Ok(None) => Caller { principal: None, role: auth_config.anonymous_role },
+        Err(error) => return Err(error),
+    };
+    if !allows(caller.role, permission)
+    {
+        return Err((StatusCode::FORBIDDEN, forbidden(&format!("Role {:?} does not have permission {:?}.", caller.role, permission))));
+    }
+    if !caller.has(permission)
+    {
+        return Err((StatusCode::FORBIDDEN, forbidden(&format!("API key does not have a scope for permission {:?}.", permission))));
+    }
+    Ok(caller)
+}
+
+async fn check(permission: Permission, req: &ServiceRequest) -> Result<Caller, (StatusCode, job::Error)>
+{
+    let authorization = auth::authorization(req.request());
+    let auth_config = match req.app_data::<web::Data<Arc<Mutex<config::Config>>>>()
+    {
+        Some(data_config) => data_config.lock().unwrap().auth.clone(),
+        None => config::Auth::default(),
+    };
+    let data_storage = req.app_data::<web::Data<dyn storage::Storage>>().expect("storage is registered").clone();
+
+    // the storage may wait for a free database connection
+    match web::block(move || check_request(permission, authorization, &auth_config, data_storage.get_ref())).await
+    {
+        Ok(checked) => checked,
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Permissions could not be checked.".to_string()))),
+    }
+}
+
+// middleware guarding a route, e.g. #[post("/contests", wrap = "permission::Require(permission::Permission::manage_contests)")]
+// requests whose role lacks the permission are answered with ERR_FORBIDDEN before the handler runs, the others carry their Caller
+pub struct Require(pub Permission);
+
+impl<S, B> Transform<S, ServiceRequest> for Require
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = RequireMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future
+    {
+        ready(Ok(RequireMiddleware { service: Rc::new(service), permission: self.0 }))
+    }
+}
+
+pub struct RequireMiddleware<S>
+{
+    service: Rc<S>,
+    permission: Permission,
+}
+
+impl<S, B> Service<ServiceRequest> for RequireMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future
+    {
+        let service = self.service.clone();
+        let permission = self.permission;
+        Box::pin(async move {
+            match check(permission, &req).await
+            {
+                Ok(caller) =>
+                {
+                    req.extensions_mut().insert(caller);
+                    service.call(req).await.map(|res| res.map_into_left_body())
+                }
+                Err((status, error)) => Ok(req.into_response(HttpResponse::build(status).json(error)).map_into_right_body()),
+            }
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,248 @@
+// judge worker for distributed mode (oj --distributed)
+// registers with the server, pulls queued jobs, judges them locally and reports the results back
+
+use clap::Parser;
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use std::path::Path;
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// the judging code is shared with the server
+#[allow(dead_code)]
+#[path = "../others_module/config.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../jobs_module/post_job.rs"]
+mod post_job;
+#[allow(dead_code)]
+#[path = "../jobs_module/job.rs"]
+mod job;
+#[path = "../jobs_module/compare_functions.rs"]
+mod compare_functions;
+#[path = "../jobs_module/spj.rs"]
+mod spj;
+#[allow(dead_code)]
+#[path = "../jobs_module/judge.rs"]
+mod judge;
+#[allow(dead_code)]
+#[path = "../worker_module/worker.rs"]
+mod worker;
+
+#[derive(Parser, Debug)]
+struct Cli
+{
+    #[clap(short, long, default_value = "http://127.0.0.1:12345")]
+    server: String,
+    #[clap(short, long, default_value = "oj-judge")]
+    name: String,
+    #[clap(long, default_value = "judge-cache")]
+    cache_dir: String, // downloaded test data, named by checksum
+    #[clap(long, default_value_t = 5)]
+    heartbeat_interval: u64, // in seconds
+    #[clap(long)]
+    token: Option<String>, // session of a user allowed to judge, needed when the config of the server has auth and its anonymous_role may not judge
+}
+
+// registers the worker with the server and returns its id
+// keeps retrying until the server is reachable
+fn register(client: &Client, server: &str, name: &str) -> u32
+{
+    loop
+    {
+        match client.post(format!("{}/workers", server))
+            .json(&worker::RegisterWorker { name: name.to_string() })
+            .send()
+        {
+            Ok(response) if response.status().is_success() =>
+            {
+                if let Ok(registered) = response.json::<worker::Worker>()
+                {
+                    log::info!("Registered as worker {}", registered.id);
+                    return registered.id;
+                }
+            }
+            Ok(response) => log::warn!("Registration refused: HTTP {}", response.status()),
+            Err(err) => log::warn!("Registration failed: {}", err),
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+// returns the local path of a test data file, downloading it if it is not cached yet
+fn fetch_data(client: &Client, server: &str, cache_dir: &str, checksum: &str) -> Option<String>
+{
+    let path = Path::new(cache_dir).join(checksum);
+    if path.exists()
+    {
+        return Some(path.to_str()?.to_string());
+    }
+
+    let response = client.get(format!("{}/workers/data/{}", server, checksum)).send().ok()?;
+    if !response.status().is_success()
+    {
+        log::warn!("Data {} could not be downloaded: HTTP {}", checksum, response.status());
+        return None;
+    }
+    let bytes = response.bytes().ok()?;
+
+    // do not cache a file that was corrupted on the way
+    if worker::checksum(&bytes) != checksum
+    {
+        log::warn!("Data {} does not match its checksum", checksum);
+        return None;
+    }
+    std::fs::write(&path, &bytes).ok()?;
+    return Some(path.to_str()?.to_string());
+}
+
+// downloads the files of the special judge and makes them executable, so that a checker can be the command itself
+fn fetch_checker(client: &Client, server: &str, cache_dir: &str, checksum: &str) -> Option<String>
+{
+    let path = fetch_data(client, server, cache_dir, checksum)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).ok()?;
+    return Some(path);
+}
+
+// judges a job and reports each case and the final result to the server
+fn judge_assignment(client: &Client, cli: &Cli, worker_id: u32, mut assignment: worker::Assignment, dir: &str)
+{
+    let job_url = format!("{}/workers/{}/jobs/{}", cli.server, worker_id, assignment.job_id);
+
+    // replaces the paths of the test data on the server with the cached copies
+    let mut missing_data = assignment.data.len() != assignment.problem.cases.len();
+    for (case, data) in assignment.problem.cases.iter_mut().zip(assignment.data.iter())
+    {
+        match (fetch_data(client, &cli.server, &cli.cache_dir, &data.input_checksum),
+            fetch_data(client, &cli.server, &cli.cache_dir, &data.answer_checksum))
+        {
+            (Some(input_file), Some(answer_file)) =>
+            {
+                case.input_file = input_file;
+                case.answer_file = answer_file;
+            }
+            _ => missing_data = true,
+        }
+    }
+    if let Some(arguments) = assignment.problem.misc.special_judge.as_mut()
+    {
+        for (argument, checksum) in arguments.iter_mut().zip(assignment.checker_data.iter())
+        {
+            let checksum = match checksum
+            {
+                Some(checksum) => checksum,
+                None => continue,
+            };
+            match fetch_checker(client, &cli.server, &cli.cache_dir, checksum)
+            {
+                Some(path) => *argument = path,
+                None => missing_data = true,
+            }
+        }
+    }
+
+    let report = if missing_data
+    {
+        worker::JobReport { result: job::PossibleResult::SystemError, score: 0.0, cases: vec![] }
+    }
+    else
+    {
+        log::info!("Judging job {}", assignment.job_id);
+        let (result, score, cases) = judge::judge_submission(&assignment.submission.source_code,
+            &assignment.language, &assignment.problem, dir, &mut |case| {
+                if let Err(err) = client.post(format!("{}/progress", job_url)).json(case).send()
+                {
+                    log::warn!("Progress of job {} could not be reported: {}", assignment.job_id, err);
+                }
+            });
+        worker::JobReport { result, score, cases }
+    };
+
+    match client.post(format!("{}/result", job_url)).json(&report).send()
+    {
+        Ok(response) if response.status().is_success() => log::info!("Job {} finished", assignment.job_id),
+        // the server may have given the job to another worker in the meantime
+        Ok(response) => log::warn!("Result of job {} refused: HTTP {}", assignment.job_id, response.status()),
+        Err(err) => log::warn!("Result of job {} could not be reported: {}", assignment.job_id, err),
+    }
+}
+
+fn main()
+{
+    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+
+    let cli = Cli::parse();
+    let mut headers = HeaderMap::new();
+    if let Some(token) = &cli.token
+    {
+        headers.insert(AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).expect("token is not a valid header value"));
+    }
+    let client = Client::builder().default_headers(headers).build().expect("HTTP client could not be created");
+
+    std::fs::create_dir_all(&cli.cache_dir).expect("cache directory could not be created");
+
+    let worker_id = Arc::new(Mutex::new(register(&client, &cli.server, &cli.name)));
+
+    // sends heartbeats in the background, registers again if the server forgot about this worker
+    {
+        let client = client.clone();
+        let server = cli.server.clone();
+        let name = cli.name.clone();
+        let worker_id = worker_id.clone();
+        let interval = Duration::from_secs(cli.heartbeat_interval);
+        std::thread::spawn(move || loop
+        {
+            std::thread::sleep(interval);
+            let id = *worker_id.lock().unwrap();
+            match client.post(format!("{}/workers/{}/heartbeat", server, id)).send()
+            {
+                Ok(response) if response.status() == StatusCode::NOT_FOUND =>
+                {
+                    let new_id = register(&client, &server, &name);
+                    *worker_id.lock().unwrap() = new_id;
+                }
+                Ok(_) => {}
+                Err(err) => log::warn!("Heartbeat failed: {}", err),
+            }
+        });
+    }
+
+    // each worker process uses its own temporary directory
+    let dir = format!("TMPDIR_{}", std::process::id());
+
+    loop
+    {
+        let id = *worker_id.lock().unwrap();
+
+        // long poll, the server answers with no content if no job was queued in time
+        let response = match client.post(format!("{}/workers/{}/poll", cli.server, id))
+            .timeout(Duration::from_secs(120))
+            .send()
+        {
+            Ok(response) => response,
+            Err(err) =>
+            {
+                log::warn!("Poll failed: {}", err);
+                std::thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+
+        if response.status() == StatusCode::NO_CONTENT {continue;}
+        if !response.status().is_success()
+        {
+            // e.g. the worker was declared dead, wait for the heartbeat thread to register again
+            std::thread::sleep(Duration::from_secs(1));
+            continue;
+        }
+
+        match response.json::<worker::Assignment>()
+        {
+            Ok(assignment) => judge_assignment(&client, &cli, id, assignment, &dir),
+            Err(err) => log::warn!("Invalid assignment: {}", err),
+        }
+    }
+}
@@ -1,8 +1,11 @@
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
 use chrono::SecondsFormat;
+use std::i64::MAX;
 
 use crate::user;
+use crate::team;
+use crate::config;
 
 // serialize the precision of time is displayed up to milliseconds
 fn serialize_datetime<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
@@ -20,7 +23,11 @@ pub struct GetContest
     pub from: DateTime<Utc>,
     pub to: DateTime<Utc>,
     pub problem_ids: Vec<u32>,
+    #[serde(default)]
     pub user_ids: Vec<u32>,
+    // contests with teams take their users from the teams, user_ids is then left empty
+    #[serde(default)]
+    pub team_ids: Vec<u32>,
     pub submission_limit: u32,
 }
 
@@ -42,6 +49,43 @@ pub struct RankInfo
     pub submission_count: u32,
 }
 
+impl RankInfo
+{
+    // the progress of a user that has not submitted anything yet, ranklists leave out profiles
+    pub fn new(user: user::User, problem_count: usize) -> RankInfo
+    {
+        RankInfo
+        {
+            user: user.summary(),
+            rank: 0,
+            scores: vec![0.0; problem_count],
+
+            highest_scores: vec![0.0; problem_count],
+            latest_scores: vec![0.0; problem_count],
+
+            competitive_score_sum: 0.0,
+            shortest_times: vec![vec![MAX; 20]; problem_count], // possible error
+
+            latest_submission: DateTime::<Utc>::MAX_UTC,
+            score: 0,
+            submission_count: 0,
+        }
+    }
+}
+
+// a row of the ranklist of a contest with teams
+// the progress of a team comes from the jobs of all its members
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamRankInfo
+{
+    pub team: team::Roster,
+    pub rank: u32,
+    pub scores: Vec<f32>,
+    pub score: u32,
+    pub submission_count: u32,
+    pub latest_submission: DateTime<Utc>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Contest
 {
@@ -54,7 +98,64 @@ pub struct Contest
 
     pub problem_ids: Vec<u32>,
     pub user_ids: Vec<u32>,
+    // the teams registered for the contest, their members are in user_ids
+    // the ranklist of a contest with teams has a row for each team instead of each user
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub team_ids: Vec<u32>,
     pub submission_limit: u32,
 
     pub users: Vec<RankInfo>,
+
+    // shortest time of each case among the submissions of the contest, used for dynamic ranking
+    // indexed like the problems of the config, and not shown in responses
+    #[serde(skip)]
+    pub case_times: Vec<Vec<i64>>,
+}
+
+// case times of a contest without submissions
+// each case time (in microseconds) is set to max i64 at the beginning
+pub fn empty_case_times(problems: &Vec<config::Problem>) -> Vec<Vec<i64>>
+{
+    let mut case_times: Vec<Vec<i64>> = vec![];
+    for problem in problems
+    {
+        case_times.push(vec![MAX; problem.cases.len()]);
+    }
+    return case_times;
+}
+
+// takes the users of a contest with teams from the current members of its teams, after a team changed
+// users that stay keep their rows, users that joined a team get a new row and users that left a team lose theirs
+// fails if a user is in more than one of the teams, so that its jobs count for a single team
+pub fn sync_team_members(contest: &mut Contest, teams: &[team::Team], users: &[user::User]) -> Result<(), String>
+{
+    let mut user_ids: Vec<u32> = vec![];
+    for team in contest.team_ids.iter().filter_map(|team_id| teams.iter().find(|team| team.id == *team_id))
+    {
+        for user_id in team.member_ids.iter()
+        {
+            if user_ids.contains(user_id)
+            {
+                return Err(format!("User {} would be in more than one team of contest {}.", user_id, contest.id));
+            }
+            user_ids.push(*user_id);
+        }
+    }
+
+    let mut rows: Vec<RankInfo> = vec![];
+    for user_id in user_ids.iter()
+    {
+        match contest.users.iter().find(|row| row.user.id == *user_id)
+        {
+            Some(row) => rows.push(row.clone()),
+            None => match users.iter().find(|user| user.id == *user_id)
+            {
+                Some(user) => rows.push(RankInfo::new(user.clone(), contest.problem_ids.len())),
+                None => continue,
+            },
+        }
+    }
+    contest.user_ids = rows.iter().map(|row| row.user.id).collect();
+    contest.users = rows;
+    return Ok(());
 }
\ No newline at end of file
@@ -5,22 +5,18 @@ use std::sync::Arc;
 
 use crate::config;
 use crate::contest;
+use crate::team;
+use crate::user;
 use crate::job;
-use crate::CASE_TIMES_LIST;
-use crate::CONTEST_LIST;
-
-use crate::CONNECTION;
-use crate::sql;
+use crate::recovery;
+use crate::storage;
+use crate::permission;
 
 // gets list of all contests
-#[get("/contests")]
-async fn get_contests() -> impl Responder
+#[get("/contests", wrap = "permission::Require(permission::Permission::view)")]
+async fn get_contests(data_storage: web::Data<dyn storage::Storage>) -> impl Responder
 {
-    let connection = CONNECTION.lock().unwrap();
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-
-    // if sql storage mode is on, then load info from the database
-    sql::get_contest(&connection, &mut lock_contest_list);
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = data_storage.contests();
 
     // retrieves each contest and stores into a vector, which is then returned
     let mut list: Vec<contest::Contest> = vec![];
@@ -32,11 +28,10 @@ async fn get_contests() -> impl Responder
 }
 
 // gets information about a contest given its id
-#[get("/contests/{contestId}")]
+#[get("/contests/{contestId}", wrap = "permission::Require(permission::Permission::view)")]
 #[allow(non_snake_case)]
-async fn get_contests_contestId(contestId: web::Path<u32>) -> impl Responder 
+async fn get_contests_contestId(contestId: web::Path<u32>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
 {
-    let connection = CONNECTION.lock().unwrap();
     // contest cannot be 0
     // if id is 0 then return error
     if contestId.to_owned() == 0
@@ -45,10 +40,7 @@ async fn get_contests_contestId(contestId: web::Path<u32>) -> impl Responder
             new(1, "ERR_INVALID_ARGUMENT".to_string(), "Invalid contest id".to_string()));
     }
 
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-
-    // if sql storage mode is on, then load info from the database
-    sql::get_contest(&connection, &mut lock_contest_list);
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = data_storage.contests();
 
     // if a contest matches the provided id, then return it
     for contest in lock_contest_list.iter()
@@ -102,26 +94,24 @@ fn get_sum(scores: &Vec<f32>) -> u32
 
 // used for dynamic ranking mode
 // gets the competitve sum score of each user in a competition 
-// based on its personal time compared to the best time in each case
-fn get_competitive_sum(personal_list: &Vec<Vec<i64>>, problems: &Vec<config::Problem>, scores: &mut Vec<f32>) -> u32
+// based on its personal time compared to the best time in each case of the contest
+fn get_competitive_sum(personal_list: &Vec<Vec<i64>>, case_times: &Vec<Vec<i64>>, problems: &Vec<config::Problem>, scores: &mut Vec<f32>) -> u32
 {
     let mut competitive_sum = 0;
 
-    let lock_times_list = CASE_TIMES_LIST.lock().unwrap();
-
     // loops through each problem and each case
-    for i in 0..lock_times_list.len()
+    for i in 0..case_times.len()
     {
         // only sums if dynamic ranking mode is on   
         if let Some(competitive_ratio) = problems[i].misc.dynamic_ranking_ratio
         {
             let mut problem_competitive_sum: f32 = 0.0;
-            for j in 0..lock_times_list[i].len()
+            for j in 0..case_times[i].len()
             {
                 // calculates competitive scores for each case and adds to total sum
                 let case_score = problems[i].cases[j].score;
 
-                let ratio = lock_times_list[i][j] as f32 / personal_list[i][j] as f32; 
+                let ratio = case_times[i][j] as f32 / personal_list[i][j] as f32; 
                 competitive_sum += (case_score * competitive_ratio * ratio) as u32;
                 problem_competitive_sum += case_score * competitive_ratio * ratio;
             }
@@ -132,67 +122,37 @@ fn get_competitive_sum(personal_list: &Vec<Vec<i64>>, problems: &Vec<config::Pro
     return competitive_sum;
 }
 
-// gets the ranklist for a selected contest
-#[get("/contests/{contestId}/ranklist")]
-#[allow(non_snake_case)]
-async fn get_contests_contestId_ranklist(contestId: web::Path<u32>, info: web::Query<AuthRequest>, data_config: web::Data<Arc<Mutex<config::Config>>>) -> impl Responder 
-{   
-    let config = data_config.lock().unwrap();
-    let connection = CONNECTION.lock().unwrap();
-    
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-
-    // if sql storage mode is on, then load info from the database
-    sql::get_contest(&connection, &mut lock_contest_list);
-
-    // return error if contest is not found
-    if contestId.to_owned() as usize >= lock_contest_list.len()
-    {
-        return HttpResponse::NotFound().json(job::Error::
-            new(3, "ERR_NOT_FOUND".to_string(), format!("Contest {} not found.", contestId.to_owned()).to_string()));
-    }
-
-    // obtains scoring rule and tiebreaker from query 
-    let mut scoring_rule = ScoringRule::latest; // latest set as default
-    let mut tie_breaker = TieBreaker::none;
-
-    // update if information provided (otherwise keep default)
-    if let Some(new_scoring_rule) = info.scoring_rule.clone()
-    {
-        scoring_rule = new_scoring_rule;
-    }
-    if let Some(new_tie_breaker) = info.tie_breaker.clone()
-    {
-        tie_breaker = new_tie_breaker;
-    }
-
-    let contest = &mut lock_contest_list[(contestId.to_owned()) as usize];
+// computes the score of each row of a ranklist with the scoring rule, then sorts the rows and gives them their rank
+fn rank(users: &mut Vec<contest::RankInfo>, case_times: &Vec<Vec<i64>>, problems: &Vec<config::Problem>, scoring_rule: &ScoringRule,
+    tie_breaker: &TieBreaker)
+{
+    if users.is_empty() {return;}
 
     // update score sums
 
-    if scoring_rule == ScoringRule::highest
+    if *scoring_rule == ScoringRule::highest
     {
-        for user in &mut contest.users
+        for user in users.iter_mut()
         {
             user.score = get_sum(&user.highest_scores); // using highest_scores to calculate
             user.scores = user.highest_scores.clone();
-            user.score += get_competitive_sum(&user.shortest_times, &config.problems, &mut user.scores);
+            user.score += get_competitive_sum(&user.shortest_times, case_times, problems, &mut user.scores);
         }
     }
     else
     {
-        for user in &mut contest.users
+        for user in users.iter_mut()
         {
             user.score = get_sum(&user.latest_scores); // using latest_scores to calculate
             user.scores = user.latest_scores.clone();
-            user.score += get_competitive_sum(&user.shortest_times, &config.problems, &mut user.scores);
+            user.score += get_competitive_sum(&user.shortest_times, case_times, problems, &mut user.scores);
         }
     }
     
     // sorts by score, then latest submission, then user id
-    if tie_breaker == TieBreaker::submission_time
+    if *tie_breaker == TieBreaker::submission_time
     {
-        contest.users.sort_by(|a, b| { 
+        users.sort_by(|a, b| { 
             b.score.cmp(&a.score)
             .then_with(|| a.latest_submission.cmp(&b.latest_submission))
             .then_with(|| a.user.id.cmp(&b.user.id))
@@ -200,24 +160,24 @@ async fn get_contests_contestId_ranklist(contestId: web::Path<u32>, info: web::Q
 
         // give a rank number to each user
         // if tied then give same rank
-        contest.users[0].rank = 1;
-        for i in 1..contest.users.len()
+        users[0].rank = 1;
+        for i in 1..users.len()
         {
-            if contest.users[i].score == contest.users[i-1].score && 
-            contest.users[i].latest_submission == contest.users[i-1].latest_submission
+            if users[i].score == users[i-1].score && 
+            users[i].latest_submission == users[i-1].latest_submission
             {
-                contest.users[i].rank = contest.users[i-1].rank
+                users[i].rank = users[i-1].rank
             }
             else {
-                contest.users[i].rank = i as u32 + 1;
+                users[i].rank = i as u32 + 1;
             }
         }
 
     }
     // sorets by score, then submission count, then user id
-    else if tie_breaker == TieBreaker::submission_count 
+    else if *tie_breaker == TieBreaker::submission_count 
     {
-        contest.users.sort_by(|a, b| {
+        users.sort_by(|a, b| {
             b.score.cmp(&a.score)
             .then_with(|| a.submission_count.cmp(&b.submission_count))
             .then_with(|| a.user.id.cmp(&b.user.id))
@@ -225,57 +185,188 @@ async fn get_contests_contestId_ranklist(contestId: web::Path<u32>, info: web::Q
 
         // give a rank number to each user
         // if tied then give same rank
-        contest.users[0].rank = 1;
-        for i in 1..contest.users.len()
+        users[0].rank = 1;
+        for i in 1..users.len()
         {
-            if contest.users[i].score == contest.users[i-1].score && 
-            contest.users[i].submission_count == contest.users[i-1].submission_count
+            if users[i].score == users[i-1].score && 
+            users[i].submission_count == users[i-1].submission_count
             {
-                contest.users[i].rank = contest.users[i-1].rank
+                users[i].rank = users[i-1].rank
             }
             else {
-                contest.users[i].rank = i as u32 + 1;
+                users[i].rank = i as u32 + 1;
             }
         }
     }
     // sorts by score, then user id
-    else if tie_breaker == TieBreaker::user_id 
+    else if *tie_breaker == TieBreaker::user_id 
     {
-        contest.users.sort_by(|a, b| {
+        users.sort_by(|a, b| {
             b.score.cmp(&a.score)
             .then_with(|| a.user.id.cmp(&b.user.id))
         });
 
         // no need to account for ties here because each user id is unique
-        for i in 0..contest.users.len()
+        for i in 0..users.len()
         {
-            contest.users[i].rank = i as u32 + 1;
+            users[i].rank = i as u32 + 1;
         }
     }
     // sorts by score, then user id
     else 
     {
-        contest.users.sort_by(|a, b| {
+        users.sort_by(|a, b| {
             b.score.cmp(&a.score)
             .then_with(|| a.user.id.cmp(&b.user.id))
         });
         
         // give a rank number to each user
         // if tied then give same rank
-        contest.users[0].rank = 1;
-        for i in 1..contest.users.len()
+        users[0].rank = 1;
+        for i in 1..users.len()
         {
-            if contest.users[i].score == contest.users[i-1].score
+            if users[i].score == users[i-1].score
             {
-                contest.users[i].rank = contest.users[i-1].rank
+                users[i].rank = users[i-1].rank
             }
             else {
-                contest.users[i].rank = i as u32 + 1;
+                users[i].rank = i as u32 + 1;
+            }
+        }
+    }
+}
+
+// users left out of ranklists, see user::User::deactivated
+fn deactivated_user_ids(storage: &dyn storage::Storage) -> Vec<u32>
+{
+    storage.users().iter().filter(|user| user.deactivated).map(|user| user.id).collect()
+}
+
+// the ranklist of a contest with teams, with a row for each team
+// the jobs of all members count for their team, and the personal best times of a team are the best of its members
+// deactivated members are left out of the rosters, but their jobs still count for their team
+fn team_ranklist(contest: &contest::Contest, config: &config::Config, scoring_rule: &ScoringRule, tie_breaker: &TieBreaker,
+    storage: &dyn storage::Storage) -> Result<Vec<contest::TeamRankInfo>, storage::Error>
+{
+    let teams = storage.teams()?;
+    let users: Vec<user::User> = storage.users().iter().filter(|user| !user.deactivated).cloned().collect();
+
+    // the rows are ranked like the rows of users, with the id and the name of their team as user
+    let mut rows: Vec<contest::RankInfo> = vec![];
+    let mut rosters: Vec<team::Roster> = vec![];
+    {
+        let jobs = storage.query_jobs(&storage::JobQuery { contest_id: Some(contest.id), ..Default::default() })?;
+        for team in contest.team_ids.iter().filter_map(|team_id| teams.iter().find(|team| team.id == *team_id))
+        {
+            let member_ids: Vec<u32> = team.member_ids.iter().filter(|user_id| contest.user_ids.contains(user_id)).copied().collect();
+            let mut row = contest::RankInfo::new(user::User::new(team.id, team.name.clone()), contest.problem_ids.len());
+            recovery::reconcile_progress(&mut row, contest.id, &contest.problem_ids, &member_ids, &jobs);
+
+            let members = contest.users.iter().filter(|rank_info| member_ids.contains(&rank_info.user.id));
+            for (index, member) in members.enumerate()
+            {
+                if index == 0
+                {
+                    row.shortest_times = member.shortest_times.clone();
+                    continue;
+                }
+                for (times, member_times) in row.shortest_times.iter_mut().zip(member.shortest_times.iter())
+                {
+                    for (time, member_time) in times.iter_mut().zip(member_times.iter())
+                    {
+                        *time = (*time).min(*member_time);
+                    }
+                }
             }
+            rows.push(row);
+            rosters.push(team::Roster::new(team, &users));
         }
     }
+    rank(&mut rows, &contest.case_times, &config.problems, scoring_rule, tie_breaker);
+
+    return Ok(rows.into_iter().map(|row| contest::TeamRankInfo
+    {
+        team: rosters.iter().find(|roster| roster.id == row.user.id).cloned().expect("every row has a team"),
+        rank: row.rank,
+        scores: row.scores,
+        score: row.score,
+        submission_count: row.submission_count,
+        latest_submission: row.latest_submission,
+    }).collect());
+}
+
+// the rank and the score of a user in a contest, with the default scoring rule and tie breaker of the ranklist
+// in contests with teams it is the row of the team of the user, None if the user is not in the contest
+pub fn standing(contest: &contest::Contest, user_id: u32, config: &config::Config, storage: &dyn storage::Storage)
+    -> Result<Option<(u32, u32)>, storage::Error>
+{
+    if !contest.team_ids.is_empty()
+    {
+        let rows = team_ranklist(contest, config, &ScoringRule::latest, &TieBreaker::none, storage)?;
+        let row = rows.iter().find(|row| row.team.members.iter().any(|member| member.id == user_id));
+        return Ok(row.map(|row| (row.rank, row.score)));
+    }
+    let deactivated = deactivated_user_ids(storage);
+    let mut users: Vec<contest::RankInfo> = contest.users.iter().filter(|rank_info| !deactivated.contains(&rank_info.user.id)).cloned().collect();
+    rank(&mut users, &contest.case_times, &config.problems, &ScoringRule::latest, &TieBreaker::none);
+    let row = users.iter().find(|row| row.user.id == user_id);
+    return Ok(row.map(|row| (row.rank, row.score)));
+}
+
+// gets the ranklist for a selected contest
+// contests with teams have a row for each team, see team_ranklist, deactivated users are left out
+#[get("/contests/{contestId}/ranklist", wrap = "permission::Require(permission::Permission::view)")]
+#[allow(non_snake_case)]
+async fn get_contests_contestId_ranklist(contestId: web::Path<u32>, info: web::Query<AuthRequest>, data_config: web::Data<Arc<Mutex<config::Config>>>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
+{   
+    // the config is copied so that requests changing the contests are not blocked behind it
+    let config = data_config.lock().unwrap().clone();
+    let deactivated = deactivated_user_ids(data_storage.get_ref());
+    
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = data_storage.contests();
+
+    // return error if contest is not found
+    if contestId.to_owned() as usize >= lock_contest_list.len()
+    {
+        return HttpResponse::NotFound().json(job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), format!("Contest {} not found.", contestId.to_owned()).to_string()));
+    }
+
+    // obtains scoring rule and tiebreaker from query 
+    let mut scoring_rule = ScoringRule::latest; // latest set as default
+    let mut tie_breaker = TieBreaker::none;
+
+    // update if information provided (otherwise keep default)
+    if let Some(new_scoring_rule) = info.scoring_rule.clone()
+    {
+        scoring_rule = new_scoring_rule;
+    }
+    if let Some(new_tie_breaker) = info.tie_breaker.clone()
+    {
+        tie_breaker = new_tie_breaker;
+    }
+
+    let contest = &lock_contest_list[(contestId.to_owned()) as usize];
+    if !contest.team_ids.is_empty()
+    {
+        // the contest is copied, so that the contest list is not held while the job list is locked
+        let contest = contest.clone();
+        drop(lock_contest_list);
+        return match team_ranklist(&contest, &config, &scoring_rule, &tie_breaker, data_storage.get_ref())
+        {
+            Ok(rows) => HttpResponse::Ok().json(rows),
+            Err(error) =>
+            {
+                let (status, error) = storage::unreadable(error);
+                HttpResponse::build(status).json(error)
+            }
+        };
+    }
+    let mut users: Vec<contest::RankInfo> = contest.users.iter().filter(|rank_info| !deactivated.contains(&rank_info.user.id)).cloned().collect();
+    rank(&mut users, &contest.case_times, &config.problems, &scoring_rule, &tie_breaker);
 
-    log::info!("{:?}", contest.users.clone());
+    log::info!("{:?}", users);
 
-    return HttpResponse::Ok().json(contest.users.clone());
+    return HttpResponse::Ok().json(users);
 }
\ No newline at end of file
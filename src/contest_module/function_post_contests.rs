@@ -1,40 +1,27 @@
 use actix_web::{post, Responder, HttpResponse, web};
-use std::i64::MAX;
+use actix_web::http::StatusCode;
 use std::sync::Mutex;
 use std::sync::Arc;
 use std::collections::HashMap;
-use chrono::{DateTime, Utc};
 
 use crate::contest;
 use crate::job;
 use crate::config;
 use crate::user;
-use crate::sql;
-use crate::Connection;
-
-use crate::CONTEST_LIST;
-use crate::CONTEST_ID_COUNT;
-
-use crate::USER_ID_COUNT;
-use crate::USER_LIST;
-
-use crate::CONNECTION;
+use crate::storage;
+use crate::storage::Counter;
+use crate::audit;
+use crate::permission;
 
 // checks if all problems and users exist
-fn check_valid(body: &web::Json<contest::GetContest>, data_config: &web::Data<Arc<Mutex<config::Config>>>, connection: &Connection) -> bool
+fn check_valid(body: &contest::GetContest, config: &config::Config, lock_user_list: &[user::User]) -> bool
 {
-    let mut lock_user_id_count = USER_ID_COUNT.lock().unwrap();
-    
-    // if SQL mode is activated, then load from database
-    sql::get_user_count(&connection, &mut lock_user_id_count);
-    
-    // checks if each user id is valid
+    // checks if each user id is valid, ids of deleted users are not
     for id in body.user_ids.iter()
     {
-        if *id >= *lock_user_id_count {return false;}
+        if !lock_user_list.iter().any(|user| user.id == *id) {return false;}
     }
     //check if each problem id is valid
-    let config = data_config.lock().unwrap();
     for id in body.problem_ids.iter()
     {
         if *id >= config.problems.len() as u32 {return false;}
@@ -43,7 +30,7 @@ fn check_valid(body: &web::Json<contest::GetContest>, data_config: &web::Data<Ar
 }
 
 // uses hash maps to check if user ids or problem ids are repeated
-fn check_repeated(body: &web::Json<contest::GetContest>) -> bool
+fn check_repeated(body: &contest::GetContest) -> bool
 {
     // checks if any problem id is repeated
     let mut occurrences: HashMap<u32, u32> = HashMap::new();
@@ -63,8 +50,50 @@ fn check_repeated(body: &web::Json<contest::GetContest>) -> bool
     return false;
 }
 
+// the users of the contest, contests with teams take the members of their teams
+// a user may only be in one of the teams of a contest, so that its jobs count for a single team
+fn contest_user_ids(body: &contest::GetContest, storage: &dyn storage::Storage) -> Result<Vec<u32>, (StatusCode, job::Error)>
+{
+    if body.team_ids.is_empty()
+    {
+        return Ok(body.user_ids.clone());
+    }
+    if !body.user_ids.is_empty()
+    {
+        return Err((StatusCode::BAD_REQUEST, job::Error::
+            new(1, "ERR_INVALID_ARGUMENT".to_string(), "Contests with teams take their users from the teams.".to_string())));
+    }
+
+    let teams = storage.teams().map_err(storage::unreadable)?;
+    let mut user_ids: Vec<u32> = vec![];
+    for (index, team_id) in body.team_ids.iter().enumerate()
+    {
+        let team = match teams.iter().find(|team| team.id == *team_id)
+        {
+            Some(team) => team,
+            None => return Err((StatusCode::NOT_FOUND, job::Error::
+                new(3, "ERR_NOT_FOUND".to_string(), format!("Team {} not found.", team_id).to_string()))),
+        };
+        if body.team_ids[..index].contains(team_id)
+        {
+            return Err((StatusCode::BAD_REQUEST, job::Error::
+                new(1, "ERR_INVALID_ARGUMENT".to_string(), format!("Team {} is repeated.", team_id).to_string())));
+        }
+        for user_id in team.member_ids.iter()
+        {
+            if user_ids.contains(user_id)
+            {
+                return Err((StatusCode::BAD_REQUEST, job::Error::
+                    new(1, "ERR_INVALID_ARGUMENT".to_string(), format!("User {} is in more than one team.", user_id).to_string())));
+            }
+            user_ids.push(*user_id);
+        }
+    }
+    return Ok(user_ids);
+}
+
 // returns user information given an id
-fn get_user(user_id: u32, lock_user_list: &std::sync::MutexGuard<Vec<user::User>>) -> user::User
+fn get_user(user_id: u32, lock_user_list: &[user::User]) -> user::User
 {
     for user in lock_user_list.iter()
     {
@@ -76,157 +105,132 @@ fn get_user(user_id: u32, lock_user_list: &std::sync::MutexGuard<Vec<user::User>
     panic!("User not found");
 }
 
-// posts a new contest
-#[post("/contests")]
-async fn post_contests(body: web::Json<contest::GetContest>, data_config: web::Data<Arc<Mutex<config::Config>>>) -> impl Responder 
+// adds a new contest, or changes the contest with the id of the body
+fn save_contest(body: contest::GetContest, caller: &permission::Caller, config: &config::Config, storage: &dyn storage::Storage) -> Result<contest::Contest, (StatusCode, job::Error)>
 {
-    let connection = CONNECTION.lock().unwrap();
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-    
-    // if SQL mode is activated, then load from database
-    sql::get_contest(&connection, &mut lock_contest_list);
+    let _lock = storage.lock();
+    let lock_user_list: std::sync::MutexGuard<Vec<user::User>> = storage.users();
 
     // check that the provided contest is valid
-    if !check_valid(&body, &data_config, &connection)
+    if !check_valid(&body, config, &lock_user_list)
     {
-        return HttpResponse::NotFound().json(job::Error::
-            new(3, "ERR_NOT_FOUND".to_string(), "Problem or user not found".to_string()));
+        return Err((StatusCode::NOT_FOUND, job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), "Problem or user not found".to_string())));
     }
     if check_repeated(&body)
     {
-        return HttpResponse::BadRequest().json(job::Error::
-            new(1, "ERR_INVALID_ARGUMENT".to_string(), "Invalid argument".to_string()));
+        return Err((StatusCode::BAD_REQUEST, job::Error::
+            new(1, "ERR_INVALID_ARGUMENT".to_string(), "Invalid argument".to_string())));
     }
+    let user_ids = contest_user_ids(&body, storage)?;
+    let actor = caller.actor();
+
+    // users are locked before contests, like everywhere else
+    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
 
     // if a contest id is provided, then it will update the information 
     if let Some(id) = body.id
     {
+        // cannot update contest 0 (special case)
+        if id == 0
+        {
+            return Err((StatusCode::BAD_REQUEST, job::Error::
+                new(1, "ERR_INVALID_ARGUMENT".to_string(), "Invalid contest id".to_string())));
+        }
         for contest in lock_contest_list.iter_mut()
         {
-            // cannot update contest 0 (special case)
-            if id == 0
-            {
-                return HttpResponse::BadRequest().json(job::Error::
-                    new(1, "ERR_INVALID_ARGUMENT".to_string(), "Invalid contest id".to_string()));
-            }
             // finds the contest and updates the information
             if id == contest.id
             {
-                *contest = contest::Contest
+                let mut output = contest::Contest
                 {
                     id: id,
                     name: body.name.clone(),
                     from: body.from,
                     to: body.to,
                     problem_ids: body.problem_ids.clone(),
-                    user_ids: body.user_ids.clone(),
+                    user_ids: user_ids.clone(),
+                    team_ids: body.team_ids.clone(),
                     submission_limit: body.submission_limit,
 
-                    users: vec![]
+                    users: vec![],
+                    // the best times come from every job of the contest, which are kept
+                    case_times: contest.case_times.clone(),
                 };
 
-                let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = USER_LIST.lock().unwrap();
-
-                // if SQL mode activated, then load from database
-                sql::get_user(&connection, &mut lock_user_list);
-
                 // vector that keeps track of each user's data
-                for id in contest.user_ids.iter()
+                for id in output.user_ids.iter()
                 {
-                    contest.users.push(contest::RankInfo
-                        {
-                            user: get_user(id.clone(), &lock_user_list),
-                            rank: 0,
-                            scores: vec![0.0 ; contest.problem_ids.len()],
-
-                            highest_scores: vec![0.0 ; contest.problem_ids.len()],
-                            latest_scores: vec![0.0 ; contest.problem_ids.len()],
-
-                            competitive_score_sum: 0.0,
-                            shortest_times: vec![vec![MAX; 20]; contest.problem_ids.len()], // possible error
-
-                            latest_submission: DateTime::<Utc>::MAX_UTC,
-                            score: 0,
-                            submission_count: 0,
-                        }
-                    )
+                    output.users.push(contest::RankInfo::new(get_user(id.clone(), &lock_user_list), output.problem_ids.len()));
                 }
                 
-                let output = contest.clone();
-                sql::push_contest(&connection, &lock_contest_list);
+                storage.transaction(&mut || {
+                    storage.save_contest(&output)?;
+                    storage.append_event(&actor, audit::Change::contest_updated { contest: output.clone() })
+                }).map_err(storage::internal)?;
+                *contest = output.clone();
                 // return the updated contest
-                return HttpResponse::Ok().json(output);
+                return Ok(output);
             }
         }
         // if the contest is not found then return an error
-        return HttpResponse::NotFound().json(job::Error::
-            new(3, "ERR_NOT_FOUND".to_string(), format!("Contest {} not found.", id.to_owned()).to_string()));
+        return Err((StatusCode::NOT_FOUND, job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), format!("Contest {} not found.", id.to_owned()).to_string())));
     }
-    // if no id is provided, then push new contest into end of list
-    else 
-    {
-        let mut lock_contest_id_count = CONTEST_ID_COUNT.lock().unwrap();
-        // if SQL mode activated, then load from database
-        sql::get_contest_count(&connection, &mut lock_contest_id_count);
-
-        // pushes contest into list
-        lock_contest_list.push(
-            contest::Contest
-            {
-                id: *lock_contest_id_count,
-                name: body.name.clone(),
-                from: body.from,
-                to: body.to,
-                problem_ids: body.problem_ids.clone(),
-                user_ids: body.user_ids.clone(),
-                submission_limit: body.submission_limit,
-
-                users: vec![]
-            }   
-        );
-        // if SQL mode activated, then load from database
-        sql::push_contest(&connection, &(lock_contest_list.clone()));
-
-        let contest = &mut lock_contest_list[(*lock_contest_id_count) as usize];
-        
-        let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = USER_LIST.lock().unwrap();
-        // if SQL mode activated, then load from database
-        sql::get_user(&connection, &mut lock_user_list);
-
-        // adds vector that keep tracks of user progress
-        for id in contest.user_ids.iter()
-        {
-            contest.users.push(contest::RankInfo
-                {
-                    user: get_user(id.clone(), &lock_user_list),
-                    rank: 0,
-                    scores: vec![0.0 ; contest.problem_ids.len()],
 
-                    highest_scores: vec![0.0 ; contest.problem_ids.len()],
-                    latest_scores: vec![0.0 ; contest.problem_ids.len()],
-
-                    competitive_score_sum: 0.0,
-                    shortest_times: vec![vec![MAX; 20]; contest.problem_ids.len()], // possible error
-
-                    latest_submission: DateTime::<Utc>::MAX_UTC,
-                    score: 0,
-                    submission_count: 0,
-                }
-            )
-        }
+    // if no id is provided, then push new contest into end of list
+    let mut lock_contest_id_count = storage.counter(Counter::Contest);
 
-        let output = contest.clone();
-        log::info!("{:?}", output);
+    // the contest is pushed into the list once it is saved
+    let mut output = contest::Contest
+    {
+        id: *lock_contest_id_count,
+        name: body.name.clone(),
+        from: body.from,
+        to: body.to,
+        problem_ids: body.problem_ids.clone(),
+        user_ids: user_ids.clone(),
+        team_ids: body.team_ids.clone(),
+        submission_limit: body.submission_limit,
+
+        users: vec![],
+        case_times: contest::empty_case_times(&config.problems),
+    };
+
+    // adds vector that keep tracks of user progress
+    for id in output.user_ids.iter()
+    {
+        output.users.push(contest::RankInfo::new(get_user(id.clone(), &lock_user_list), output.problem_ids.len()));
+    }
 
-        // if SQL mode activated, then load into database
-        sql::push_contest(&connection, &lock_contest_list);
+    log::info!("{:?}", output);
 
-        *lock_contest_id_count += 1;
+    // the contest and the contest count are stored together
+    storage.transaction(&mut || {
+        storage.save_contest(&output)?;
+        storage.save_counter(Counter::Contest, output.id + 1)?;
+        storage.append_event(&actor, audit::Change::contest_created { contest: output.clone() })
+    }).map_err(storage::internal)?;
+    lock_contest_list.push(output.clone());
+    *lock_contest_id_count += 1;
 
-        // if SQL mode activated, then load into database
-        sql::push_contest_count(&connection, *lock_contest_id_count);
+    // return newly added contest
+    return Ok(output);
+}
 
-        // return newly added contest
-        return HttpResponse::Ok().json(output);
+// posts a new contest, or updates one
+// saving waits for other writers, so it runs on the blocking thread pool
+#[post("/contests", wrap = "permission::Require(permission::Permission::manage_contests)")]
+async fn post_contests(body: web::Json<contest::GetContest>, caller: web::ReqData<permission::Caller>,
+    data_config: web::Data<Arc<Mutex<config::Config>>>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
+{
+    let config = data_config.lock().unwrap().clone();
+    let caller = caller.into_inner();
+    match web::block(move || save_contest(body.into_inner(), &caller, &config, data_storage.get_ref())).await
+    {
+        Ok(Ok(contest)) => HttpResponse::Ok().json(contest),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Contest could not be saved.".to_string())),
     }
 }
\ No newline at end of file
@@ -0,0 +1,106 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::job;
+
+use crate::SUBSCRIBER_LIST;
+
+// what a client is listening to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Topic
+{
+    Job(u32),
+    User(u32),
+    Contest(u32),
+}
+
+pub struct Subscriber
+{
+    pub topic: Topic,
+    pub sender: UnboundedSender<web::Bytes>,
+}
+
+// sent every time a job changes state or one of its cases gets a result
+// kind is "state" for state changes and "case" for case results
+#[derive(Serialize, Debug, Clone)]
+pub struct JobEvent
+{
+    #[serde(skip)]
+    pub kind: String,
+    pub job_id: u32,
+    pub user_id: u32,
+    pub contest_id: u32,
+    pub problem_id: u32,
+    pub state: String,
+    pub result: job::PossibleResult,
+    pub score: f32,
+    pub case_count: u32, // number of cases, excluding the compilation
+    pub case: Option<job::Case>,
+    pub time: DateTime<Utc>,
+}
+
+impl JobEvent
+{
+    // builds a state event, or a case event if a case is given
+    pub fn from_job(content: &job::ResponseContent, case: Option<job::Case>) -> JobEvent
+    {
+        return JobEvent
+        {
+            kind: if case.is_some() {"case".to_string()} else {"state".to_string()},
+            job_id: content.id,
+            user_id: content.submission.user_id,
+            contest_id: content.submission.contest_id,
+            problem_id: content.submission.problem_id,
+            state: content.state.clone(),
+            result: content.result.clone(),
+            score: content.score,
+            case_count: content.cases.len().saturating_sub(1) as u32,
+            case,
+            time: Utc::now(),
+        };
+    }
+
+    // formats the event as a server-sent event
+    pub fn to_message(&self) -> web::Bytes
+    {
+        let data = serde_json::to_string(self).expect("error serializing event");
+        return web::Bytes::from(format!("event: {}\ndata: {}\n\n", self.kind, data));
+    }
+
+    fn matches(&self, topic: &Topic) -> bool
+    {
+        match topic
+        {
+            Topic::Job(id) => self.job_id == *id,
+            Topic::User(id) => self.user_id == *id,
+            Topic::Contest(id) => self.contest_id == *id,
+        }
+    }
+}
+
+// sends an event to every client listening to the job, its user or its contest
+pub fn publish(event: JobEvent)
+{
+    let message = event.to_message();
+    let mut lock_subscriber_list = SUBSCRIBER_LIST.lock().unwrap();
+
+    // clients that disconnected are removed when sending fails
+    lock_subscriber_list.retain(|subscriber| {
+        if !event.matches(&subscriber.topic) {return true;}
+        return subscriber.sender.send(message.clone()).is_ok();
+    });
+
+    // the stream of a single job ends once it is finished
+    if event.state == "Finished"
+    {
+        lock_subscriber_list.retain(|subscriber| subscriber.topic != Topic::Job(event.job_id));
+    }
+}
+
+// shortcut for state changes
+pub fn publish_state(content: &job::ResponseContent)
+{
+    publish(JobEvent::from_job(content, None));
+}
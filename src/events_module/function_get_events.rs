@@ -0,0 +1,97 @@
+use actix_web::{get, Responder, HttpResponse, web};
+use futures_util::stream;
+use tokio::sync::mpsc;
+
+use crate::events;
+use crate::job;
+use crate::user;
+use crate::contest;
+use crate::storage;
+
+use crate::SUBSCRIBER_LIST;
+use crate::permission;
+
+// registers a new subscriber and returns the server-sent event response streaming its events
+// the initial events are sent before anything else
+fn subscribe(topic: events::Topic, initial: Vec<web::Bytes>, keep_open: bool) -> HttpResponse
+{
+    let (sender, receiver) = mpsc::unbounded_channel::<web::Bytes>();
+    for message in initial
+    {
+        sender.send(message).ok();
+    }
+    // if the sender is dropped the stream ends after the initial events
+    if keep_open
+    {
+        SUBSCRIBER_LIST.lock().unwrap().push(events::Subscriber { topic, sender });
+    }
+
+    let event_stream = stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await
+        {
+            Some(message) => Some((Ok::<_, actix_web::Error>(message), receiver)),
+            None => None,
+        }
+    });
+
+    return HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(event_stream);
+}
+
+// streams the progress of a job
+// the current state is sent first, and the stream ends when the job is finished
+#[get("/jobs/{jobId}/events", wrap = "permission::Require(permission::Permission::view)")]
+#[allow(non_snake_case)]
+async fn get_jobs_jobId_events(jobId: web::Path<u32>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let job_id = jobId.into_inner();
+    // finished jobs may be read from the database
+    match web::block(move || data_storage.job(job_id).map_err(storage::unreadable)).await
+    {
+        Ok(Ok(Some(content))) =>
+        {
+            let current = events::JobEvent::from_job(&content, None);
+            subscribe(events::Topic::Job(content.id), vec![current.to_message()], content.state != "Finished")
+        }
+        Ok(Ok(None)) => HttpResponse::NotFound().json(job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} not found.", job_id).to_string())),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), format!("Job {} could not be read.", job_id).to_string())),
+    }
+}
+
+// streams the progress of every job submitted by a user
+#[get("/users/{userId}/events", wrap = "permission::Require(permission::Permission::view)")]
+#[allow(non_snake_case)]
+async fn get_users_userId_events(userId: web::Path<u32>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let lock_user_list: std::sync::MutexGuard<Vec<user::User>> = data_storage.users();
+
+    for user in lock_user_list.iter()
+    {
+        if user.id == userId.to_owned()
+        {
+            return subscribe(events::Topic::User(user.id), vec![], true);
+        }
+    }
+    return HttpResponse::NotFound().json(job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", userId.to_owned()).to_string()));
+}
+
+// streams the progress of every job submitted in a contest
+#[get("/contests/{contestId}/events", wrap = "permission::Require(permission::Permission::view)")]
+#[allow(non_snake_case)]
+async fn get_contests_contestId_events(contestId: web::Path<u32>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = data_storage.contests();
+
+    for contest in lock_contest_list.iter()
+    {
+        if contest.id == contestId.to_owned()
+        {
+            return subscribe(events::Topic::Contest(contest.id), vec![], true);
+        }
+    }
+    return HttpResponse::NotFound().json(job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("Contest {} not found.", contestId.to_owned()).to_string()));
+}
\ No newline at end of file
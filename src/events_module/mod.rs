@@ -0,0 +1,2 @@
+pub mod events;
+pub mod function_get_events;
\ No newline at end of file
@@ -0,0 +1,47 @@
+use actix_web::{delete, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
+use chrono::Utc;
+
+use crate::job;
+use crate::quota;
+use crate::audit;
+use crate::storage;
+use crate::permission;
+
+// resets the usage of a user, so that its earlier jobs do not count for its quotas anymore
+// returns the usage left, which only has jobs submitted since, e.g. by requests that were already waiting for the lock
+fn reset_usage(user_id: u32, caller: &permission::Caller, storage: &dyn storage::Storage) -> Result<quota::UserUsage, (StatusCode, job::Error)>
+{
+    let _lock = storage.lock();
+    if !storage.users().iter().any(|user| user.id == user_id)
+    {
+        return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", user_id).to_string())));
+    }
+    let actor = caller.actor();
+
+    let reset_time = Utc::now();
+    storage.transaction(&mut || {
+        storage.save_usage_reset(user_id, reset_time)?;
+        storage.append_event(&actor, audit::Change::usage_reset { user_id, reset_time })
+    }).map_err(storage::internal)?;
+    log::info!("Usage of user {} reset", user_id);
+    let jobs = storage.query_jobs(&storage::JobQuery { user_id: Some(user_id), ..Default::default() }).map_err(storage::unreadable)?;
+    return Ok(quota::usage(user_id, &jobs, Some(reset_time), reset_time));
+}
+
+// resets the daily and contest usage of a user
+#[delete("/admin/usage/{userId}", wrap = "permission::Require(permission::Permission::administer)")]
+#[allow(non_snake_case)]
+async fn delete_admin_usage_userId(userId: web::Path<u32>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let user_id = userId.into_inner();
+    let caller = caller.into_inner();
+    match web::block(move || reset_usage(user_id, &caller, data_storage.get_ref())).await
+    {
+        Ok(Ok(usage)) => HttpResponse::Ok().json(usage),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), format!("Usage of user {} could not be reset.", user_id).to_string())),
+    }
+}
\ No newline at end of file
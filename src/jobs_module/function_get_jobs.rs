@@ -1,16 +1,11 @@
 use actix_web::{get, Responder, HttpResponse, web};
 use serde::Deserialize;
+use serde_json::Value;
 use chrono::{DateTime, Utc};
 
-use crate::JOB_LIST;
 use crate::job;
-
-use crate::USER_LIST;
-use crate::user;
-use crate::sql;
-
-use crate::Connection;
-use crate::CONNECTION;
+use crate::storage;
+use crate::permission;
 
 // used to obtain query data
 #[derive(Debug, Deserialize)]
@@ -25,110 +20,88 @@ pub struct AuthRequest // from https://docs.rs/actix-web/latest/actix_web/web/st
     to: Option<DateTime<Utc>>,
     state: Option<String>,
     result: Option<job::PossibleResult>,
+
+    order: Option<Order>,
+    after_id: Option<u32>,
+    limit: Option<u32>,
+    omit_source: Option<bool>,
 }
 
-// function that returns user id given its name
-fn get_id(user_name: String, connection: &Connection) -> Option<u32>
+// order of the jobs by id
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Order
 {
-    let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = USER_LIST.lock().unwrap();
-    // if SQL storage is on, then load from database
-    sql::get_user(&connection, &mut lock_user_list);
+    asc,
+    desc,
+}
 
-    for user in lock_user_list.iter()
+// gets list of jobs that satisfy query requirements
+// pages of at most limit jobs are listed by passing the id of the last job as after_id
+#[get("/jobs", wrap = "permission::Require(permission::Permission::view)")]
+async fn get_jobs(info: web::Query<AuthRequest>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder {
+    if info.limit == Some(0)
     {
-        if user.name == user_name
-        {
-            return Some(user.id);
-        }
+        return HttpResponse::BadRequest().json(job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), "Limit must be positive.".to_string()));
     }
-    return None;
-}
+    let omit_source = info.omit_source.unwrap_or(false);
+    let info = info.into_inner();
+    let query = storage::JobQuery
+    {
+        user_id: info.user_id,
+        user_name: info.user_name,
+        contest_id: info.contest_id,
+        problem_id: info.problem_id,
+        language: info.language,
+        from: info.from,
+        to: info.to,
+        state: info.state,
+        result: info.result,
 
-// gets list of jobs that satisfy query requirements
-#[get("/jobs")]
-async fn get_jobs(info: web::Query<AuthRequest>) -> impl Responder {
-    let connection = CONNECTION.lock().unwrap();
+        descending: info.order == Some(Order::desc),
+        after_id: info.after_id,
+        limit: info.limit,
+        with_source: !omit_source,
+    };
 
-    let mut lock_job_list = JOB_LIST.lock().unwrap();
-    // if SQL storage is on, then load from database
-    sql::get_job(&connection, &mut lock_job_list);
+    // the storage may wait for a free database connection
+    let jobs = match web::block(move || data_storage.query_jobs(&query).map_err(storage::unreadable)).await
+    {
+        Ok(Ok(jobs)) => jobs,
+        Ok(Err((status, error))) => return HttpResponse::build(status).json(error),
+        Err(_) => return HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Jobs could not be listed.".to_string())),
+    };
 
-    // filtering list of jobs based on query information 
-    let filtered_jobs: Vec<job::ResponseContent> = lock_job_list.iter()
-        .filter(|job| {
-            if let Some(user_id) = info.user_id {
-                if job.submission.user_id != user_id {
-                    return false;
-                }
-            }
-            if let Some(user_name) = &info.user_name {
-                if let Some(user_id) = get_id(user_name.to_string(), &connection)
-                {
-                    if job.submission.user_id != user_id {
-                        return false;
-                    }
-                }
-            }
-            if let Some(contest_id) = info.contest_id {
-                if job.submission.contest_id != contest_id {
-                    return false;
-                }
-            }
-            if let Some(problem_id) = info.problem_id {
-                if job.submission.problem_id != problem_id {
-                    return false;
-                }
-            }
-            if let Some(language) = &info.language {
-                if job.submission.language != *language {
-                    return false;
-                }
-            }
-            if let Some(from) = info.from {
-                if job.created_time < from {
-                    return false;
-                }
-            }
-            if let Some(to) = info.to {
-                if job.created_time > to {
-                    return false;
-                }
-            }
-            if let Some(state) = &info.state {
-                if job.state != *state {
-                    return false;
-                }
-            }
-            if let Some(result) = &info.result {
-                if job.result != *result {
-                    return false;
-                }
+    if omit_source
+    {
+        let jobs: Vec<Value> = jobs.iter().map(|content| {
+            let mut value = serde_json::to_value(content).unwrap_or_default();
+            if let Some(submission) = value["submission"].as_object_mut()
+            {
+                submission.remove("source_code");
             }
-            return true;
-        })
-        .cloned()
-        .collect();
-    HttpResponse::Ok().json(filtered_jobs)
+            value
+        }).collect();
+        return HttpResponse::Ok().json(jobs);
+    }
+    HttpResponse::Ok().json(jobs)
 }
 
 // returns job information provided its id
-#[get("/jobs/{jobId}")]
+#[get("/jobs/{jobId}", wrap = "permission::Require(permission::Permission::view)")]
 #[allow(non_snake_case)]
-async fn get_jobs_jobId(jobId: web::Path<u32>) -> impl Responder 
+async fn get_jobs_jobId(jobId: web::Path<u32>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
 {
-    let connection = CONNECTION.lock().unwrap();
-
-    let mut lock_job_list = JOB_LIST.lock().unwrap();
-    // if SQL storage is on, then load from database
-    sql::get_job(&connection, &mut lock_job_list);
-    
-    for content in lock_job_list.iter()
+    let job_id = jobId.into_inner();
+    // finished jobs may be read from the database
+    match web::block(move || data_storage.job(job_id).map_err(storage::unreadable)).await
     {
-        if jobId.to_owned() == content.id
-        {
-            return HttpResponse::Ok().json(content.clone());
-        }
+        Ok(Ok(Some(content))) => HttpResponse::Ok().json(content),
+        // if not found, then return an error
+        Ok(Ok(None)) => HttpResponse::NotFound().json(job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} not found.", job_id).to_string())),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), format!("Job {} could not be read.", job_id).to_string())),
     }
-    // if not found, then return an error
-    return HttpResponse::NotFound().json(job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} not found.", jobId.to_owned()).to_string()));
 }
\ No newline at end of file
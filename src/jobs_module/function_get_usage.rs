@@ -0,0 +1,69 @@
+use actix_web::{get, Responder, HttpResponse, web};
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+use std::sync::Mutex;
+use std::sync::Arc;
+
+use crate::job;
+use crate::quota;
+use crate::config;
+use crate::storage;
+use crate::permission;
+
+// query of GET /admin/usage, every user is listed unless user_id is given
+#[derive(Deserialize, Debug)]
+pub struct UsageQuery
+{
+    user_id: Option<u32>,
+}
+
+// response of GET /admin/usage, with the quotas the usage is checked against
+#[derive(Serialize, Debug, Clone)]
+pub struct UsageReport
+{
+    pub quotas: config::Quotas,
+    pub users: Vec<quota::UserUsage>,
+}
+
+// lists the judging used by users today and in each contest, see quota::usage
+#[get("/admin/usage", wrap = "permission::Require(permission::Permission::administer)")]
+async fn get_admin_usage(query: web::Query<UsageQuery>, data_config: web::Data<Arc<Mutex<config::Config>>>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let user_ids: Vec<u32> = data_storage.users().iter()
+        .map(|user| user.id)
+        .filter(|id| query.user_id.map_or(true, |user_id| user_id == *id))
+        .collect();
+    if let Some(user_id) = query.user_id
+    {
+        if user_ids.is_empty()
+        {
+            return HttpResponse::NotFound().json(job::Error::
+                new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", user_id).to_string()));
+        }
+    }
+
+    let quotas = data_config.lock().unwrap().quotas.clone();
+    let only_user_id = query.user_id;
+    let compute = move || -> Result<UsageReport, storage::Error> {
+        let now = Utc::now();
+        let reset_times = user_ids.iter().map(|user_id| data_storage.usage_reset(*user_id)).collect::<Result<Vec<_>, _>>()?;
+        // the jobs are read once for every user listed
+        let jobs = data_storage.query_jobs(&storage::JobQuery { user_id: only_user_id, ..Default::default() })?;
+        let users = user_ids.iter().zip(reset_times.into_iter())
+            .map(|(user_id, reset_time)| quota::usage(*user_id, &jobs, reset_time, now))
+            .collect();
+        Ok(UsageReport { quotas, users })
+    };
+    match web::block(compute).await
+    {
+        Ok(Ok(report)) => HttpResponse::Ok().json(report),
+        Ok(Err(error)) =>
+        {
+            let (status, error) = storage::unreadable(error);
+            HttpResponse::build(status).json(error)
+        }
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Usage could not be computed.".to_string())),
+    }
+}
\ No newline at end of file
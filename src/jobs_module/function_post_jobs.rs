@@ -1,10 +1,8 @@
-use actix_web::{get, post, web, Responder, HttpResponse};
-use std::fs::File;
-use std::process::Command;
-use std::process::Stdio;
-use std::time::Duration;
-use wait_timeout::ChildExt;
-use chrono::Utc;
+use actix_web::{get, post, web, Responder, HttpResponse, HttpRequest};
+use actix_web::http::StatusCode;
+use actix_web::http::header;
+use chrono::{DateTime, Utc};
+use std::sync::MutexGuard;
 
 use crate::post_job;
 use crate::job;
@@ -12,22 +10,21 @@ use crate::user;
 use crate::config;
 use crate::Arc;
 use crate::Mutex;
-use crate::compare_functions;
 use crate::contest;
-use crate::spj;
-use crate::sql;
-use crate::Connection;
-
-use crate::JOB_LIST;
-use crate::JOB_ID_COUNT;
-
-use crate::USER_LIST;
-
-use crate::CONTEST_LIST;
-
-use crate::CASE_TIMES_LIST;
-
-use crate::CONNECTION;
+use crate::judge;
+use crate::worker;
+use crate::events;
+use crate::webhook;
+use crate::storage;
+use crate::storage::Counter;
+use crate::audit;
+use crate::auth;
+use crate::permission;
+use crate::rate_limit;
+use crate::quota;
+
+use crate::JOB_QUEUE;
+use crate::IS_DISTRIBUTED;
 
 #[get("/hello/{name}")]
 async fn greet(name: web::Path<String>) -> impl Responder {
@@ -36,7 +33,7 @@ async fn greet(name: web::Path<String>) -> impl Responder {
 }
 
 // returns the index of a problem in a vector given its id
-fn get_problem_index(problems: &Vec<config::Problem>, problem_id: u32) -> usize
+pub fn get_problem_index(problems: &Vec<config::Problem>, problem_id: u32) -> usize
 {
     for i in 0..problems.len()
     {
@@ -49,7 +46,7 @@ fn get_problem_index(problems: &Vec<config::Problem>, problem_id: u32) -> usize
 }
 
 // returns the index of a language in a vector given its name
-fn get_language_index(languages: &Vec<config::Language>, language_name: String) -> usize
+pub fn get_language_index(languages: &Vec<config::Language>, language_name: String) -> usize
 {
     for i in 0..languages.len()
     {
@@ -61,535 +58,415 @@ fn get_language_index(languages: &Vec<config::Language>, language_name: String)
     return 0;
 }
 
-// runs an individual testcase
-fn run_test_case(job_result: &mut job::PossibleResult, case_result: &mut job::PossibleResult, case_info: &mut String,
-    case: &config::Case, config: &config::Config, problem_index: usize, case_index: usize, score_sum: &mut f32, 
-    body: &post_job::PostJob, time: &mut i64, connection: &Connection)
+// only cases whose output was compared have a measured time
+fn is_timed(case: &job::Case) -> bool
+{
+    return case.id != 0 && (case.result == job::PossibleResult::Accepted || case.result == job::PossibleResult::WrongAnswer);
+}
+
+// records the execution time of each case for dynamic ranking
+// updates both the best times of the contest and the personal best times of the user
+pub fn record_case_times(body: &post_job::PostJob, problem: &config::Problem, problem_index: usize, cases: &Vec<job::Case>,
+    contest: &mut contest::Contest)
 {
+    if problem.ty != config::ProblemType::dynamic_ranking {return;}
 
-    // opens the file where input is obtained from
-    let in_file = match File::open(case.input_file.clone()) 
+    for case in cases.iter()
     {
-        Ok(file) => file,
-        Err(_) => 
+        if !is_timed(case)
         {
-            panic!("input file not found"); // TODO
+            continue;
         }
-    };
+        let case_index = case.id as usize - 1;
 
-    // creates and opens temporary file where results stored
-    let out_file = match File::create("TMPDIR/test.out") 
-    {
-        Ok(file) => file,
-        Err(_) => 
+        // UPDATE CONTEST BEST TIME
+
+        if case.time < contest.case_times[problem_index][case_index]
         {
-            panic!("output file not created"); // TODO
+            contest.case_times[problem_index][case_index] = case.time;
         }
-    };
 
-    // starts keeping track of execution time for dynamic ranking mode
-    let start_time = Utc::now().naive_utc();
+        // END UPDATE CONTEST BEST TIME
 
-    // start running test case
-    let mut child: std::process::Child = Command::new("TMPDIR/test.exe".to_string())
-        .stdin(Stdio::from(in_file))
-        .stdout(Stdio::from(out_file))
-        .stderr(Stdio::piped())
-        .spawn()
-        .expect("Error child");
-    
-    // keep track of timeout limit
-    let timeout = Duration::from_micros(case.time_limit);
+        // UPDATE USER PERSONAL BEST TIME
 
-    // check that runtime is within timeout limit
-    match child.wait_timeout(timeout).unwrap() 
-    {
-        
-        Some(status) => 
+        for user in &mut contest.users
         {
-            // if runtime error, then set the case and job results to RuntimeError
-            if !status.success()
+            if user.user.id == body.user_id
             {
-                *case_result = job::PossibleResult::RuntimeError;
-                *job_result = job::PossibleResult::RuntimeError;
-            }
-            else 
-            {
-                // finish keeping track of execution time
-                let end_time = Utc::now().naive_utc();
+                log::info!("Time used by {}: {}", user.user.id, case.time);
 
-                // open answer for this testcase
-                let ans_file = match File::open(case.answer_file.clone())
-                {
-                    Ok(file) => file,
-                    Err(_) => {
-                        panic!("missing case data");
-                    }
-                };
-
-                // open output
-                let out_file2 = match File::open("TMPDIR/test.out")
-                {
-                    Ok(file) => file,
-                    Err(_) => 
-                    {
-                        panic!("could not create file");
-                    }
-                };
-                
-                let mut accepted: bool = false;
-                let mut info: String = "".to_string();
-                let mut spj_error: bool = false;
-                let mut correntness_ratio: f32 = 1.0;
-
-                // select type of compare and compare answer with output to yield result for the case
-                if *&config.problems[problem_index].ty == config::ProblemType::standard
-                {
-                    accepted = compare_functions::compare_standard(out_file2, ans_file);
-                }
-                else if *&config.problems[problem_index].ty == config::ProblemType::strict
-                {
-                    accepted = compare_functions::compare_strict(out_file2, ans_file);
-                }
-                else if *&config.problems[problem_index].ty == config::ProblemType::spj
-                {
-                    // see this compare function in spj file
-                    (accepted, info, spj_error) = spj::compare_spj("TMPDIR/test.out".to_string(), 
-                    case.answer_file.clone(), &config, problem_index);
-                    match std::fs::remove_dir_all("SPJDIR") 
-                    {
-                        Ok(_) => {}
-                        Err(_) => {} // TODO
-                    }
-                }
-                else if *&config.problems[problem_index].ty == config::ProblemType::dynamic_ranking
+                if case.time < user.shortest_times[problem_index][case_index]
                 {
-                    if let Some(competitive_ratio) = config.problems[problem_index].misc.dynamic_ranking_ratio
-                    {
-                        // calculates first component of score
-                        correntness_ratio = 1.0 - competitive_ratio;
-                        accepted = compare_functions::compare_standard(out_file2, ans_file);
-
-                        let time_used = end_time-start_time;
-
-                        let mut lock_case_list: std::sync::MutexGuard<Vec<Vec<i64>>> = CASE_TIMES_LIST.lock().unwrap();
-                        
-                        // calculates second component of score
-                        if let Some(time_used_micros) = time_used.num_microseconds()
-                        {
-                            *time = time_used_micros;
-                            
-                            // UPDATE GLOBAL TIME
-                            
-                            if time_used_micros < lock_case_list[problem_index][case_index]
-                            {
-                                lock_case_list[problem_index][case_index] = time_used_micros;
-                            }
-
-                            // END UPDATE GLOBAL BEST TIME
-
-                            // UPDATE USER PERSONAL BEST TIME
-
-                            let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-                            sql::get_contest(&connection, &mut lock_contest_list);
-                            
-                            let contest = &mut lock_contest_list[body.contest_id as usize];
-
-                            for user in &mut contest.users
-                            {
-                                if user.user.id == body.user_id
-                                {
-                                    log::info!("Time used by {}: {}", user.user.id, time_used_micros);
-                                    
-                                    if time_used_micros < user.shortest_times[problem_index][case_index]
-                                    {
-                                        user.shortest_times[problem_index][case_index] = time_used_micros;
-                                    }
-                                }
-                            }
-
-                            sql::push_contest(&connection, &mut lock_contest_list);
-
-                            // END UPDATE USER TIME
-    
-                        }
-                        else  {
-                            panic!("Overflow occured when dealing with time");
-                        }
-                    }
-                    else 
-                    {
-                        panic!("dynamic_ranking mode selected but no ratio was provided");
-                    }
-                }
-                if spj_error 
-                {
-                    *case_result = job::PossibleResult::SPJError;
-                }
-                else {
-                    if accepted {
-                        *case_result = job::PossibleResult::Accepted;
-                        *case_info = info;
-                        *score_sum += case.score * correntness_ratio;
-                    }
-                    else
-                    {
-                        *case_info = info;
-                        *case_result = job::PossibleResult::WrongAnswer;
-                    }
+                    user.shortest_times[problem_index][case_index] = case.time;
                 }
             }
         }
-        None => 
-        {
-            child.kill().unwrap();
-            child.wait().unwrap();
-            // log::info!("Time limit exceeded");
-            *job_result = job::PossibleResult::TimeLimitExceeded;
-            *case_result = job::PossibleResult::TimeLimitExceeded;
-        }
-    }
-}
 
-// function runs a job
-pub fn post_jobs_action(body: web::Json<post_job::PostJob>, data_config: web::Data<Arc<Mutex<config::Config>>>, 
-    is_put: bool, connection: &Connection) -> job::ResponseContent {
-
-    // keep track of when job was started
-    let created_time = Utc::now();
-    
-    let mut results: Vec<job::Case> = vec![];
-    let mut score_sum: f32 = 0.0;
-    
-    // creating temporary directory for problem
-    match std::fs::create_dir("TMPDIR") 
-    {
-        Ok(_) => {}
-        Err(_) => {}
+        // END UPDATE USER TIME
     }
+}
 
-    let mut config = data_config.lock().unwrap();
-
-    let language_index = get_language_index(&config.languages, body.language.clone());
-    let file_name = &config.languages[language_index].get_file_name();
-
-    // writing source code into file
-    match std::fs::write("TMPDIR/".to_owned() + file_name, body.source_code.clone())
-    {
-        Ok(_) => {}
-        Err(_) => {} // TODO
-    }
-
-    // obrains the command to be executed to run the code
-    let mut commands: Vec<String> = vec![];
-    for i in &config.languages[language_index].command.clone()
-    {
-        if i == &config.languages[language_index].command[0] {continue;} // ignore first element
-        else if i == "%INPUT%" {commands.push("TMPDIR/".to_owned() + file_name);}
-        else if i == "%OUTPUT%" {commands.push("TMPDIR/test.exe".to_string());}
-        else {commands.push(i.clone());}
-    }
-    
-    let mut job_result: job::PossibleResult;
-
-    // uses commands to compile the source code
-    match Command::new(&config.languages[language_index].command[0])
-                .args(commands)
-                .stderr(Stdio::null())
-                .status()
+// recomputes the best case times of a contest from the finished jobs submitted to it
+// used when the times were never stored, personal best times are stored with the participants
+pub fn rebuild_case_times(contest: &mut contest::Contest, jobs: &Vec<job::ResponseContent>, problems: &Vec<config::Problem>)
+{
+    for job in jobs.iter()
     {
-        Ok(status) if status.success() => {
-            job_result = job::PossibleResult::CompilationSuccess;
-            results.push(job::Case {
-                id: 0,
-                result: job::PossibleResult::CompilationSuccess,
-                info: "".to_string(),
-                time: 0
-            })
+        if job.submission.contest_id != contest.id || job.state != "Finished"
+        {
+            continue;
         }
-        _ =>
+        // problems that are not in the config anymore are skipped
+        let problem_index = get_problem_index(problems, job.submission.problem_id);
+        match problems.get(problem_index)
         {
-            job_result = job::PossibleResult::CompilationError;
-            results.push(job::Case {
-                id: 0,
-                result: job::PossibleResult::CompilationError,
-                info: "".to_string(),
-                time: 0
-            });
+            Some(problem) if problem.id == job.submission.problem_id && problem.ty == config::ProblemType::dynamic_ranking => {}
+            _ => continue,
         }
-    }
-
-    let mut case_id = 1;
-    let problem_index = get_problem_index(&config.problems, body.problem_id);
 
-    // if packing mode is on then run cases pack by pack
-    if let Some(packing) = config.problems[problem_index].misc.packing.clone()
-    {
-        // loops through each pack
-        for i in 0..packing.len()
+        for case in job.cases.iter()
         {
-            let mut accept = true;
-            // loops through each case in a pack
-            for j in 0..packing[i].len()
+            if !is_timed(case)
             {
-                // if a case in the pack is failed, then the rest are skipped
-                if accept == false
-                {
-                    results.push(job::Case {
-                        id: packing[i][j],
-                        result: job::PossibleResult::Skipped,
-                        info: "".to_string(),
-                        time: 0,
-                    });
-                    continue;
-                }
-                
-                let mut case_result: job::PossibleResult = job::PossibleResult::Waiting;
-
-                if job_result == job::PossibleResult::CompilationError 
-                {
-                    results.push(job::Case {
-                        id: packing[i][j],
-                        result: case_result,
-                        info: "".to_string(),
-                        time: 0,
-                    });
-                    continue;
-                }
-
-                let mut info: String = "".to_string();
-                let mut time: i64 = 0;
-
-                // runs the test case and stores the information into variables
-                run_test_case(&mut job_result, &mut case_result, &mut info,
-                    &config.problems[problem_index].cases[packing[i][j] as usize -1], 
-                    &config, problem_index, packing[i][j] as usize -1, &mut score_sum, 
-                    &body, &mut time, &connection
-                );
-
-                if case_result != job::PossibleResult::Accepted
+                continue;
+            }
+            if let Some(case_time) = contest.case_times[problem_index].get_mut(case.id as usize - 1)
+            {
+                if case.time < *case_time
                 {
-                    accept = false;
+                    *case_time = case.time;
                 }
-                
-                // stores the result into vector
-                results.push(job::Case {
-                    id: packing[i][j],
-                    result: case_result,
-                    info: info,
-                    time: time
-                });
-
             }
         }
     }
-    // if not packing mode, then run each test case in linear order
-    else 
+}
+
+// updates the scores of the user in the contest of the job
+pub fn update_contest(body: &post_job::PostJob, score_sum: f32, created_time: DateTime<Utc>, contest: &mut contest::Contest)
+{
+    // update result for user
+    let mut problem_index: usize = 0;
+    for i in 0..contest.problem_ids.len()
     {
-        for case in &config.problems[problem_index].cases 
+        if contest.problem_ids[i] == body.problem_id
         {
-            
-            let mut case_result: job::PossibleResult = job::PossibleResult::Waiting;
-
-            if job_result == job::PossibleResult::CompilationError 
-            {
-                results.push(job::Case {
-                    id: case_id,
-                    result: case_result,
-                    info: "".to_string(),
-                    time: 0
-                });
-                case_id += 1;
-                continue;
-            }
-
-            let mut info: String = "".to_string();
-            let mut time: i64 = 0;
-
-            // runs test case and store information into variables
-
-            run_test_case(&mut job_result, &mut case_result, &mut info, case, 
-                &config, problem_index, case_id as usize -1, &mut score_sum,
-                 &body, &mut time, &connection
-            );
-            
-            // pushes result into vector
-
-            results.push(job::Case {
-                id: case_id,
-                result: case_result,
-                info: info,
-                time: time
-            });
-            case_id += 1;
+            problem_index = i;
         }
     }
 
-    // AFTER ALL TESTCASES HAVE BEEN RUN:
-
-    let state_str = "Finished";
-    
-    // only calculate exact results if timelimit was not exceeded, and there were no other errors
-    if job_result == job::PossibleResult::TimeLimitExceeded {}
-    else if job_result == job::PossibleResult::RuntimeError {}
-    else if job_result == job::PossibleResult::CompilationError {}
-    else
+    // update result for user
+    for rank_info in contest.users.iter_mut()
     {
-        // in dynamic_ranking mode, we have to account fot the fact that the maximum possible
-        // score is 100.0 * correntness_ratio
-        if config.problems[problem_index].ty == config::ProblemType::dynamic_ranking
-        {
-            
-            if let Some(competitive_ratio) = config.problems[problem_index].misc.dynamic_ranking_ratio
-            {
-                let correntness_ratio = 1.0 - competitive_ratio;
-                if score_sum == 100.0 * correntness_ratio
-                {
-                    job_result = job::PossibleResult::Accepted;
-                }
-                else if score_sum < 100.0* correntness_ratio
-                {
-                    job_result = job::PossibleResult::WrongAnswer;
-                }
-            }
-            else {
-                panic!("dynamic_ranking selected but no ratio provided")
-            }
-        }
-        // otherwise, no need to account for correntness_ratio
-        else 
+        if rank_info.user.id == body.user_id
         {
-            if score_sum == 100.0
+            rank_info.latest_scores[problem_index] = score_sum;
+
+            if score_sum >= rank_info.highest_scores[problem_index]
             {
-                job_result = job::PossibleResult::Accepted;
+                rank_info.highest_scores[problem_index] = score_sum;
+                rank_info.latest_submission = created_time;
             }
-            else if score_sum < 100.0
-            {
-                job_result = job::PossibleResult::WrongAnswer;
-            }   
         }
     }
+}
 
-    // delete temporary directory
-    match std::fs::remove_dir_all("TMPDIR") 
+// counts a new job in the submission count of its user in its contest
+// jobs are counted when they are submitted rather than when they are judged,
+// so that jobs still waiting for a worker count for the submission limit, see check_submission_limit
+fn count_submission(body: &post_job::PostJob, contest: &mut contest::Contest)
+{
+    for rank_info in contest.users.iter_mut().filter(|rank_info| rank_info.user.id == body.user_id)
     {
-        Ok(_) => {}
-        Err(_) => {} // TODO
+        rank_info.submission_count += 1;
     }
+}
+
+// function runs a job
+// rejudge_id is the id of the job being judged again, if the HTTP request is of type PUT
+// actor is who submitted or rejudged the job, as written to the audit log
+// lock is the Storage::lock of the caller, it is released while the job is judged so that other requests can change the storage
+pub fn post_jobs_action<'a>(body: web::Json<post_job::PostJob>, data_config: web::Data<Arc<Mutex<config::Config>>>,
+    rejudge_id: Option<u32>, actor: &str, lock: MutexGuard<'a, ()>, storage: &'a dyn storage::Storage) -> Result<job::ResponseContent, storage::Error> {
 
-    let updated_time = Utc::now();
+    let is_put = rejudge_id.is_some();
 
-    let mut lock_job_id_count = JOB_ID_COUNT.lock().unwrap();
-    // if SQL mode activated, then load information from database
-    sql::get_job_count(&connection, &mut lock_job_id_count);
-    
-    // store information
-    let job = job::Job 
+    // keep track of when job was started
+    let created_time = Utc::now();
+
+    // the config is copied so that other requests can read it while the job is judged
+    let config = data_config.lock().unwrap().clone();
+
+    let language_index = get_language_index(&config.languages, body.language.clone());
+    let problem_index = get_problem_index(&config.problems, body.problem_id);
+
+    // state of the job while it is running, only used for events
+    let mut running: job::ResponseContent;
     {
-        poll_for_job: true,
-        request: job::Request
+        let mut lock_job_id_count = storage.counter(Counter::Job);
+
+        // the id is needed beforehand so that progress can be published
+        let job_id = match rejudge_id
+        {
+            Some(id) => id,
+            None => *lock_job_id_count,
+        };
+        running = job::ResponseContent
         {
-            path: "jobs".to_string(),
-            method: "POST".to_string(),
-            content: post_job::PostJob 
+            id: job_id,
+            created_time,
+            updated_time: created_time,
+            submission: body.clone(),
+            state: "Running".to_string(),
+            result: job::PossibleResult::Running,
+            score: 0.0,
+            cases: waiting_cases(&config.problems[problem_index]),
+        };
+        // the contest with the new job counted, it is put into the contest list once the job is saved
+        let mut contest = storage.contests()[body.contest_id as usize].clone();
+        count_submission(&body, &mut contest);
+        // the job is stored as running before it is judged, so that it is found if the server stops meanwhile, see recovery
+        storage.transaction(&mut || {
+            if !is_put
+            {
+                storage.save_counter(Counter::Job, job_id + 1)?;
+                storage.save_participant(&contest, body.user_id)?;
+            }
+            storage.save_job(&running)?;
+            let change = match is_put
             {
-                source_code: body.source_code.clone(),
-                language: body.language.clone(),
-                user_id: body.user_id.clone(),
-                problem_id: body.problem_id.clone(),
-                contest_id: body.contest_id.clone(),
-            },
-        },
-        response: job::Response
+                false => audit::Change::job_submitted { job: running.clone() },
+                true => audit::Change::job_rejudged { job: running.clone() },
+            };
+            storage.append_event(actor, change)
+        })?;
+        if !is_put
         {
-            status: 200,
-            content: job::ResponseContent
+            *lock_job_id_count += 1;
+            count_submission(&body, &mut storage.contests()[body.contest_id as usize]);
+        }
+        storage.keep_job(&running);
+    }
+    drop(lock);
+    events::publish_state(&running);
+
+    // compiles the source code and runs every case
+    // each judging has its own directory, so that jobs judged at the same time do not share files
+    let dir = format!("TMPDIR_{}_{}", running.id, created_time.timestamp_micros());
+    let (job_result, score_sum, results) = judge::judge_submission(&body.source_code, 
+        &config.languages[language_index], &config.problems[problem_index], &dir, &mut |case| {
+            for running_case in running.cases.iter_mut()
             {
-                id: *lock_job_id_count,
-                created_time,
-                updated_time,
-                submission: post_job::PostJob 
-                {
-                    source_code: body.source_code.clone(),
-                    language: body.language.clone(),
-                    user_id: body.user_id.clone(),
-                    problem_id: body.problem_id.clone(),
-                    contest_id: body.contest_id.clone(),
-                },
-                state: state_str.to_string(),
-                result: job_result,
-                score: score_sum,
-                cases: results,
-            },
-        },
-        // restart_server: false,
-    };
+                if running_case.id == case.id {*running_case = case.clone();}
+            }
+            events::publish(events::JobEvent::from_job(&running, Some(case.clone())));
+        });
+
+    // the result is stored like the one of a worker, on the job as it is now,
+    // since its user may have been anonymized or deleted while it was judged
+    let _lock = storage.lock();
+    let queued = worker::QueuedJob { job_id: running.id, is_rejudge: is_put };
+    let report = worker::JobReport { result: job_result, score: score_sum, cases: results };
+    if let Some(content) = finish_queued_job(&queued, report.clone(), &config, audit::ACTOR_SYSTEM, storage)?
+    {
+        return Ok(content);
+    }
 
-    // if the HTTP request is of type PUT, then do not count this as a new job
-    if !is_put
+    // the job was deleted meanwhile, so there is nothing to store
+    running.updated_time = Utc::now();
+    running.state = "Finished".to_string();
+    running.result = report.result;
+    running.score = report.score;
+    running.cases = report.cases;
+    return Ok(running);
+}
+
+// returns the list of cases of a job that has not been judged yet
+fn waiting_cases(problem: &config::Problem) -> Vec<job::Case>
+{
+    let mut cases: Vec<job::Case> = vec![];
+    for i in 0..problem.cases.len() + 1
     {
-        *lock_job_id_count +=1 ;
-        let mut lock_job_list = JOB_LIST.lock().unwrap();
-        lock_job_list.push(job.response.content.clone());
-        // if SQL mode activated, then load information into database
-        sql::push_job(&connection, &lock_job_list);
+        cases.push(job::Case {
+            id: i as u32,
+            result: job::PossibleResult::Waiting,
+            info: "".to_string(),
+            time: 0,
+            memory: 0,
+            feedback: None,
+        });
     }
+    return cases;
+}
 
-    // START update contest information
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-    // if SQL mode activated, then load information from database
-    sql::get_contest(&connection, &mut lock_contest_list);
+// clears the results of a job so that it can be judged again
+pub fn reset_job(content: &mut job::ResponseContent, problem: &config::Problem)
+{
+    content.updated_time = Utc::now();
+    content.state = "Queueing".to_string();
+    content.result = job::PossibleResult::Waiting;
+    content.score = 0.0;
+    content.cases = waiting_cases(problem);
+}
 
-    let contest = &mut lock_contest_list[body.contest_id as usize];
+// used in distributed mode
+// stores a new job without judging it and puts it into the queue, where a worker will pick it up
+pub fn queue_job(body: &post_job::PostJob, config: &config::Config, storage: &dyn storage::Storage) -> Result<job::ResponseContent, storage::Error>
+{
+    let mut lock_job_id_count = storage.counter(Counter::Job);
 
-    // update result for user
-    let mut problem_index: usize = 0;
-    for i in 0..contest.problem_ids.len()
+    let problem_index = get_problem_index(&config.problems, body.problem_id);
+
+    let content = job::ResponseContent
     {
-        if contest.problem_ids[i] == body.problem_id
+        id: *lock_job_id_count,
+        created_time: Utc::now(),
+        updated_time: Utc::now(),
+        submission: body.clone(),
+        state: "Queueing".to_string(),
+        result: job::PossibleResult::Waiting,
+        score: 0.0,
+        cases: waiting_cases(&config.problems[problem_index]),
+    };
+
+    // the contest with the new job counted, it is put into the contest list once the job is saved
+    let mut contest = storage.contests()[body.contest_id as usize].clone();
+    count_submission(body, &mut contest);
+
+    storage.transaction(&mut || {
+        storage.save_job(&content)?;
+        storage.save_counter(Counter::Job, content.id + 1)?;
+        storage.save_participant(&contest, body.user_id)?;
+        storage.append_event(&audit::user_actor(body.user_id), audit::Change::job_submitted { job: content.clone() })
+    })?;
+    *lock_job_id_count += 1;
+    count_submission(body, &mut storage.contests()[body.contest_id as usize]);
+    storage.keep_job(&content);
+
+    JOB_QUEUE.lock().unwrap().push_back(worker::QueuedJob { job_id: content.id, is_rejudge: false });
+    log::info!("Job {} queued", content.id);
+    events::publish_state(&content);
+
+    return Ok(content);
+}
+
+// used in distributed mode
+// puts a job back into the queue, e.g. when its worker stopped sending heartbeats
+pub fn requeue_job(queued: worker::QueuedJob, config: &config::Config, storage: &dyn storage::Storage)
+{
+    {
+        let mut lock_job_list = storage.jobs();
+        for content in lock_job_list.iter_mut()
         {
-            problem_index = i;
+            if content.id == queued.job_id
+            {
+                let problem_index = get_problem_index(&config.problems, content.submission.problem_id);
+                reset_job(content, &config.problems[problem_index]);
+                // the job is queued even if it could not be saved, recovery finds it again after a restart
+                let saved = storage.transaction(&mut || {
+                    storage.save_job(content)?;
+                    storage.append_event(audit::ACTOR_SYSTEM, audit::Change::job_requeued { job: content.clone() })
+                });
+                if let Err(error) = saved {log::error!("Job {} could not be saved: {}", content.id, error);}
+                events::publish_state(content);
+            }
         }
     }
 
-    // update result for user
-    for rank_info in contest.users.iter_mut()
+    log::info!("Job {} queued again", queued.job_id);
+    // requeued jobs are judged before newer ones
+    JOB_QUEUE.lock().unwrap().push_front(queued);
+}
+
+// used in distributed mode
+// stores the result reported by a worker and updates the contest information
+// returns None if the job does not exist
+pub fn finish_queued_job(queued: &worker::QueuedJob, report: worker::JobReport, config: &config::Config, 
+    actor: &str, storage: &dyn storage::Storage) -> Result<Option<job::ResponseContent>, storage::Error>
+{
+    let found = storage.jobs().iter().find(|content| content.id == queued.job_id).cloned();
+    let mut output = match found
     {
-        if rank_info.user.id == body.user_id
+        Some(content) => content,
+        None => return Ok(None),
+    };
+    output.updated_time = Utc::now();
+    output.state = "Finished".to_string();
+    output.result = report.result.clone();
+    output.score = report.score;
+    output.cases = report.cases.clone();
+
+    // the job and the contest are stored together
+    // the progress of the user and the best times are put back if they cannot be saved
+    let problem_index = get_problem_index(&config.problems, output.submission.problem_id);
+    let problem = &config.problems[problem_index];
+    {
+        let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
+        let contest = &mut lock_contest_list[output.submission.contest_id as usize];
+        let old_case_times = contest.case_times.clone();
+        let old_rank_info = contest.users.iter().find(|rank_info| rank_info.user.id == output.submission.user_id).cloned();
+
+        record_case_times(&output.submission, problem, problem_index, &output.cases, contest);
+        update_contest(&output.submission, output.score, output.created_time, contest);
+        let saved = storage.transaction(&mut || {
+            storage.save_job(&output)?;
+            storage.append_event(actor, audit::Change::job_judged { job: output.clone() })?;
+            if problem.ty == config::ProblemType::dynamic_ranking
+            {
+                storage.save_case_times(contest)?;
+            }
+            storage.save_participant(contest, output.submission.user_id)
+        });
+        if let Err(error) = saved
         {
-            rank_info.latest_scores[problem_index] = score_sum;
-
-            if score_sum >= rank_info.highest_scores[problem_index]
+            contest.case_times = old_case_times;
+            if let Some(old_rank_info) = old_rank_info
             {
-                rank_info.highest_scores[problem_index] = score_sum;
-                rank_info.latest_submission = created_time;
+                for rank_info in contest.users.iter_mut().filter(|rank_info| rank_info.user.id == old_rank_info.user.id)
+                {
+                    *rank_info = old_rank_info.clone();
+                }
             }
+            return Err(error);
+        }
+    }
+    storage.keep_job(&output);
 
-            if !is_put
+    // webhooks are only fired once the job is stored, so that receivers can fetch it
+    if queued.is_rejudge
+    {
+        webhook::fire("rejudge_finished", &output);
+    }
+    else
+    {
+        // the accepted jobs of the problem tell whether this is the first one
+        let query = storage::JobQuery
+        {
+            contest_id: Some(output.submission.contest_id),
+            problem_id: Some(output.submission.problem_id),
+            result: Some(job::PossibleResult::Accepted),
+            ..Default::default()
+        };
+        match storage.query_jobs(&query)
+        {
+            Ok(accepted) => webhook::fire_job_finished(&output, &accepted),
+            Err(error) =>
             {
-                rank_info.submission_count += 1;
+                log::error!("The accepted jobs of job {} could not be read: {}", output.id, error);
+                webhook::fire("job_finished", &output);
             }
-
-            rank_info.competitive_score_sum;
         }
     }
-    // if SQL mode activated, then load information into database
-    sql::push_contest(&connection, &mut lock_contest_list);
 
-    // END update contest information
-
-    // if SQL mode activated, then load information into database
-    sql::push_job_count(&connection, *lock_job_id_count);
-    
-
-    return job.response.content;
+    log::info!("Job {} finished", output.id);
+    events::publish_state(&output);
+    return Ok(Some(output));
 }
 
 // checks if user exists
-fn exists_user(user_id: u32, connection: &Connection) -> bool
+fn exists_user(user_id: u32, storage: &dyn storage::Storage) -> bool
 {
-    let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = USER_LIST.lock().unwrap();
-    sql::get_user(&connection, &mut lock_user_list);
+    let lock_user_list: std::sync::MutexGuard<Vec<user::User>> = storage.users();
     for user in lock_user_list.iter()
     {
         if user.id == user_id 
@@ -601,10 +478,9 @@ fn exists_user(user_id: u32, connection: &Connection) -> bool
 }
 
 // checks if user is in the contest
-fn check_user_in_contest(body: &web::Json<post_job::PostJob>, connection: &Connection) -> bool
+fn check_user_in_contest(body: &web::Json<post_job::PostJob>, storage: &dyn storage::Storage) -> bool
 {
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-    sql::get_contest(&connection, &mut lock_contest_list);
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
 
     let user_ids = lock_contest_list[body.contest_id as usize].user_ids.clone();
     for id in user_ids
@@ -615,10 +491,9 @@ fn check_user_in_contest(body: &web::Json<post_job::PostJob>, connection: &Conne
 }
 
 // checks if problem is in the contest
-fn check_problem_in_contest(body: &web::Json<post_job::PostJob>, connection: &Connection) -> bool
+fn check_problem_in_contest(body: &web::Json<post_job::PostJob>, storage: &dyn storage::Storage) -> bool
 {
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-    sql::get_contest(&connection, &mut lock_contest_list);
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
     let problem_ids = lock_contest_list[body.contest_id as usize].problem_ids.clone();
     for id in problem_ids
     {
@@ -628,30 +503,28 @@ fn check_problem_in_contest(body: &web::Json<post_job::PostJob>, connection: &Co
 }
 
 // checks if the contest has started yet
-fn check_valid_start(body: &web::Json<post_job::PostJob>, connection: &Connection) -> bool
+fn check_valid_start(body: &web::Json<post_job::PostJob>, storage: &dyn storage::Storage) -> bool
 {
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-    sql::get_contest(&connection, &mut lock_contest_list);
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
     let start_time = lock_contest_list[body.contest_id as usize].from.clone();
     if Utc::now() < start_time {return false;}
     return true;
 }
 
 // checks that the contest has not ended yet
-fn check_valid_end(body: &web::Json<post_job::PostJob>, connection: &Connection) -> bool
+fn check_valid_end(body: &web::Json<post_job::PostJob>, storage: &dyn storage::Storage) -> bool
 {
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-    sql::get_contest(&connection, &mut lock_contest_list);
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
     let end_time = lock_contest_list[body.contest_id as usize].to.clone();
     if Utc::now() > end_time {return false;}
     return true;
 }
 
 // checks that the user has not passed the submission limit for the contest
-fn check_submission_limit(body: &web::Json<post_job::PostJob>, connection: &Connection) -> bool
+// the count includes the jobs of the user that are not judged yet, see count_submission
+fn check_submission_limit(body: &web::Json<post_job::PostJob>, storage: &dyn storage::Storage) -> bool
 {
-    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-    sql::get_contest(&connection, &mut lock_contest_list);
+    let lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
     
     let limit = lock_contest_list[body.contest_id as usize].submission_limit.clone();
     if limit == 0 {return true;}
@@ -672,40 +545,124 @@ fn check_submission_limit(body: &web::Json<post_job::PostJob>, connection: &Conn
     return false;
 }
 
-// posts a new job
-#[post("/jobs")]
-async fn post_jobs(body: web::Json<post_job::PostJob>, data_config: web::Data<Arc<Mutex<config::Config>>>) -> impl Responder 
+// why a job was not submitted, with the seconds to wait before submitting again if it was rate limited or out of its daily quota
+struct Rejection
+{
+    status: StatusCode,
+    error: job::Error,
+    retry_after: Option<u64>,
+}
+
+impl From<(StatusCode, job::Error)> for Rejection
+{
+    fn from((status, error): (StatusCode, job::Error)) -> Rejection
+    {
+        Rejection { status, error, retry_after: None }
+    }
+}
+
+// checks that the job can be submitted, then judges it or queues it for a worker
+// returns the status and the error to respond with if the job is rejected
+// jobs submitted with a session or an API key belong to its user, whatever user_id says
+// anonymous jobs may only name their user_id when config.auth.anonymous_role manages users
+fn submit_job(mut body: web::Json<post_job::PostJob>, caller: permission::Caller, ip: Option<String>,
+    data_config: web::Data<Arc<Mutex<config::Config>>>, storage: &dyn storage::Storage) -> Result<job::ResponseContent, Rejection>
 {
-    let connection = CONNECTION.lock().unwrap();
+    match &caller.principal
+    {
+        Some(principal) => body.user_id = principal.user_id,
+        None if !caller.has(permission::Permission::manage_users) =>
+            return Err((StatusCode::UNAUTHORIZED, auth::unauthorized("Authentication required.")).into()),
+        None => {},
+    }
+
+    let lock = storage.lock();
     // START CHECK VALID CONDITIONS
-    if !exists_user(body.user_id, &connection) 
+    if !exists_user(body.user_id, storage) 
     {
-        return HttpResponse::NotFound().json(job::Error {
+        return Err((StatusCode::NOT_FOUND, job::Error {
                 code: 3,
                 reason: "ERR_NOT_FOUND".to_string(),
                 message: "HTTP 404 Not Found".to_string(),
-            });
+            }).into());
+    }
+    if storage.users().iter().any(|user| user.id == body.user_id && user.deactivated)
+    {
+        return Err((StatusCode::FORBIDDEN, permission::forbidden(&format!("User {} is deactivated.", body.user_id))).into());
     }
     if body.contest_id != 0
     {
-        if !check_user_in_contest(&body, &connection) || !check_problem_in_contest(&body, &connection) ||
-        !check_valid_start(&body, &connection) || !check_valid_end(&body, &connection)
+        if !check_user_in_contest(&body, storage) || !check_problem_in_contest(&body, storage) ||
+        !check_valid_start(&body, storage) || !check_valid_end(&body, storage)
         {
-            return HttpResponse::BadRequest().json(job::Error {
+            return Err((StatusCode::BAD_REQUEST, job::Error {
                 code: 1,
                 reason: "ERR_INVALID_ARGUMENT".to_string(),
                 message: "HTTP 400 Bad Request".to_string(),
-            });
+            }).into());
         }
-        if !check_submission_limit(&body, &connection)
+        if !check_submission_limit(&body, storage)
         {
-            return HttpResponse::BadRequest().json(job::Error {
+            return Err((StatusCode::BAD_REQUEST, job::Error {
                 code: 4,
                 reason: "ERR_RATE_LIMIT".to_string(),
                 message: "HTTP 400 Bad Request".to_string(),
-            });
+            }).into());
         }
     }
     // END CHECK VALID CONDITIONS
-    return HttpResponse::Ok().json(post_jobs_action(body, data_config, false, &connection));
-}    
\ No newline at end of file
+
+    // jobs are refused once a quota is used up, see quota
+    let quotas = data_config.lock().unwrap().quotas.clone();
+    match quota::check(&quotas, body.user_id, body.contest_id, storage)
+    {
+        Ok(None) => (),
+        Ok(Some((message, retry_after))) =>
+        {
+            return Err(Rejection { status: StatusCode::TOO_MANY_REQUESTS, error: quota::error(message), retry_after });
+        }
+        Err(error) => return Err(storage::unreadable(error).into()),
+    }
+
+    // rate limits are checked last, so that jobs rejected for other reasons do not use them up
+    let rate_limits = data_config.lock().unwrap().rate_limits.clone();
+    if let Err(retry_after) = rate_limit::take(&rate_limits, "jobs", Some(body.user_id), ip.as_deref())
+    {
+        return Err(Rejection { status: StatusCode::TOO_MANY_REQUESTS, error: rate_limit::error(retry_after), retry_after: Some(retry_after) });
+    }
+
+    // in distributed mode the job is judged later by a worker
+    let is_distributed = *IS_DISTRIBUTED.lock().unwrap();
+    if is_distributed
+    {
+        let config = data_config.lock().unwrap();
+        return queue_job(&body, &config, storage).map_err(|error| storage::internal(error).into());
+    }
+    let actor = audit::user_actor(body.user_id);
+    return post_jobs_action(body, data_config, None, &actor, lock, storage).map_err(|error| storage::internal(error).into());
+}
+
+// posts a new job
+// judging blocks, so it runs on the blocking thread pool while the server keeps answering other requests
+#[post("/jobs", wrap = "permission::Require(permission::Permission::submit)")]
+async fn post_jobs(req: HttpRequest, body: web::Json<post_job::PostJob>, caller: web::ReqData<permission::Caller>,
+    data_config: web::Data<Arc<Mutex<config::Config>>>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
+{
+    let caller = caller.into_inner();
+    let ip = req.peer_addr().map(|address| address.ip().to_string());
+    match web::block(move || submit_job(body, caller, ip, data_config, data_storage.get_ref())).await
+    {
+        Ok(Ok(content)) => HttpResponse::Ok().json(content),
+        Ok(Err(rejection)) =>
+        {
+            let mut response = HttpResponse::build(rejection.status);
+            if let Some(retry_after) = rejection.retry_after
+            {
+                response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+            }
+            response.json(rejection.error)
+        }
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Job could not be judged.".to_string())),
+    }
+}
\ No newline at end of file
@@ -1,41 +1,77 @@
 use actix_web::{put, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
 use std::sync::Mutex;
 use std::sync::Arc;
 
-use crate::JOB_LIST;
 use crate::job;
-use crate::sql;
 use crate::config;
 use crate::function_post_jobs;
 use crate::web::Json;
-use crate::CONNECTION;
+use crate::worker;
+use crate::events;
+use crate::storage;
+use crate::audit;
+use crate::permission;
+use crate::JOB_QUEUE;
+use crate::IS_DISTRIBUTED;
+
+// judges a job again, or puts it back into the queue in distributed mode
+// returns the status and the error to respond with if the job does not exist
+// actor is who asked for the rejudge, as written to the audit log
+pub fn rejudge_job(job_id: u32, data_config: web::Data<Arc<Mutex<config::Config>>>, actor: &str,
+    storage: &dyn storage::Storage) -> Result<job::ResponseContent, (StatusCode, job::Error)>
+{
+    let lock = storage.lock();
+
+    // find job based on the id provided
+    let content = match storage.job(job_id).map_err(storage::unreadable)?
+    {
+        Some(content) => content,
+        None => return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} not found.", job_id).to_string()))),
+    };
+
+    // in distributed mode the job is put back into the queue for a worker
+    let is_distributed = *IS_DISTRIBUTED.lock().unwrap();
+    if is_distributed
+    {
+        let config = data_config.lock().unwrap();
+        let problem_index = function_post_jobs::get_problem_index(&config.problems, content.submission.problem_id);
+        let mut output = content.clone();
+        function_post_jobs::reset_job(&mut output, &config.problems[problem_index]);
+        output.created_time = output.updated_time;
+
+        storage.transaction(&mut || {
+            storage.save_job(&output)?;
+            storage.append_event(actor, audit::Change::job_rejudged { job: output.clone() })
+        }).map_err(storage::internal)?;
+        storage.keep_job(&output);
+
+        JOB_QUEUE.lock().unwrap().push_back(worker::QueuedJob { job_id: output.id, is_rejudge: true });
+        events::publish_state(&output);
+        return Ok(output);
+    }
+
+    // run the job again, the new result is stored by post_jobs_action
+    // the storage is not locked meanwhile, so that it can still be read and changed
+    let output = function_post_jobs::post_jobs_action(Json(content.submission), data_config, Some(job_id), actor, lock, storage).map_err(storage::internal)?;
+    log::info!("Successfull put with ID: {}", job_id);
+    return Ok(output);
+}
 
 // puts a job
-#[put("/jobs/{jobId}")]
+// judging blocks, so it runs on the blocking thread pool while the server keeps answering other requests
+#[put("/jobs/{jobId}", wrap = "permission::Require(permission::Permission::rejudge)")]
 #[allow(non_snake_case)]
-async fn get_jobs_jobId(jobId: web::Path<u32>, data_config: web::Data<Arc<Mutex<config::Config>>>) -> impl Responder 
+async fn get_jobs_jobId(jobId: web::Path<u32>, caller: web::ReqData<permission::Caller>, data_config: web::Data<Arc<Mutex<config::Config>>>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
 {
-    let connection = CONNECTION.lock().unwrap();
-    
-    let mut lock_job_list = JOB_LIST.lock().unwrap();
-    // if SQL storage activated, then load from database
-    sql::get_job(&connection, &mut lock_job_list);
-    // find job based on the id provided
-    for content in lock_job_list.iter_mut()
+    let job_id = jobId.into_inner();
+    let actor = caller.actor();
+    match web::block(move || rejudge_job(job_id, data_config, &actor, data_storage.get_ref())).await
     {
-        if jobId.to_owned() == content.id
-        {
-            // run the job again and store the new result
-            *content = function_post_jobs::post_jobs_action(Json(content.submission.clone()), data_config, true, &connection);
-            content.id = jobId.to_owned();
-            log::info!("Successfull put with ID: {}", jobId.to_owned());
-
-            let output = content.clone();
-            
-            sql::push_job(&connection, &mut lock_job_list);
-
-            return HttpResponse::Ok().json(output);
-        }
+        Ok(Ok(content)) => HttpResponse::Ok().json(content),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), format!("Job {} could not be judged.", job_id).to_string())),
     }
-    return HttpResponse::NotFound().json(job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} not found.", jobId.to_owned()).to_string()));
 }
\ No newline at end of file
@@ -21,27 +21,28 @@ impl Error
     }
 }
 
-#[derive(Serialize, Debug, Clone)]
-pub struct Request
-{
-    pub path: String,
-    pub method: String,
-    pub content: post_job::PostJob,
-}
-
+// the aliases allow results to be read back in the same format they are serialized in
 #[derive(Debug, PartialEq, Clone, Deserialize)]
 pub enum PossibleResult
 {
     Waiting,
     Running,
     Accepted,
+    #[serde(alias = "Compilation Error")]
     CompilationError,
+    #[serde(alias = "Compilation Success")]
     CompilationSuccess,
+    #[serde(alias = "Wrong Answer")]
     WrongAnswer,
+    #[serde(alias = "Runtime Error")]
     RuntimeError,
+    #[serde(alias = "Time Limit Exceeded")]
     TimeLimitExceeded,
+    #[serde(alias = "Memory Limit Exceeded")]
     MemoryLimitExceeded,
+    #[serde(alias = "System Error")]
     SystemError,
+    #[serde(alias = "SPJ Error")]
     SPJError,
     Skipped,
 }
@@ -69,16 +70,30 @@ impl Serialize for PossibleResult {
     }
 }
 
+// what a submission did on a sample case, hidden cases never get this
+#[derive(Serialize, Debug, Clone, Deserialize)]
+pub struct CaseFeedback
+{
+    pub input: String,
+    pub answer: String,
+    pub output: String,
+    pub stderr: String,
+}
+
 #[derive(Serialize, Debug, Clone, Deserialize)]
 pub struct Case
 {
     pub id: u32,
     pub result: PossibleResult,
     pub info: String,
-    pub time: i64
+    pub time: i64,
+    #[serde(default)]
+    pub memory: i64, // in kilobytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<CaseFeedback>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, Deserialize)]
 pub struct ResponseContent
 {
     pub id: u32,
@@ -89,20 +104,4 @@ pub struct ResponseContent
     pub result: PossibleResult,
     pub score: f32,
     pub cases: Vec<Case>,
-}
-
-#[derive(Serialize, Debug, Clone)]
-pub struct Response
-{
-    pub status: u32,
-    pub content: ResponseContent,
-}
-
-#[derive(Serialize, Debug, Clone)]
-pub struct Job
-{
-    pub poll_for_job: bool,
-    pub request: Request,
-    pub response: Response,
-    // pub restart_server: bool,
 }
\ No newline at end of file
@@ -0,0 +1,517 @@
+use std::fs::File;
+use std::io::Read;
+use std::process::Command;
+use std::os::unix::process::CommandExt;
+use std::process::Stdio;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::job;
+use crate::config;
+use crate::compare_functions;
+use crate::spj;
+
+// functions in this file only depend on the submission and the problem configuration
+// so that they can be shared by the server and the judge workers
+
+// the most bytes of each file shown in the feedback of a sample case
+const FEEDBACK_LIMIT: usize = 65536;
+
+// runs an individual testcase
+fn run_test_case(job_result: &mut job::PossibleResult, case_result: &mut job::PossibleResult, case_info: &mut String,
+    case: &config::Case, problem: &config::Problem, score_sum: &mut f32, time: &mut i64, memory: &mut i64, dir: &str)
+{
+
+    // opens the file where input is obtained from
+    let in_file = match File::open(case.input_file.clone())
+    {
+        Ok(file) => file,
+        Err(_) =>
+        {
+            panic!("input file not found"); // TODO
+        }
+    };
+
+    // creates and opens temporary file where results stored
+    let out_file_path = format!("{}/test.out", dir);
+    let out_file = match File::create(out_file_path.clone())
+    {
+        Ok(file) => file,
+        Err(_) =>
+        {
+            panic!("output file not created"); // TODO
+        }
+    };
+
+    // the error output is kept so that it can be shown for sample cases
+    let err_file = match File::create(format!("{}/test.err", dir))
+    {
+        Ok(file) => file,
+        Err(_) =>
+        {
+            panic!("error file not created"); // TODO
+        }
+    };
+
+    // start running test case, the child is killed if it exceeds the time limit
+    // the memory of the case is compared with its limit after the run, so the program is not capped
+    let execution = execute(&format!("{}/test.exe", dir), Stdio::from(in_file), Stdio::from(out_file),
+        Stdio::from(err_file), case.time_limit, 0);
+    *time = execution.time;
+    *memory = execution.memory;
+
+    // the peak memory is in kilobytes, the limit in bytes, a program over both limits is reported for its memory
+    if case.memory_limit > 0 && execution.memory as u64 * 1024 > case.memory_limit
+    {
+        *job_result = job::PossibleResult::MemoryLimitExceeded;
+        *case_result = job::PossibleResult::MemoryLimitExceeded;
+    }
+    else if execution.timed_out
+    {
+        *job_result = job::PossibleResult::TimeLimitExceeded;
+        *case_result = job::PossibleResult::TimeLimitExceeded;
+    }
+    // if runtime error, then set the case and job results to RuntimeError
+    else if execution.exit_code != Some(0)
+    {
+        *case_result = job::PossibleResult::RuntimeError;
+        *job_result = job::PossibleResult::RuntimeError;
+    }
+    else
+    {
+        // open answer for this testcase
+        let ans_file = match File::open(case.answer_file.clone())
+        {
+            Ok(file) => file,
+            Err(_) => {
+                panic!("missing case data");
+            }
+        };
+
+        // open output
+        let out_file2 = match File::open(out_file_path.clone())
+        {
+            Ok(file) => file,
+            Err(_) =>
+            {
+                panic!("could not create file");
+            }
+        };
+
+        let mut accepted: bool = false;
+        let mut info: String = "".to_string();
+        let mut spj_error: bool = false;
+        let mut correntness_ratio: f32 = 1.0;
+
+        // select type of compare and compare answer with output to yield result for the case
+        if problem.ty == config::ProblemType::standard
+        {
+            accepted = compare_functions::compare_standard(out_file2, ans_file);
+        }
+        else if problem.ty == config::ProblemType::strict
+        {
+            accepted = compare_functions::compare_strict(out_file2, ans_file);
+        }
+        else if problem.ty == config::ProblemType::spj
+        {
+            // see this compare function in spj file
+            let spj_dir = format!("{}/SPJDIR", dir);
+            (accepted, info, spj_error) = spj::compare_spj(out_file_path.clone(),
+            case.answer_file.clone(), problem, &spj_dir);
+            match std::fs::remove_dir_all(spj_dir)
+            {
+                Ok(_) => {}
+                Err(_) => {} // TODO
+            }
+        }
+        else if problem.ty == config::ProblemType::dynamic_ranking
+        {
+            if let Some(competitive_ratio) = problem.misc.dynamic_ranking_ratio
+            {
+                // calculates first component of score
+                correntness_ratio = 1.0 - competitive_ratio;
+                // the second component of score is calculated by the server from the time of the case
+                accepted = compare_functions::compare_standard(out_file2, ans_file);
+            }
+            else
+            {
+                panic!("dynamic_ranking mode selected but no ratio was provided");
+            }
+        }
+        if spj_error
+        {
+            *case_result = job::PossibleResult::SPJError;
+        }
+        else {
+            if accepted {
+                *case_result = job::PossibleResult::Accepted;
+                *case_info = info;
+                *score_sum += case.score * correntness_ratio;
+            }
+            else
+            {
+                *case_info = info;
+                *case_result = job::PossibleResult::WrongAnswer;
+            }
+        }
+    }
+}
+
+// writes the source code into the temporary directory dir and compiles it into dir/test.exe
+// returns the messages of the compiler if compilation failed
+pub fn compile(source_code: &str, language: &config::Language, dir: &str) -> Result<(), String>
+{
+    // creating temporary directory for problem
+    match std::fs::create_dir(dir)
+    {
+        Ok(_) => {}
+        Err(_) => {}
+    }
+
+    let file_name = language.get_file_name();
+
+    // writing source code into file
+    match std::fs::write(format!("{}/{}", dir, file_name), source_code)
+    {
+        Ok(_) => {}
+        Err(_) => {} // TODO
+    }
+
+    // obrains the command to be executed to run the code
+    let mut commands: Vec<String> = vec![];
+    for i in &language.command
+    {
+        if i == &language.command[0] {continue;} // ignore first element
+        else if i == "%INPUT%" {commands.push(format!("{}/{}", dir, file_name));}
+        else if i == "%OUTPUT%" {commands.push(format!("{}/test.exe", dir));}
+        else {commands.push(i.clone());}
+    }
+
+    // uses commands to compile the source code
+    match Command::new(&language.command[0])
+                .args(commands)
+                .output()
+    {
+        Ok(output) if output.status.success() => return Ok(()),
+        Ok(output) => return Err(String::from_utf8_lossy(&output.stderr).to_string()),
+        Err(err) => return Err(err.to_string()),
+    }
+}
+
+// result of running a program once
+pub struct Execution
+{
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub time: i64, // in microseconds
+    pub memory: i64, // peak resident memory, in kilobytes
+    pub timed_out: bool,
+}
+
+// runs a program, killing it once it runs for more than time_limit microseconds
+// memory_limit caps the address space of the program in bytes, 0 means no limit, allocations past it fail
+// measures the running time and the peak memory usage of the program
+pub fn execute(program: &str, stdin: Stdio, stdout: Stdio, stderr: Stdio, time_limit: u64, memory_limit: u64) -> Execution
+{
+    let mut execution = Execution { exit_code: None, signal: None, time: 0, memory: 0, timed_out: false };
+
+    let mut command = Command::new(program);
+    command.stdin(stdin).stdout(stdout).stderr(stderr);
+    if memory_limit > 0
+    {
+        let limit = libc::rlimit { rlim_cur: memory_limit as libc::rlim_t, rlim_max: memory_limit as libc::rlim_t };
+        // runs in the child between fork and exec, where only async-signal-safe calls such as setrlimit are allowed
+        unsafe
+        {
+            command.pre_exec(move || {
+                match libc::setrlimit(libc::RLIMIT_AS, &limit)
+                {
+                    0 => Ok(()),
+                    _ => Err(std::io::Error::last_os_error()),
+                }
+            });
+        }
+    }
+
+    let start_time = Instant::now();
+    let mut child = match command.spawn()
+    {
+        Ok(child) => child,
+        Err(_) => return execution,
+    };
+    let pid = child.id() as libc::pid_t;
+    let timeout = Duration::from_micros(time_limit);
+
+    loop
+    {
+        let mut status: libc::c_int = 0;
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+        // wait4 is used instead of wait so that the resource usage of the child is known
+        let ret = unsafe { libc::wait4(pid, &mut status, libc::WNOHANG, &mut usage) };
+        if ret == pid
+        {
+            execution.time = start_time.elapsed().as_micros() as i64;
+            execution.memory = usage.ru_maxrss as i64;
+            if libc::WIFEXITED(status) {execution.exit_code = Some(libc::WEXITSTATUS(status));}
+            if libc::WIFSIGNALED(status) {execution.signal = Some(libc::WTERMSIG(status));}
+            return execution;
+        }
+        if ret < 0
+        {
+            return execution;
+        }
+
+        // the child is killed once, and then waited for like any other child
+        if !execution.timed_out && start_time.elapsed() > timeout
+        {
+            execution.timed_out = true;
+            child.kill().ok();
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+// reads at most limit bytes of a file
+// returns the text and whether the file was longer than the limit
+pub fn read_excerpt(path: &str, limit: usize) -> (String, bool)
+{
+    let mut bytes: Vec<u8> = vec![];
+    if let Ok(file) = File::open(path)
+    {
+        // one more byte is read to know if the file was truncated
+        file.take(limit as u64 + 1).read_to_end(&mut bytes).ok();
+    }
+    let truncated = bytes.len() > limit;
+    bytes.truncate(limit);
+    return (String::from_utf8_lossy(&bytes).to_string(), truncated);
+}
+
+// returns the input, answer and outputs of a sample case that was just run in dir
+// hidden cases only get their result, time and memory
+fn sample_feedback(case: &config::Case, dir: &str) -> Option<job::CaseFeedback>
+{
+    if !case.sample
+    {
+        return None;
+    }
+    return Some(job::CaseFeedback {
+        input: read_excerpt(&case.input_file, FEEDBACK_LIMIT).0,
+        answer: read_excerpt(&case.answer_file, FEEDBACK_LIMIT).0,
+        output: read_excerpt(&format!("{}/test.out", dir), FEEDBACK_LIMIT).0,
+        stderr: read_excerpt(&format!("{}/test.err", dir), FEEDBACK_LIMIT).0,
+    });
+}
+
+// compiles a submission in the temporary directory dir and runs it on every case of the problem
+// on_case is called every time a case gets its result, so that progress can be reported
+// returns the result of the job, its score and the result of each case (case 0 is the compilation)
+pub fn judge_submission(source_code: &str, language: &config::Language, problem: &config::Problem, dir: &str,
+    on_case: &mut dyn FnMut(&job::Case)) -> (job::PossibleResult, f32, Vec<job::Case>)
+{
+    let mut results: Vec<job::Case> = vec![];
+    let mut score_sum: f32 = 0.0;
+
+    let mut job_result: job::PossibleResult;
+
+    match compile(source_code, language, dir)
+    {
+        Ok(_) => {
+            job_result = job::PossibleResult::CompilationSuccess;
+            results.push(job::Case {
+                id: 0,
+                result: job::PossibleResult::CompilationSuccess,
+                info: "".to_string(),
+                time: 0,
+                memory: 0,
+                feedback: None,
+            })
+        }
+        Err(_) =>
+        {
+            job_result = job::PossibleResult::CompilationError;
+            results.push(job::Case {
+                id: 0,
+                result: job::PossibleResult::CompilationError,
+                info: "".to_string(),
+                time: 0,
+                memory: 0,
+                feedback: None,
+            });
+        }
+    }
+    on_case(&results[0]);
+
+    let mut case_id = 1;
+
+    // if packing mode is on then run cases pack by pack
+    if let Some(packing) = problem.misc.packing.clone()
+    {
+        // loops through each pack
+        for i in 0..packing.len()
+        {
+            let mut accept = true;
+            // loops through each case in a pack
+            for j in 0..packing[i].len()
+            {
+                // if a case in the pack is failed, then the rest are skipped
+                if accept == false
+                {
+                    results.push(job::Case {
+                        id: packing[i][j],
+                        result: job::PossibleResult::Skipped,
+                        info: "".to_string(),
+                        time: 0,
+                        memory: 0,
+                        feedback: None,
+                    });
+                    on_case(&results[results.len() - 1]);
+                    continue;
+                }
+
+                let mut case_result: job::PossibleResult = job::PossibleResult::Waiting;
+
+                if job_result == job::PossibleResult::CompilationError
+                {
+                    results.push(job::Case {
+                        id: packing[i][j],
+                        result: case_result,
+                        info: "".to_string(),
+                        time: 0,
+                        memory: 0,
+                        feedback: None,
+                    });
+                    continue;
+                }
+
+                let mut info: String = "".to_string();
+                let mut time: i64 = 0;
+                let mut memory: i64 = 0;
+                let case = &problem.cases[packing[i][j] as usize -1];
+
+                // runs the test case and stores the information into variables
+                run_test_case(&mut job_result, &mut case_result, &mut info,
+                    case, problem, &mut score_sum, &mut time, &mut memory, dir
+                );
+                let feedback = sample_feedback(case, dir);
+
+                if case_result != job::PossibleResult::Accepted
+                {
+                    accept = false;
+                }
+
+                // stores the result into vector
+                results.push(job::Case {
+                    id: packing[i][j],
+                    result: case_result,
+                    info: info,
+                    time: time,
+                    memory: memory,
+                    feedback: feedback,
+                });
+                on_case(&results[results.len() - 1]);
+
+            }
+        }
+    }
+    // if not packing mode, then run each test case in linear order
+    else
+    {
+        for case in &problem.cases
+        {
+
+            let mut case_result: job::PossibleResult = job::PossibleResult::Waiting;
+
+            if job_result == job::PossibleResult::CompilationError
+            {
+                results.push(job::Case {
+                    id: case_id,
+                    result: case_result,
+                    info: "".to_string(),
+                    time: 0,
+                    memory: 0,
+                    feedback: None,
+                });
+                case_id += 1;
+                continue;
+            }
+
+            let mut info: String = "".to_string();
+            let mut time: i64 = 0;
+            let mut memory: i64 = 0;
+
+            // runs test case and store information into variables
+
+            run_test_case(&mut job_result, &mut case_result, &mut info, case,
+                problem, &mut score_sum, &mut time, &mut memory, dir
+            );
+            let feedback = sample_feedback(case, dir);
+
+            // pushes result into vector
+
+            results.push(job::Case {
+                id: case_id,
+                result: case_result,
+                info: info,
+                time: time,
+                memory: memory,
+                feedback: feedback,
+            });
+            on_case(&results[results.len() - 1]);
+            case_id += 1;
+        }
+    }
+
+    // AFTER ALL TESTCASES HAVE BEEN RUN:
+
+    // only calculate exact results if timelimit was not exceeded, and there were no other errors
+    if job_result == job::PossibleResult::TimeLimitExceeded {}
+    else if job_result == job::PossibleResult::MemoryLimitExceeded {}
+    else if job_result == job::PossibleResult::RuntimeError {}
+    else if job_result == job::PossibleResult::CompilationError {}
+    else
+    {
+        // in dynamic_ranking mode, we have to account fot the fact that the maximum possible
+        // score is 100.0 * correntness_ratio
+        if problem.ty == config::ProblemType::dynamic_ranking
+        {
+
+            if let Some(competitive_ratio) = problem.misc.dynamic_ranking_ratio
+            {
+                let correntness_ratio = 1.0 - competitive_ratio;
+                if score_sum == 100.0 * correntness_ratio
+                {
+                    job_result = job::PossibleResult::Accepted;
+                }
+                else if score_sum < 100.0* correntness_ratio
+                {
+                    job_result = job::PossibleResult::WrongAnswer;
+                }
+            }
+            else {
+                panic!("dynamic_ranking selected but no ratio provided")
+            }
+        }
+        // otherwise, no need to account for correntness_ratio
+        else
+        {
+            if score_sum == 100.0
+            {
+                job_result = job::PossibleResult::Accepted;
+            }
+            else if score_sum < 100.0
+            {
+                job_result = job::PossibleResult::WrongAnswer;
+            }
+        }
+    }
+
+    // delete temporary directory
+    match std::fs::remove_dir_all(dir)
+    {
+        Ok(_) => {}
+        Err(_) => {} // TODO
+    }
+
+    return (job_result, score_sum, results);
+}
@@ -2,6 +2,12 @@ pub mod compare_functions;
 pub mod function_get_jobs;
 pub mod function_post_jobs;
 pub mod function_put_jobs;
+pub mod function_get_usage;
+pub mod function_delete_usage;
 pub mod job;
+pub mod judge;
 pub mod post_job;
+pub mod quota;
+pub mod recovery;
+pub mod rate_limit;
 pub mod spj;
\ No newline at end of file
@@ -4,7 +4,8 @@ use serde::{Deserialize, Serialize};
 pub struct PostJob {
     pub source_code: String,
     pub language: String,
-    pub user_id: u32,
+    #[serde(default)]
+    pub user_id: u32, // taken from the session instead when the job is submitted with a bearer token
     pub contest_id: u32,
     pub problem_id: u32
 }
\ No newline at end of file
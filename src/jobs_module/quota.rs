@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc, Duration};
+use serde::Serialize;
+
+use crate::job;
+use crate::config;
+use crate::storage;
+
+// the judging used by a user, see config::Quotas
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Usage
+{
+    pub jobs: u32,
+    pub cpu_seconds: f64,
+}
+
+impl Usage
+{
+    fn add(&mut self, content: &job::ResponseContent)
+    {
+        self.jobs += 1;
+        self.cpu_seconds += cpu_seconds(content);
+    }
+
+    // describes the limit of the quota that is used up, if there is one
+    fn exceeded(&self, quota: &config::Quota) -> Option<String>
+    {
+        if let Some(jobs) = quota.jobs
+        {
+            if self.jobs >= jobs {return Some(format!("{} jobs", jobs));}
+        }
+        if let Some(cpu_seconds) = quota.cpu_seconds
+        {
+            if self.cpu_seconds >= cpu_seconds {return Some(format!("{} CPU seconds", cpu_seconds));}
+        }
+        return None;
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ContestUsage
+{
+    pub contest_id: u32,
+    #[serde(flatten)]
+    pub usage: Usage,
+}
+
+// the usage of a user listed by GET /admin/usage, today is the UTC day of the request
+// contests only appear once the user has a job counting in them
+#[derive(Serialize, Debug, Clone)]
+pub struct UserUsage
+{
+    pub user_id: u32,
+    pub today: Usage,
+    pub contests: Vec<ContestUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reset_time: Option<DateTime<Utc>>,
+}
+
+// the run times of the cases of a job, in seconds
+// cases that are still waiting have no time yet, so jobs being judged add up as they go
+fn cpu_seconds(content: &job::ResponseContent) -> f64
+{
+    content.cases.iter().map(|case| case.time.max(0)).sum::<i64>() as f64 / 1_000_000.0
+}
+
+// the start of the UTC day of a time
+fn day_start(time: DateTime<Utc>) -> DateTime<Utc>
+{
+    time.date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc()
+}
+
+pub fn error(message: String) -> job::Error
+{
+    job::Error::new(9, "ERR_QUOTA_EXCEEDED".to_string(), message)
+}
+
+// computes the usage of a user from its jobs, jobs submitted before reset_time do not count
+pub fn usage(user_id: u32, jobs: &[job::ResponseContent], reset_time: Option<DateTime<Utc>>, now: DateTime<Utc>) -> UserUsage
+{
+    let today = day_start(now);
+    let mut output = UserUsage { user_id, today: Usage::default(), contests: vec![], reset_time };
+    for content in jobs.iter()
+    {
+        if content.submission.user_id != user_id {continue;}
+        if reset_time.map_or(false, |reset_time| content.created_time < reset_time) {continue;}
+
+        if content.created_time >= today {output.today.add(content);}
+        let contest_id = content.submission.contest_id;
+        let index = match output.contests.iter().position(|contest| contest.contest_id == contest_id)
+        {
+            Some(index) => index,
+            None =>
+            {
+                output.contests.push(ContestUsage { contest_id, usage: Usage::default() });
+                output.contests.len() - 1
+            }
+        };
+        output.contests[index].usage.add(content);
+    }
+    output.contests.sort_by_key(|contest| contest.contest_id);
+    return output;
+}
+
+// checks that the user has judging left for a job in the contest
+// returns the message of the error if a quota is used up, with the seconds until the next UTC day if it is the daily quota
+// the jobs of the user are only read when there is a quota to check
+// the caller holds Storage::lock, so that jobs submitted meanwhile are counted
+pub fn check(quotas: &config::Quotas, user_id: u32, contest_id: u32, storage: &dyn storage::Storage)
+    -> Result<Option<(String, Option<u64>)>, storage::Error>
+{
+    let is_limited = |quota: &config::Quota| quota.jobs.is_some() || quota.cpu_seconds.is_some();
+    if !is_limited(&quotas.per_day) && !is_limited(&quotas.per_contest) {return Ok(None);}
+
+    let now = Utc::now();
+    let reset_time = storage.usage_reset(user_id)?;
+    let jobs = storage.query_jobs(&storage::JobQuery { user_id: Some(user_id), ..Default::default() })?;
+    let usage = usage(user_id, &jobs, reset_time, now);
+
+    if let Some(limit) = usage.today.exceeded(&quotas.per_day)
+    {
+        let tomorrow = day_start(now) + Duration::days(1);
+        let retry_after = (tomorrow - now).num_seconds().max(1) as u64;
+        return Ok(Some((format!("Daily quota of {} is used up, it is renewed at {}.", limit, tomorrow.to_rfc3339()), Some(retry_after))));
+    }
+    let contest_usage = usage.contests.iter().find(|contest| contest.contest_id == contest_id).map(|contest| contest.usage.clone()).unwrap_or_default();
+    if let Some(limit) = contest_usage.exceeded(&quotas.per_contest)
+    {
+        return Ok(Some((format!("Quota of {} in contest {} is used up.", limit, contest_id), None)));
+    }
+    return Ok(None);
+}
\ No newline at end of file
@@ -0,0 +1,96 @@
+use std::time::Instant;
+
+use crate::job;
+use crate::config;
+use crate::RATE_LIMIT_BUCKETS;
+
+// the submissions left in a token bucket, see config::RateLimit
+// tokens come back one by one as time passes, up to the count of the limit
+#[derive(Debug, Clone)]
+pub struct Bucket
+{
+    tokens: f64,
+    updated_time: Instant,
+}
+
+impl Bucket
+{
+    // a bucket nobody has taken from yet is full
+    fn new(limit: &config::RateLimit, now: Instant) -> Bucket
+    {
+        Bucket { tokens: limit.count as f64, updated_time: now }
+    }
+
+    fn refill(&mut self, limit: &config::RateLimit, now: Instant)
+    {
+        let elapsed = now.duration_since(self.updated_time).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.count as f64 / limit.seconds as f64).min(limit.count as f64);
+        self.updated_time = now;
+    }
+
+    // seconds until the bucket has a token again
+    fn wait(&self, limit: &config::RateLimit) -> f64
+    {
+        if self.tokens >= 1.0 {return 0.0;}
+        return (1.0 - self.tokens) * limit.seconds as f64 / limit.count as f64;
+    }
+}
+
+pub fn error(retry_after: u64) -> job::Error
+{
+    job::Error::new(4, "ERR_RATE_LIMIT".to_string(), format!("Too many submissions, try again in {} seconds.", retry_after))
+}
+
+// takes a submission from the buckets of the user and of the IP address, or from none of them if one is empty
+// scope tells apart the buckets of different endpoints, e.g. "jobs" and "runs", anonymous callers only have the bucket of their IP address
+// returns the seconds to wait, rounded up, if a bucket is empty
+// buckets that have not been used for longer than every limit takes to refill are full again, so they are forgotten
+pub fn take(limits: &config::RateLimits, scope: &str, user_id: Option<u32>, ip: Option<&str>) -> Result<(), u64>
+{
+    let mut keys: Vec<(String, &Vec<config::RateLimit>)> = vec![];
+    if let Some(user_id) = user_id
+    {
+        if !limits.per_user.is_empty() {keys.push((format!("{}:user:{}", scope, user_id), &limits.per_user));}
+    }
+    if let Some(ip) = ip
+    {
+        if !limits.per_ip.is_empty() {keys.push((format!("{}:ip:{}", scope, ip), &limits.per_ip));}
+    }
+    if keys.is_empty() {return Ok(());}
+
+    let now = Instant::now();
+    let mut buckets = RATE_LIMIT_BUCKETS.lock().unwrap();
+    let longest = limits.per_user.iter().chain(limits.per_ip.iter()).map(|limit| limit.seconds).max().unwrap_or(0);
+    let prefix = format!("{}:", scope);
+    buckets.retain(|key, (updated_time, _)| !key.starts_with(&prefix) || now.duration_since(*updated_time).as_secs() < longest);
+
+    let mut wait: f64 = 0.0;
+    for (key, key_limits) in keys.iter()
+    {
+        let (updated_time, key_buckets) = buckets.entry(key.clone()).or_insert_with(|| (now, vec![]));
+        *updated_time = now;
+        // the limits may have changed since the buckets were made
+        if key_buckets.len() != key_limits.len()
+        {
+            *key_buckets = key_limits.iter().map(|limit| Bucket::new(limit, now)).collect();
+        }
+        for (bucket, limit) in key_buckets.iter_mut().zip(key_limits.iter())
+        {
+            bucket.refill(limit, now);
+            wait = wait.max(bucket.wait(limit));
+        }
+    }
+    if wait > 0.0
+    {
+        return Err(wait.ceil() as u64);
+    }
+
+    for (key, _) in keys.iter()
+    {
+        for bucket in buckets.get_mut(key).unwrap().1.iter_mut()
+        {
+            bucket.tokens -= 1.0;
+        }
+    }
+    return Ok(());
+}
\ No newline at end of file
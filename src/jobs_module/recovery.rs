@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+
+use crate::job;
+use crate::config;
+use crate::contest;
+use crate::storage;
+use crate::storage::Counter;
+use crate::audit;
+
+// finds the jobs that were not finished when the server stopped, see config::Recovery
+// with system_error they are finished as System Error, with requeue their ids are returned so that they are judged again
+// the contest progress of their users is computed again from the jobs, since the server may have stopped before it was stored
+pub fn recover_jobs(config: &config::Config, storage: &dyn storage::Storage) -> Vec<u32>
+{
+    let _lock = storage.lock();
+
+    // the jobs are changed as copies, the list and the contests are only changed once they are saved
+    let mut recovered: Vec<job::ResponseContent> = storage.jobs().iter().filter(|content| content.state != "Finished").cloned().collect();
+    if config.database.recovery == config::Recovery::system_error
+    {
+        for content in recovered.iter_mut()
+        {
+            content.updated_time = Utc::now();
+            content.state = "Finished".to_string();
+            content.result = job::PossibleResult::SystemError;
+            content.score = 0.0;
+            for case in content.cases.iter_mut()
+            {
+                if case.result == job::PossibleResult::Waiting || case.result == job::PossibleResult::Running
+                {
+                    case.result = job::PossibleResult::Skipped;
+                }
+            }
+        }
+    }
+
+    // the count must stay ahead of every stored job
+    let last_query = storage::JobQuery { descending: true, limit: Some(1), ..Default::default() };
+    let last_jobs = storage.query_jobs(&last_query).expect("failed to read the last job");
+    let next_id = last_jobs.iter().map(|content| content.id + 1).max().unwrap_or(0);
+    let mut lock_job_id_count = storage.counter(Counter::Job);
+    let count_behind = *lock_job_id_count < next_id;
+
+    if recovered.is_empty() && !count_behind {return vec![];}
+
+    // the progress of each user is computed from their jobs in the contest, with the recovered ones as they are saved
+    let mut contests = storage.contests().clone();
+    for content in recovered.iter()
+    {
+        let query = storage::JobQuery
+        {
+            user_id: Some(content.submission.user_id),
+            contest_id: Some(content.submission.contest_id),
+            ..Default::default()
+        };
+        let mut jobs = storage.query_jobs(&query).expect("failed to read the jobs of a recovered job");
+        for other in jobs.iter_mut()
+        {
+            if let Some(recovered_job) = recovered.iter().find(|recovered_job| recovered_job.id == other.id)
+            {
+                *other = recovered_job.clone();
+            }
+        }
+        reconcile_participant(&mut contests[content.submission.contest_id as usize], content.submission.user_id, &jobs);
+    }
+
+    storage.transaction(&mut || {
+        if count_behind
+        {
+            storage.save_counter(Counter::Job, next_id)?;
+        }
+        for content in recovered.iter()
+        {
+            storage.save_job(content)?;
+            if content.state == "Finished"
+            {
+                storage.append_event(audit::ACTOR_SYSTEM, audit::Change::job_judged { job: content.clone() })?;
+            }
+            storage.save_participant(&contests[content.submission.contest_id as usize], content.submission.user_id)?;
+        }
+        Ok(())
+    }).expect("failed to save recovered jobs");
+    if count_behind
+    {
+        *lock_job_id_count = next_id;
+    }
+    *storage.contests() = contests;
+
+    for content in recovered.iter()
+    {
+        storage.keep_job(content);
+        log::warn!("Job {} was not finished when the server stopped", content.id);
+    }
+    if config.database.recovery == config::Recovery::requeue
+    {
+        return recovered.iter().map(|content| content.id).collect();
+    }
+    return vec![];
+}
+
+// computes the scores and submission count of a user in a contest from the jobs, in the order they were created
+// unfinished jobs only count as submissions, their score is added when they are judged again
+pub fn reconcile_participant(contest: &mut contest::Contest, user_id: u32, jobs: &Vec<job::ResponseContent>)
+{
+    let contest::Contest { id, problem_ids, users, .. } = contest;
+    if let Some(rank_info) = users.iter_mut().find(|rank_info| rank_info.user.id == user_id)
+    {
+        reconcile_progress(rank_info, *id, problem_ids, &[user_id], jobs);
+    }
+}
+
+// computes the progress in a contest from the jobs of the given users, like reconcile_participant
+// used for teams, whose progress comes from the jobs of all their members
+pub fn reconcile_progress(rank_info: &mut contest::RankInfo, contest_id: u32, problem_ids: &Vec<u32>, user_ids: &[u32],
+    jobs: &Vec<job::ResponseContent>)
+{
+    let mut user_jobs: Vec<&job::ResponseContent> = jobs.iter()
+        .filter(|content| content.submission.contest_id == contest_id && user_ids.contains(&content.submission.user_id))
+        .collect();
+    user_jobs.sort_by_key(|content| content.created_time);
+
+    rank_info.submission_count = user_jobs.len() as u32;
+    rank_info.highest_scores = vec![0.0; rank_info.highest_scores.len()];
+    rank_info.latest_scores = vec![0.0; rank_info.latest_scores.len()];
+    rank_info.latest_submission = DateTime::<Utc>::MAX_UTC;
+
+    for content in user_jobs.iter()
+    {
+        if content.state != "Finished" {continue;}
+        let problem_index = match problem_ids.iter().position(|id| *id == content.submission.problem_id)
+        {
+            Some(problem_index) => problem_index,
+            None => continue,
+        };
+
+        rank_info.latest_scores[problem_index] = content.score;
+        if content.score >= rank_info.highest_scores[problem_index]
+        {
+            rank_info.highest_scores[problem_index] = content.score;
+            rank_info.latest_submission = content.created_time;
+        }
+    }
+}
@@ -7,10 +7,10 @@ use std::io::BufRead;
 use crate::config;
 
 // function for special judge compare
-pub fn compare_spj(out_file_path: String, ans_file_path: String, config: &config::Config, problem_index: usize) -> (bool, String, bool)
+pub fn compare_spj(out_file_path: String, ans_file_path: String, problem: &config::Problem, spj_dir: &str) -> (bool, String, bool)
 {
     // creates a temporary directory for special judge
-    match std::fs::create_dir("SPJDIR") 
+    match std::fs::create_dir(spj_dir) 
     {
         Ok(_) => {}
         Err(_) => 
@@ -19,7 +19,8 @@ pub fn compare_spj(out_file_path: String, ans_file_path: String, config: &config
         }
     }
     // creates a temporary output file
-    let out_file = match File::create("SPJDIR/spj.out") 
+    let spj_out_path = format!("{}/spj.out", spj_dir);
+    let out_file = match File::create(spj_out_path.clone()) 
     {
         Ok(file) => file,
         Err(_) => 
@@ -29,7 +30,7 @@ pub fn compare_spj(out_file_path: String, ans_file_path: String, config: &config
     };
 
     let mut commands: Vec<String> = vec![];
-    if let Some(original_commands) = config.problems[problem_index].misc.special_judge.clone()
+    if let Some(original_commands) = problem.misc.special_judge.clone()
     {
         // obtains the command to be ran
         for i in original_commands.clone()
@@ -52,7 +53,7 @@ pub fn compare_spj(out_file_path: String, ans_file_path: String, config: &config
             Ok(status) if status.success() => 
             {
                 log::info!("Code ran successfully");
-                let out_file = match File::open("SPJDIR/spj.out") 
+                let out_file = match File::open(spj_out_path) 
                 {
                     Ok(file) => file,
                     Err(_) => 
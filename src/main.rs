@@ -2,23 +2,36 @@ use actix_web::{middleware::Logger, web, App, HttpServer, Responder, post};
 use env_logger;
 use log;
 use clap::Parser;
-use std::i64::MAX;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
 use lazy_static::lazy_static;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use rusqlite::Connection;
 
 mod user_module;
 use crate::user_module::user;
+use crate::user_module::stats;
+use crate::user_module::provision;
+use crate::user_module::deletion;
 use crate::user_module::function_post_users;
 use crate::user_module::function_get_users;
+use crate::user_module::function_put_users;
+use crate::user_module::function_patch_users;
+use crate::user_module::function_delete_users;
 
 mod contest_module;
 use crate::contest_module::contest;
 use crate::contest_module::function_get_contests;
 use crate::contest_module::function_post_contests;
 
+mod team_module;
+use crate::team_module::team;
+use crate::team_module::function_post_teams;
+use crate::team_module::function_get_teams;
+
 mod jobs_module;
 use crate::jobs_module::job;
 use crate::jobs_module::post_job;
@@ -27,58 +40,100 @@ use crate::jobs_module::function_post_jobs;
 use crate::jobs_module::function_get_jobs;
 use crate::jobs_module::function_put_jobs;
 use crate::jobs_module::compare_functions;
+use crate::jobs_module::judge;
+use crate::jobs_module::recovery;
+use crate::jobs_module::rate_limit;
+use crate::jobs_module::quota;
+use crate::jobs_module::function_get_usage;
+use crate::jobs_module::function_delete_usage;
 
 mod others_module;
 use crate::others_module::sql;
+use crate::others_module::migrations;
 use crate::others_module::config;
 use crate::others_module::parameters;
 
-// defining global variables and vectors mostly used for non-persistent storage (execpt last two)
+mod worker_module;
+use crate::worker_module::worker;
+use crate::worker_module::function_get_workers;
+use crate::worker_module::function_post_workers;
+
+mod events_module;
+use crate::events_module::events;
+use crate::events_module::function_get_events;
+
+mod webhook_module;
+use crate::webhook_module::webhook;
+use crate::webhook_module::function_get_webhooks;
+
+mod runs_module;
+use crate::runs_module::run;
+use crate::runs_module::function_post_runs;
+
+mod storage_module;
+use crate::storage_module::storage;
+use crate::storage_module::memory_storage;
+use crate::storage_module::sqlite_storage;
+
+mod archive_module;
+use crate::archive_module::archive;
+use crate::archive_module::function_get_export;
+use crate::archive_module::function_post_import;
+
+mod audit_module;
+use crate::audit_module::audit;
+use crate::audit_module::function_get_audit;
+
+mod auth_module;
+use crate::auth_module::auth;
+use crate::auth_module::permission;
+use crate::auth_module::api_key;
+use crate::auth_module::function_post_auth;
+use crate::auth_module::function_get_me;
+use crate::auth_module::function_post_api_keys;
+use crate::auth_module::function_get_api_keys;
+use crate::auth_module::function_delete_api_keys;
+
+mod problems_module;
+use crate::problems_module::problem;
+use crate::problems_module::function_get_problems;
+
+// defining global variables and vectors used while the server is running
+// users, contests and jobs are kept by the storage, see storage_module
 
 lazy_static! {
-    static ref JOB_ID_COUNT: Arc<Mutex<u32>> = Arc::new(Mutex::new(0));
+    // signals if jobs are judged by workers instead of by the server itself
+    static ref IS_DISTRIBUTED: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
 
-    static ref JOB_LIST: Arc<Mutex<Vec<job::ResponseContent>>> = Arc::new(Mutex::new(Vec::new()));
+    // jobs waiting for a worker, in the order they should be judged
+    static ref JOB_QUEUE: Arc<Mutex<VecDeque<worker::QueuedJob>>> = Arc::new(Mutex::new(VecDeque::new()));
 
-    static ref USER_ID_COUNT: Arc<Mutex<u32>> = Arc::new(Mutex::new(1)); // user 0 is root
+    static ref WORKER_ID_COUNT: Arc<Mutex<u32>> = Arc::new(Mutex::new(0));
 
-    static ref USER_LIST: Arc<Mutex<Vec<user::User>>> = Arc::new(Mutex::new(vec![ // root user is added by default
-        user::User {
-        id: 0,
-        name: "root".to_string(),
-      }]));
+    static ref WORKER_LIST: Arc<Mutex<Vec<worker::Worker>>> = Arc::new(Mutex::new(Vec::new()));
 
-    static ref CONTEST_ID_COUNT: Arc<Mutex<u32>> = Arc::new(Mutex::new(1)); // contest 0 has special use
+    // maps the checksum of each test data file sent to workers to its path
+    static ref DATA_FILES: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(HashMap::new()));
 
-    // contest 0 is added by default
-    // specific values are added in main()
-    static ref CONTEST_LIST: Arc<Mutex<Vec<contest::Contest>>> = Arc::new(Mutex::new(vec![
-        contest::Contest {
-        id: 0,
-        name: "root".to_string(),
-        from: DateTime::<Utc>::MAX_UTC,
-        to: DateTime::<Utc>::MIN_UTC,
-        problem_ids: vec![],
-        user_ids: vec![0],
-        submission_limit: 0,
+    // clients listening to job progress through server-sent events
+    static ref SUBSCRIBER_LIST: Arc<Mutex<Vec<events::Subscriber>>> = Arc::new(Mutex::new(Vec::new()));
 
-        users: vec![],
-    }
-    ]));
+    // used to pass events to the thread delivering webhooks, None if no webhooks are configured
+    static ref WEBHOOK_SENDER: Arc<Mutex<Option<std::sync::mpsc::Sender<(String, String)>>>> = Arc::new(Mutex::new(None));
 
-    // stores the shortest execution times for each case of each problem
-    // used in dynamic ranking
-    static ref CASE_TIMES_LIST: Arc<Mutex<Vec<Vec<i64>>>> = Arc::new(Mutex::new(Vec::new()));
+    static ref WEBHOOK_DELIVERY_ID_COUNT: Arc<Mutex<u32>> = Arc::new(Mutex::new(0));
 
-    // signals if storage should be persistent
-    static ref IS_SQL: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    static ref WEBHOOK_DELIVERY_LIST: Arc<Mutex<Vec<webhook::Delivery>>> = Arc::new(Mutex::new(Vec::new()));
 
-    // establishes a connection to the database
-    static ref CONNECTION: Arc<Mutex<Connection>> = Arc::new(Mutex::new(Connection::open("data.db").expect("database not loaded")));
+    // used to give each custom run its own temporary directory
+    static ref RUN_ID_COUNT: Arc<Mutex<u32>> = Arc::new(Mutex::new(0));
+
+    // token buckets of each user and IP address submitting jobs or runs, with when they were last used, see rate_limit
+    static ref RATE_LIMIT_BUCKETS: Arc<Mutex<HashMap<String, (std::time::Instant, Vec<rate_limit::Bucket>)>>> = Arc::new(Mutex::new(HashMap::new()));
 }
 
 // DO NOT REMOVE: used in automatic testing
-#[post("/internal/exit")]
+#[post("/internal/exit", wrap = "permission::Require(permission::Permission::administer)")]
 #[allow(unreachable_code)]
 async fn exit() -> impl Responder {
     log::info!("Shutdown as requested");
@@ -93,6 +148,38 @@ async fn main() -> std::io::Result<()> {
     // obtains information from cli arguments parser
     let cli = parameters::Cli::parse();
 
+    // only migrates the database, the config file is only read for the database path
+    if cli.migrate_only
+    {
+        let path = match (&cli.database, &cli.config)
+        {
+            (Some(path), _) => path.clone(),
+            (None, Some(filename)) => config::load(filename).expect("config file could not be loaded").database.path,
+            (None, None) => config::Database::default().path,
+        };
+        let connection = Connection::open(&path).expect("database not loaded");
+        let version = migrations::current_version(&connection).expect("failed to read schema version");
+        let result = if cli.dry_run {migrations::dry_run(&connection)} else {migrations::migrate(&connection)};
+        match result
+        {
+            Ok(applied) =>
+            {
+                println!("schema version {}, latest version {}", version, migrations::latest_version());
+                for migration in applied.iter()
+                {
+                    let verb = if cli.dry_run {"would apply"} else {"applied"};
+                    println!("{} migration {}: {}", verb, migration.version, migration.description);
+                }
+            }
+            Err(err) =>
+            {
+                eprintln!("migration failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
     // checks if config file was provided
     let filename: String;
     if let Some(valid_filename) = cli.config
@@ -104,7 +191,7 @@ async fn main() -> std::io::Result<()> {
     }
 
     // opens the config file and stores its contents in a variable of type Config
-    let config: config::Config;
+    let mut config: config::Config;
     match config::load(&filename)
     {
         Ok(valid_config) => 
@@ -114,131 +201,116 @@ async fn main() -> std::io::Result<()> {
         Err(_) => panic!("config file could not be loaded")
     }
 
-    // checks if persistent storage in SQL should be user
-    // cli.storage == true iff persistent storage mode is on
-    if cli.storage
+    // the database given on the command line takes precedence over the config file
+    if let Some(path) = cli.database
     {
-        // setting global variable to true
-        // which signals to all the modules that persistent storage is on
-        {
-            let mut is_sql = IS_SQL.lock().unwrap();
-            *is_sql = true;
-        }
-        
-        // START SQL CONNECTION
-        let connection = CONNECTION.lock().unwrap();
-
-        // initializing the database
-        sql::initialize(&connection).expect("failed to initialize database");
+        config.database.path = path;
+    }
 
-        // clears the database and inserts all default values if --reset_storage
-        if cli.reset_storage
+    // exports or imports the sql database, or creates users, and exits, see archive and provision
+    if let Some(command) = cli.command
+    {
+        let storage = sqlite_storage::SqliteStorage::open(&config, false).expect("failed to initialize database");
+        let storage: &dyn storage::Storage = &storage;
+        let _lock = storage.lock();
+        let result = match command
         {
-            sql::clear(&connection).expect("failed to clear database");
-
-            sql::insert_default_user_id_count(&connection).expect("failed to insert default value into database");
-            sql::insert_default_user_list(&connection).expect("failed to insert default value into database");
-
-            sql::insert_default_job_id_count(&connection).expect("failed to insert default value into database");
-
-            sql::insert_default_contest_id_count(&connection).expect("failed to insert default value into database");
-            sql::insert_default_contest(&connection, &config).expect("failed to insert default value into database");
+            parameters::Command::Export { path } =>
+                archive::export(storage).map_err(|error| error.to_string())
+                    .and_then(|archive| archive::write(&path, &archive)).map(|_| format!("exported to {}", path)),
+            parameters::Command::Import { path, merge } =>
+                archive::read(&path).and_then(|archive| archive::import(archive, merge, &config, storage).map_err(|(_, error)| error.message))
+                    .map(|summary| format!("imported {} users, {} contests and {} jobs", summary.users, summary.contests, summary.jobs)),
+            parameters::Command::ImportUsers { path, contest } => provision::import_file(&path, contest, storage),
+        };
+        match result
+        {
+            Ok(message) => println!("{}", message),
+            Err(err) =>
+            {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
+        return Ok(());
+    }
 
-        // // For testing:
-
-        // log::info!("Retrieving list");
-
-        // let mut lock_job_list = JOB_LIST.lock().unwrap();
-
-        // sql::get_job(&connection, &mut lock_job_list);
+    // checks if persistent storage in SQL should be used
+    // cli.storage == true iff persistent storage mode is on
+    // the database is cleared and filled with the default values if --reset_storage
+    let storage: Arc<dyn storage::Storage> = if cli.storage
+    {
+        Arc::new(sqlite_storage::SqliteStorage::open(&config, cli.reset_storage).expect("failed to initialize database"))
+    }
+    else
+    {
+        Arc::new(memory_storage::MemoryStorage::new(&config))
+    };
 
-        // log::info!("Before:");
-        // for x in lock_job_list.clone()
-        // {
-        //     log::info!("{:?}", x);
-        // }
+    // jobs that were not finished when the server stopped are finished or judged again, see recovery
+    let recovered_jobs = if cli.storage {recovery::recover_jobs(&config, storage.as_ref())} else {vec![]};
 
-        // lock_job_list.push(job::ResponseContent {
-        //     id: 90999999,
-        //     created_time: Utc::now(),
-        //     updated_time: Utc::now(),
-        //     submission: PostJob {source_code: "".to_string(), language: "Rust".to_string(), user_id: 0, contest_id: 0, problem_id: 0},
-        //     state: "finished".to_string(),
-        //     result: job::PossibleResult::Accepted,
-        //     score: 0.0,
-        //     cases: vec![]
-        // });
+    // START webhooks
+    if !config.webhooks.is_empty()
+    {
+        webhook::start(config.webhooks.clone());
 
-        // log::info!("Updated:");
-        // for x in lock_job_list.clone()
-        // {
-        //     log::info!("{:?}", x);
-        // }
+        // periodically checks for contests that started or ended
+        let watcher_storage = storage.clone();
+        actix_web::rt::spawn(async move {
+            let mut last_check = Utc::now();
+            loop
+            {
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+                let now = Utc::now();
+                {
+                    let lock_contest_list = watcher_storage.contests();
+                    webhook::fire_contest_times(&lock_contest_list, last_check, now);
+                }
+                last_check = now;
+            }
+        });
+    }
+    // END webhooks
 
-        // sql::push_job(&connection, &lock_job_list);
+    // passing in the config file so functions have access to it
+    let config_arc = Arc::new(Mutex::new(config));
 
-    }
-    
-    // START setup contest 0
+    // in distributed mode, jobs are judged by oj-judge workers
+    if cli.distributed
     {
-        let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-        
-        // fills in all the problem ids (which are provided by the config file)
-        for i in 0..config.problems.len()
         {
-            lock_contest_list[0].problem_ids.push(i as u32);
+            let mut is_distributed = IS_DISTRIBUTED.lock().unwrap();
+            *is_distributed = true;
         }
 
-        // inserts the root user (id = 0) into the list of users 
-        // other users are inserted as they are posted
-        lock_contest_list[0].users.push(
-            contest::RankInfo
+        // periodically checks for workers that stopped sending heartbeats
+        // it waits for the storage like any writer, so it has its own thread rather than blocking the server
+        let reaper_config = config_arc.clone();
+        let reaper_storage = storage.clone();
+        std::thread::spawn(move || {
+            loop
             {
-                user: user::User {
-                    id: 0,
-                    name: "root".to_string(),
-                    },
-                scores: vec![0.0; config.problems.len()],
-                rank: 0,
-                
-                highest_scores: vec![0.0 ; config.problems.len()],
-                latest_scores: vec![0.0 ; config.problems.len()],
-
-                competitive_score_sum: 0.0,
-
-                // assuming each problem has a maximum of 20 test cases
-                // can be changed if needed
-                shortest_times: vec![vec![MAX; 20]; config.problems.len()], 
-                
-                latest_submission: DateTime::<Utc>::MAX_UTC,
-                score: 0,
-                submission_count: 0,
+                std::thread::sleep(Duration::from_secs(1));
+                let config = reaper_config.lock().unwrap().clone();
+                let _lock = reaper_storage.lock();
+                function_post_workers::reap_workers(&config, reaper_storage.as_ref());
             }
-        );
+        });
     }
-    // END setup contest 0
 
-    // START setup case times list
-    // to keep track of shortest submission times for dynamic ranking
+    // recovered jobs are judged again in the background, in distributed mode they are queued for the workers
+    if !recovered_jobs.is_empty()
     {
-        let mut lock_case_list: std::sync::MutexGuard<Vec<Vec<i64>>> = CASE_TIMES_LIST.lock().unwrap();
-
-        for problem in &config.problems
-        {
-            let mut cases: Vec<i64> = vec![];
-            for _case in &problem.cases
+        let recovery_config = web::Data::new(config_arc.clone());
+        let recovery_storage = storage.clone();
+        std::thread::spawn(move || {
+            for job_id in recovered_jobs
             {
-                // each case time (in microseconds) is set to max i64 at the beginning
-                cases.push(MAX);
+                function_put_jobs::rejudge_job(job_id, recovery_config.clone(), audit::ACTOR_SYSTEM, recovery_storage.as_ref()).ok();
             }
-            lock_case_list.push(cases.clone());
-        }
+        });
     }
-    // END setup case times list
-
-    // passing in the config file so functions have access to it
-    let config_arc = Arc::new(Mutex::new(config));
 
     HttpServer::new(move || {
         App::new()
@@ -254,14 +326,60 @@ async fn main() -> std::io::Result<()> {
             .service(function_put_jobs::get_jobs_jobId)
 
             .service(function_post_users::post_users)
+            .service(function_post_users::post_users_import)
             .service(function_get_users::get_users)
+            .service(function_get_users::get_users_userId)
+            .service(function_get_users::get_users_userId_stats)
+            .service(function_patch_users::patch_users_userId)
+            .service(function_put_users::put_users_userId_role)
+            .service(function_put_users::put_users_userId_deactivated)
+            .service(function_delete_users::delete_users_userId)
 
             .service(function_post_contests::post_contests)
             .service(function_get_contests::get_contests)
             .service(function_get_contests::get_contests_contestId)
             .service(function_get_contests::get_contests_contestId_ranklist)
 
+            .service(function_post_teams::post_teams)
+            .service(function_get_teams::get_teams)
+            .service(function_get_teams::get_teams_teamId)
+
+            .service(function_post_workers::post_workers)
+            .service(function_post_workers::post_workers_workerId_heartbeat)
+            .service(function_post_workers::post_workers_workerId_poll)
+            .service(function_post_workers::post_workers_workerId_jobs_jobId_progress)
+            .service(function_post_workers::post_workers_workerId_jobs_jobId_result)
+            .service(function_get_workers::get_workers)
+            .service(function_get_workers::get_workers_data)
+
+            .service(function_get_events::get_jobs_jobId_events)
+            .service(function_get_events::get_users_userId_events)
+            .service(function_get_events::get_contests_contestId_events)
+
+            .service(function_get_webhooks::get_webhooks_deliveries)
+
+            .service(function_post_runs::post_runs)
+
+            .service(function_get_problems::get_problems_problemId_samples)
+
+            .service(function_get_export::get_admin_export)
+            .service(function_post_import::post_admin_import)
+            .service(function_get_usage::get_admin_usage)
+            .service(function_delete_usage::delete_admin_usage_userId)
+
+            .service(function_get_audit::get_audit_replay)
+            .service(function_get_audit::get_audit)
+
+            .service(function_post_auth::post_register)
+            .service(function_post_auth::post_login)
+            .service(function_post_auth::post_logout)
+            .service(function_get_me::get_me)
+            .service(function_post_api_keys::post_api_keys)
+            .service(function_get_api_keys::get_api_keys)
+            .service(function_delete_api_keys::delete_api_keys_apiKeyId)
+
             .app_data(web::Data::new(config_arc.clone()))
+            .app_data(web::Data::from(storage.clone()))
         
     })
     .bind(("127.0.0.1", 12345))?
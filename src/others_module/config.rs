@@ -17,6 +17,8 @@ pub struct Case
     pub answer_file: String,
     pub time_limit: u64,
     pub memory_limit: u64,
+    #[serde(default)]
+    pub sample: bool, // samples are public: their data is shown with the results of a job
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
@@ -59,18 +61,204 @@ pub struct Language
 
 impl Language
 {
-    pub fn get_file_name(&mut self) -> String
+    pub fn get_file_name(&self) -> String
     {
         return self.file_name.clone();
     }
 }
 
+// settings for distributed judging, all times are in seconds
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Workers
+{
+    pub heartbeat_timeout: u64,
+    pub poll_timeout: u64,
+}
+
+impl Default for Workers
+{
+    fn default() -> Self
+    {
+        return Workers { heartbeat_timeout: 15, poll_timeout: 10 };
+    }
+}
+
+// limits used by custom runs when no problem is given
+// time_limit is in microseconds, memory_limit in bytes (0 means no limit), output_limit in bytes
+// rate_limits are the token buckets of POST /runs, which are separate from the ones of POST /jobs
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Runs
+{
+    pub time_limit: u64,
+    pub memory_limit: u64,
+    pub output_limit: usize,
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+}
+
+impl Default for Runs
+{
+    fn default() -> Self
+    {
+        return Runs { time_limit: 1000000, memory_limit: 0, output_limit: 65536, rate_limits: RateLimits::default() };
+    }
+}
+
+// what is done at startup with the jobs that were not finished when the server stopped
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Recovery
+{
+    requeue, // the jobs are judged again
+    system_error, // the jobs are finished with System Error
+}
+
+impl Default for Recovery
+{
+    fn default() -> Self
+    {
+        return Recovery::requeue;
+    }
+}
+
+// the SQL database used with --storage
+// readers is the number of connections that only read, writes go through a single other connection
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Database
+{
+    pub path: String,
+    pub readers: u32,
+    #[serde(default)]
+    pub recovery: Recovery,
+}
+
+impl Default for Database
+{
+    fn default() -> Self
+    {
+        return Database { path: "data.db".to_string(), readers: 4, recovery: Recovery::default() };
+    }
+}
+
+// what a user may do, see permission
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Role
+{
+    admin,
+    problem_setter,
+    contest_manager,
+    contestant,
+    observer,
+}
+
+// sessions given by POST /login, session_ttl is in seconds
+// with required, every guarded request needs a session, otherwise requests without one act with anonymous_role
+// without auth in the config, anonymous requests act as admin, so that servers whose clients do not log in keep working
+// a config giving auth opts in to roles, its anonymous_role is observer unless it says otherwise
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Auth
+{
+    #[serde(default = "default_session_ttl")]
+    pub session_ttl: u64,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default = "default_anonymous_role")]
+    pub anonymous_role: Role,
+}
+
+fn default_session_ttl() -> u64
+{
+    return 86400;
+}
+
+fn default_anonymous_role() -> Role
+{
+    return Role::observer;
+}
+
+impl Default for Auth
+{
+    fn default() -> Self
+    {
+        return Auth { session_ttl: default_session_ttl(), required: false, anonymous_role: Role::admin };
+    }
+}
+
+// an outgoing webhook, the body of each request is signed with the secret
+// events lists the events to send, all events are sent if it is empty
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Webhook
+{
+    pub url: String,
+    pub secret: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub max_attempts: Option<u32>,
+}
+
+// a token bucket of POST /jobs, holding count submissions that come back one by one over seconds
+// e.g. {"count": 1, "seconds": 10} allows one job every 10 seconds, {"count": 100, "seconds": 86400} 100 jobs a day
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RateLimit
+{
+    pub count: u32,
+    pub seconds: u64,
+}
+
+// each user and each IP address has a bucket for each of its limits, and a job needs a submission from all of them
+// jobs of every contest count, including contest 0, rejudged jobs do not, custom runs have their own limits in Runs
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct RateLimits
+{
+    #[serde(default)]
+    pub per_user: Vec<RateLimit>,
+    #[serde(default)]
+    pub per_ip: Vec<RateLimit>,
+}
+
+// the judging a user may use, limits that are not given are not enforced
+// cpu_seconds is the sum of the run times of the cases of the jobs, e.g. {"jobs": 200, "cpu_seconds": 3600}
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Quota
+{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_seconds: Option<f64>,
+}
+
+// usage is counted for each user in each UTC day, and in each contest, including contest 0
+// a job counts from its submission, its CPU seconds as its cases are judged, see quota
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Quotas
+{
+    #[serde(default)]
+    pub per_day: Quota,
+    #[serde(default)]
+    pub per_contest: Quota,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Config 
 {
     pub server: Server,
     pub problems: Vec<Problem>,
     pub languages: Vec<Language>,
+    #[serde(default)]
+    pub workers: Workers,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default)]
+    pub runs: Runs,
+    #[serde(default)]
+    pub database: Database,
+    #[serde(default)]
+    pub auth: Auth,
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+    #[serde(default)]
+    pub quotas: Quotas,
 }
 
 // function loads information into type Config
@@ -88,5 +276,14 @@ pub fn load(filename: &str) -> Result<Config, Box<dyn std::error::Error>>
     }
 
     let config: Config = serde_json::from_str(&contents)?;
+    let rate_limits = [&config.rate_limits, &config.runs.rate_limits];
+    if rate_limits.iter().flat_map(|limits| limits.per_user.iter().chain(limits.per_ip.iter())).any(|limit| limit.count == 0 || limit.seconds == 0)
+    {
+        return Err("Rate limits must have a count and seconds above 0".into());
+    }
+    if [&config.quotas.per_day, &config.quotas.per_contest].iter().any(|quota| quota.cpu_seconds.map_or(false, |seconds| seconds < 0.0))
+    {
+        return Err("Quotas must not have negative CPU seconds".into());
+    }
     Ok(config)
 }
\ No newline at end of file
@@ -0,0 +1,756 @@
+use rusqlite::{params, Connection, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::job::PossibleResult;
+
+// versioned schema of data.db
+// the version of a database is stored in schema_version, databases created before it existed are version 0
+// migrations are never edited once released, changes to the schema are new migrations at the end of the list
+
+pub struct Migration
+{
+    pub version: u32,
+    pub description: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "tables storing lists as JSON", up: up_1 },
+    Migration { version: 2, description: "normalized tables for jobs and contests", up: up_2 },
+    Migration { version: 3, description: "best case times of each contest", up: up_3 },
+    Migration { version: 4, description: "indexes for listing jobs", up: up_4 },
+    Migration { version: 5, description: "audit log", up: up_5 },
+    Migration { version: 6, description: "passwords and sessions", up: up_6 },
+    Migration { version: 7, description: "roles of users", up: up_7 },
+    Migration { version: 8, description: "API keys", up: up_8 },
+    Migration { version: 9, description: "profiles of users", up: up_9 },
+    Migration { version: 10, description: "teams", up: up_10 },
+    Migration { version: 11, description: "deactivated users", up: up_11 },
+    Migration { version: 12, description: "usage resets of quotas", up: up_12 },
+    Migration { version: 13, description: "time indexes on the stored text", up: up_13 },
+];
+
+// the version the database will have once every migration is applied
+pub fn latest_version() -> u32
+{
+    return MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0);
+}
+
+// reads the version of the database, 0 if it has never been migrated
+pub fn current_version(conn: &Connection) -> Result<u32>
+{
+    if !table_exists(conn, "schema_version")?
+    {
+        return Ok(0);
+    }
+    let version: Option<u32> = conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| row.get(0))?;
+    Ok(version.unwrap_or(0))
+}
+
+// returns the migrations that have not been applied to the database yet
+pub fn pending(conn: &Connection) -> Result<Vec<&'static Migration>>
+{
+    let version = current_version(conn)?;
+    // an older server must not write into a schema it does not know
+    if version > latest_version()
+    {
+        panic!("database schema version {} is newer than the latest known version {}", version, latest_version());
+    }
+    Ok(MIGRATIONS.iter().filter(|migration| migration.version > version).collect())
+}
+
+// applies every pending migration, each one in its own transaction
+// returns the migrations that were applied
+pub fn migrate(conn: &Connection) -> Result<Vec<&'static Migration>>
+{
+    let pending = pending(conn)?;
+    disable_foreign_keys(conn)?;
+    for migration in pending.iter()
+    {
+        let tx = conn.unchecked_transaction()?;
+        apply(&tx, migration)?;
+        tx.commit()?;
+        log::info!("Migrated database to version {}: {}", migration.version, migration.description);
+    }
+    Ok(pending)
+}
+
+// applies every pending migration and rolls all of them back
+// returns the migrations that would be applied, or the error that would stop them
+pub fn dry_run(conn: &Connection) -> Result<Vec<&'static Migration>>
+{
+    let pending = pending(conn)?;
+    disable_foreign_keys(conn)?;
+    let tx = conn.unchecked_transaction()?;
+    for migration in pending.iter()
+    {
+        apply(&tx, migration)?;
+    }
+    tx.rollback()?;
+    Ok(pending)
+}
+
+fn apply(conn: &Connection, migration: &Migration) -> Result<()>
+{
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+             version INTEGER PRIMARY KEY,
+             applied_time TEXT NOT NULL
+         )",
+        [],
+    )?;
+    (migration.up)(conn)?;
+    conn.execute(
+        "INSERT INTO schema_version (version, applied_time) VALUES (?1, ?2)",
+        params![migration.version, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+// data written by older versions is copied as it is, even if it does not satisfy the new foreign keys
+// foreign keys cannot be switched inside a transaction, sql::initialize switches them on again
+fn disable_foreign_keys(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, name: &str) -> Result<bool>
+{
+    let count: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        params![name],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+// VERSION 1
+
+// the original tables, lists were stored as JSON text
+// tables are only created if missing, since databases of version 0 may already have them
+fn up_1(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS user_id_count (
+             key TEXT PRIMARY KEY,
+             value TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS user_list (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS job_id_count (
+             key TEXT PRIMARY KEY,
+             value TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS response_content (
+             id INTEGER PRIMARY KEY,
+             created_time TEXT NOT NULL,
+             updated_time TEXT NOT NULL,
+             submission TEXT NOT NULL,
+             state TEXT NOT NULL,
+             result TEXT NOT NULL,
+             score REAL NOT NULL,
+             cases TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS contest_id_count (
+             key TEXT PRIMARY KEY,
+             value TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS contest (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL,
+             from_time TEXT NOT NULL,
+             to_time TEXT NOT NULL,
+             problem_ids TEXT NOT NULL,
+             user_ids TEXT NOT NULL,
+             submission_limit INTEGER NOT NULL,
+             users TEXT NOT NULL
+         );",
+    )?;
+    Ok(())
+}
+
+// VERSION 2
+
+// jobs, cases, contests and participants get their own rows
+// the JSON of version 1 is copied into the new tables and its tables are dropped
+fn up_2(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS contests (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL,
+             from_time TEXT NOT NULL,
+             to_time TEXT NOT NULL,
+             submission_limit INTEGER NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS contest_problems (
+             contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+             position INTEGER NOT NULL,
+             problem_id INTEGER NOT NULL,
+             PRIMARY KEY (contest_id, position)
+         );
+         CREATE TABLE IF NOT EXISTS contest_participants (
+             contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+             user_id INTEGER NOT NULL REFERENCES user_list(id),
+             position INTEGER NOT NULL,
+             rank INTEGER NOT NULL,
+             score INTEGER NOT NULL,
+             submission_count INTEGER NOT NULL,
+             competitive_score_sum REAL NOT NULL,
+             latest_submission TEXT NOT NULL,
+             PRIMARY KEY (contest_id, user_id)
+         );
+         CREATE TABLE IF NOT EXISTS participant_results (
+             contest_id INTEGER NOT NULL,
+             user_id INTEGER NOT NULL,
+             problem_index INTEGER NOT NULL,
+             score REAL NOT NULL,
+             highest_score REAL NOT NULL,
+             latest_score REAL NOT NULL,
+             PRIMARY KEY (contest_id, user_id, problem_index),
+             FOREIGN KEY (contest_id, user_id) REFERENCES contest_participants(contest_id, user_id) ON DELETE CASCADE
+         );
+         CREATE TABLE IF NOT EXISTS participant_case_times (
+             contest_id INTEGER NOT NULL,
+             user_id INTEGER NOT NULL,
+             problem_index INTEGER NOT NULL,
+             case_index INTEGER NOT NULL,
+             time INTEGER NOT NULL,
+             PRIMARY KEY (contest_id, user_id, problem_index, case_index),
+             FOREIGN KEY (contest_id, user_id) REFERENCES contest_participants(contest_id, user_id) ON DELETE CASCADE
+         );
+         CREATE TABLE IF NOT EXISTS jobs (
+             id INTEGER PRIMARY KEY,
+             created_time TEXT NOT NULL,
+             updated_time TEXT NOT NULL,
+             source_code TEXT NOT NULL,
+             language TEXT NOT NULL,
+             user_id INTEGER NOT NULL REFERENCES user_list(id),
+             contest_id INTEGER NOT NULL REFERENCES contests(id),
+             problem_id INTEGER NOT NULL,
+             state TEXT NOT NULL,
+             result TEXT NOT NULL,
+             score REAL NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS jobs_user_id ON jobs (user_id);
+         CREATE INDEX IF NOT EXISTS jobs_contest_id ON jobs (contest_id, problem_id);
+         CREATE INDEX IF NOT EXISTS jobs_problem_id ON jobs (problem_id);
+         CREATE TABLE IF NOT EXISTS job_cases (
+             job_id INTEGER NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+             id INTEGER NOT NULL,
+             result TEXT NOT NULL,
+             info TEXT NOT NULL,
+             time INTEGER NOT NULL,
+             memory INTEGER NOT NULL,
+             input TEXT,
+             answer TEXT,
+             output TEXT,
+             stderr TEXT,
+             PRIMARY KEY (job_id, id)
+         );",
+    )?;
+
+    copy_json_contests(conn)?;
+    copy_json_jobs(conn)?;
+
+    conn.execute_batch(
+        "DROP TABLE response_content;
+         DROP TABLE contest;",
+    )?;
+    Ok(())
+}
+
+// version 1 stored results with their debug names, e.g. "WrongAnswer"
+// they are stored the way they are shown in responses from version 2 on, e.g. "Wrong Answer"
+fn result_name(name: &str) -> String
+{
+    match serde_json::from_value::<PossibleResult>(Value::String(name.to_string()))
+    {
+        Ok(result) => serde_json::to_value(result).ok()
+            .and_then(|value| value.as_str().map(|name| name.to_string()))
+            .unwrap_or(name.to_string()),
+        Err(_) => name.to_string(),
+    }
+}
+
+// times are stored in the format written by to_rfc3339 from version 2 on
+fn time_text(time: &str) -> String
+{
+    match time.parse::<DateTime<Utc>>()
+    {
+        Ok(time) => time.to_rfc3339(),
+        Err(_) => time.to_string(),
+    }
+}
+
+fn invalid_json(err: serde_json::Error) -> rusqlite::Error
+{
+    return rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(err));
+}
+
+fn copy_json_jobs(conn: &Connection) -> Result<()>
+{
+    let mut stmt = conn.prepare("SELECT id, created_time, updated_time, submission, state, result, score, cases FROM response_content")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()?
+    {
+        let id: u32 = row.get(0)?;
+        let submission: Value = serde_json::from_str(&row.get::<_, String>(3)?).map_err(invalid_json)?;
+        let cases: Value = serde_json::from_str(&row.get::<_, String>(7)?).map_err(invalid_json)?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO jobs (id, created_time, updated_time, source_code, language, user_id, contest_id, problem_id, state, result, score)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                id,
+                time_text(&row.get::<_, String>(1)?),
+                time_text(&row.get::<_, String>(2)?),
+                submission["source_code"].as_str().unwrap_or_default(),
+                submission["language"].as_str().unwrap_or_default(),
+                submission["user_id"].as_u64().unwrap_or(0),
+                submission["contest_id"].as_u64().unwrap_or(0),
+                submission["problem_id"].as_u64().unwrap_or(0),
+                row.get::<_, String>(4)?,
+                result_name(&row.get::<_, String>(5)?),
+                row.get::<_, f64>(6)?,
+            ],
+        )?;
+
+        for case in cases.as_array().unwrap_or(&vec![])
+        {
+            conn.execute(
+                "INSERT OR REPLACE INTO job_cases (job_id, id, result, info, time, memory) VALUES (?1, ?2, ?3, ?4, ?5, 0)",
+                params![
+                    id,
+                    case["id"].as_u64().unwrap_or(0),
+                    result_name(case["result"].as_str().unwrap_or_default()),
+                    case["info"].as_str().unwrap_or_default(),
+                    case["time"].as_i64().unwrap_or(0),
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_json_contests(conn: &Connection) -> Result<()>
+{
+    let mut stmt = conn.prepare("SELECT id, name, from_time, to_time, problem_ids, submission_limit, users FROM contest")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()?
+    {
+        let id: u32 = row.get(0)?;
+        let problem_ids: Value = serde_json::from_str(&row.get::<_, String>(4)?).map_err(invalid_json)?;
+        let users: Value = serde_json::from_str(&row.get::<_, String>(6)?).map_err(invalid_json)?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO contests (id, name, from_time, to_time, submission_limit) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                id,
+                row.get::<_, String>(1)?,
+                time_text(&row.get::<_, String>(2)?),
+                time_text(&row.get::<_, String>(3)?),
+                row.get::<_, u32>(5)?,
+            ],
+        )?;
+
+        for (position, problem_id) in problem_ids.as_array().unwrap_or(&vec![]).iter().enumerate()
+        {
+            conn.execute(
+                "INSERT OR REPLACE INTO contest_problems (contest_id, position, problem_id) VALUES (?1, ?2, ?3)",
+                params![id, position, problem_id.as_u64().unwrap_or(0)],
+            )?;
+        }
+
+        for (position, rank_info) in users.as_array().unwrap_or(&vec![]).iter().enumerate()
+        {
+            let user_id = rank_info["user"]["id"].as_u64().unwrap_or(0);
+            conn.execute(
+                "INSERT OR REPLACE INTO contest_participants (contest_id, user_id, position, rank, score, submission_count, competitive_score_sum, latest_submission)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    id,
+                    user_id,
+                    position,
+                    rank_info["rank"].as_u64().unwrap_or(0),
+                    rank_info["score"].as_u64().unwrap_or(0),
+                    rank_info["submission_count"].as_u64().unwrap_or(0),
+                    rank_info["competitive_score_sum"].as_f64().unwrap_or(0.0),
+                    time_text(rank_info["latest_submission"].as_str().unwrap_or_default()),
+                ],
+            )?;
+
+            let highest_scores = rank_info["highest_scores"].as_array().cloned().unwrap_or_default();
+            for problem_index in 0..highest_scores.len()
+            {
+                conn.execute(
+                    "INSERT OR REPLACE INTO participant_results (contest_id, user_id, problem_index, score, highest_score, latest_score)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        id,
+                        user_id,
+                        problem_index,
+                        rank_info["scores"][problem_index].as_f64().unwrap_or(0.0),
+                        highest_scores[problem_index].as_f64().unwrap_or(0.0),
+                        rank_info["latest_scores"][problem_index].as_f64().unwrap_or(0.0),
+                    ],
+                )?;
+            }
+
+            // cases that were never run have the time i64::MAX and are not copied
+            for (problem_index, times) in rank_info["shortest_times"].as_array().unwrap_or(&vec![]).iter().enumerate()
+            {
+                for (case_index, time) in times.as_array().unwrap_or(&vec![]).iter().enumerate()
+                {
+                    let time = time.as_i64().unwrap_or(i64::MAX);
+                    if time == i64::MAX {continue;}
+                    conn.execute(
+                        "INSERT OR REPLACE INTO participant_case_times (contest_id, user_id, problem_index, case_index, time)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![id, user_id, problem_index, case_index, time],
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// VERSION 3
+
+// the best time of each case among the submissions of a contest, used by dynamic ranking
+// the table starts empty, the server rebuilds it from the jobs since problems are only known from the config
+fn up_3(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS contest_case_times (
+             contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+             problem_index INTEGER NOT NULL,
+             case_index INTEGER NOT NULL,
+             time INTEGER NOT NULL,
+             PRIMARY KEY (contest_id, problem_index, case_index)
+         );",
+    )?;
+    Ok(())
+}
+
+// VERSION 4
+
+// every filter of GET /jobs gets an index
+// times are stored as RFC 3339 text with varying precision, so they are compared through julianday
+fn up_4(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS jobs_created_time ON jobs (julianday(created_time));
+         CREATE INDEX IF NOT EXISTS jobs_state ON jobs (state, result);
+         CREATE INDEX IF NOT EXISTS jobs_language ON jobs (language);",
+    )?;
+    Ok(())
+}
+
+// VERSION 5
+
+// every change to users, contests and jobs, see audit
+// entries cannot be changed or removed, not even when the other tables are cleared
+fn up_5(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+             id INTEGER PRIMARY KEY AUTOINCREMENT,
+             time TEXT NOT NULL,
+             actor TEXT NOT NULL,
+             kind TEXT NOT NULL,
+             change TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS audit_log_kind ON audit_log (kind);
+         CREATE INDEX IF NOT EXISTS audit_log_actor ON audit_log (actor);
+         CREATE INDEX IF NOT EXISTS audit_log_time ON audit_log (julianday(time));
+         CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+         BEGIN
+             SELECT RAISE(ABORT, 'audit log entries cannot be changed');
+         END;
+         CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+         BEGIN
+             SELECT RAISE(ABORT, 'audit log entries cannot be removed');
+         END;",
+    )?;
+    Ok(())
+}
+
+// VERSION 6
+
+// passwords are argon2 hashes, sessions are found by the sha256 hash of their token, see auth
+fn up_6(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS credentials (
+             user_id INTEGER PRIMARY KEY REFERENCES user_list(id),
+             password_hash TEXT NOT NULL
+         );
+         CREATE TABLE IF NOT EXISTS sessions (
+             token_hash TEXT PRIMARY KEY,
+             user_id INTEGER NOT NULL REFERENCES user_list(id),
+             created_time TEXT NOT NULL,
+             expires_time TEXT NOT NULL,
+             revoked INTEGER NOT NULL
+         );",
+    )?;
+    Ok(())
+}
+
+// VERSION 7
+
+// users without a row have the default role, see permission::role_of
+fn up_7(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS user_roles (
+             user_id INTEGER PRIMARY KEY REFERENCES user_list(id),
+             role TEXT NOT NULL
+         );",
+    )?;
+    Ok(())
+}
+
+// VERSION 8
+
+// keys are found by the sha256 hash of the key, like sessions, scopes are a JSON array
+fn up_8(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS api_keys (
+             id INTEGER PRIMARY KEY,
+             key_hash TEXT NOT NULL UNIQUE,
+             user_id INTEGER NOT NULL REFERENCES user_list(id),
+             name TEXT NOT NULL,
+             scopes TEXT NOT NULL,
+             created_time TEXT NOT NULL,
+             expires_time TEXT,
+             last_used_time TEXT,
+             revoked INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS api_keys_user_id ON api_keys (user_id);",
+    )?;
+    Ok(())
+}
+
+// VERSION 9
+
+// profile fields are NULL when they are not set, see user::Profile
+fn up_9(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "ALTER TABLE user_list ADD COLUMN display_name TEXT;
+         ALTER TABLE user_list ADD COLUMN email TEXT;
+         ALTER TABLE user_list ADD COLUMN organization TEXT;
+         ALTER TABLE user_list ADD COLUMN country TEXT;
+         ALTER TABLE user_list ADD COLUMN avatar_url TEXT;
+         ALTER TABLE user_list ADD COLUMN bio TEXT;",
+    )?;
+    Ok(())
+}
+
+// VERSION 10
+
+// members and the teams of a contest keep their order with their position, like contest_problems
+fn up_10(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS teams (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL,
+             coach_id INTEGER REFERENCES user_list(id)
+         );
+         CREATE TABLE IF NOT EXISTS team_members (
+             team_id INTEGER NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+             position INTEGER NOT NULL,
+             user_id INTEGER NOT NULL REFERENCES user_list(id),
+             PRIMARY KEY (team_id, position)
+         );
+         CREATE TABLE IF NOT EXISTS contest_teams (
+             contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+             position INTEGER NOT NULL,
+             team_id INTEGER NOT NULL REFERENCES teams(id),
+             PRIMARY KEY (contest_id, position)
+         );",
+    )?;
+    Ok(())
+}
+
+// VERSION 11
+
+// users that existed before are active
+fn up_11(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch("ALTER TABLE user_list ADD COLUMN deactivated INTEGER NOT NULL DEFAULT 0;")?;
+    Ok(())
+}
+
+// VERSION 12
+
+// users without a row never had their usage reset, see quota
+fn up_12(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS usage_resets (
+             user_id INTEGER PRIMARY KEY REFERENCES user_list(id),
+             reset_time TEXT NOT NULL
+         );",
+    )?;
+    Ok(())
+}
+
+// VERSION 13
+
+// times are compared as the text written by to_rfc3339, which is always in UTC and sorts like the times,
+// so the indexes are on the columns themselves, which range filters can use
+fn up_13(conn: &Connection) -> Result<()>
+{
+    conn.execute_batch(
+        "DROP INDEX IF EXISTS jobs_created_time;
+         CREATE INDEX IF NOT EXISTS jobs_created_time ON jobs (created_time);
+         DROP INDEX IF EXISTS audit_log_time;
+         CREATE INDEX IF NOT EXISTS audit_log_time ON audit_log (time);",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // databases as they were left by each past version of the server
+    const FIXTURE_0_EMPTY: &str = "";
+    const FIXTURE_0_JSON: &str = include_str!("../../tests/fixtures/data_v0_json.sql");
+    const FIXTURE_0_NORMALIZED: &str = include_str!("../../tests/fixtures/data_v0_normalized.sql");
+    const FIXTURE_1: &str = include_str!("../../tests/fixtures/data_v1.sql");
+
+    fn open(fixture: &str) -> Connection
+    {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(fixture).unwrap();
+        return conn;
+    }
+
+    fn count(conn: &Connection, sql: &str) -> u32
+    {
+        return conn.query_row(sql, [], |row| row.get(0)).unwrap();
+    }
+
+    // checks that the data of the JSON fixtures ended up in the normalized tables
+    fn check_json_data(conn: &Connection)
+    {
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM jobs"), 2);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM job_cases"), 4);
+        let result: String = conn.query_row("SELECT result FROM jobs WHERE id = 1", [], |row| row.get(0)).unwrap();
+        assert_eq!(result, "Wrong Answer");
+        let source_code: String = conn.query_row("SELECT source_code FROM jobs WHERE id = 0", [], |row| row.get(0)).unwrap();
+        assert_eq!(source_code, "fn main() {}");
+
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM contests"), 2);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM contest_problems WHERE contest_id = 1"), 2);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM contest_participants WHERE contest_id = 1"), 2);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM participant_results WHERE contest_id = 1"), 4);
+        let highest_score: f64 = conn.query_row(
+            "SELECT highest_score FROM participant_results WHERE contest_id = 1 AND user_id = 1 AND problem_index = 0",
+            [], |row| row.get(0)).unwrap();
+        assert_eq!(highest_score, 100.0);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM participant_case_times"), 1);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM contest_case_times"), 0);
+        let latest_submission: String = conn.query_row(
+            "SELECT latest_submission FROM contest_participants WHERE contest_id = 0 AND user_id = 0",
+            [], |row| row.get(0)).unwrap();
+        assert!(latest_submission.parse::<DateTime<Utc>>().is_ok());
+
+        assert!(!table_exists(conn, "response_content").unwrap());
+        assert!(!table_exists(conn, "contest").unwrap());
+    }
+
+    #[test]
+    fn migrates_empty_database()
+    {
+        let conn = open(FIXTURE_0_EMPTY);
+        assert_eq!(migrate(&conn).unwrap().len(), MIGRATIONS.len());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM jobs"), 0);
+    }
+
+    #[test]
+    fn migrates_unversioned_json_database()
+    {
+        let conn = open(FIXTURE_0_JSON);
+        migrate(&conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        check_json_data(&conn);
+    }
+
+    #[test]
+    fn migrates_unversioned_normalized_database()
+    {
+        let conn = open(FIXTURE_0_NORMALIZED);
+        migrate(&conn).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM jobs"), 1);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM contest_participants"), 1);
+        assert!(!table_exists(&conn, "response_content").unwrap());
+    }
+
+    #[test]
+    fn migrates_version_1()
+    {
+        let conn = open(FIXTURE_1);
+        assert_eq!(current_version(&conn).unwrap(), 1);
+        assert_eq!(migrate(&conn).unwrap().len(), MIGRATIONS.len() - 1);
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        check_json_data(&conn);
+    }
+
+    #[test]
+    fn migrating_twice_does_nothing()
+    {
+        let conn = open(FIXTURE_1);
+        migrate(&conn).unwrap();
+        assert!(migrate(&conn).unwrap().is_empty());
+        check_json_data(&conn);
+    }
+
+    #[test]
+    fn audit_log_cannot_be_changed()
+    {
+        let conn = open(FIXTURE_0_EMPTY);
+        migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO audit_log (time, actor, kind, change) VALUES ('2022-08-27T02:05:29+00:00', 'admin', 'state_cleared', '{}')",
+            [],
+        ).unwrap();
+        assert!(conn.execute("UPDATE audit_log SET actor = 'system'", []).is_err());
+        assert!(conn.execute("DELETE FROM audit_log", []).is_err());
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM audit_log"), 1);
+    }
+
+    #[test]
+    fn time_filters_use_indexes()
+    {
+        let conn = open(FIXTURE_0_EMPTY);
+        migrate(&conn).unwrap();
+        let plan: String = conn.query_row(
+            "EXPLAIN QUERY PLAN SELECT id FROM jobs WHERE created_time >= ?1 AND created_time <= ?2",
+            params!["2022-08-27T02:05:29+00:00", "2022-08-28T02:05:29+00:00"], |row| row.get(3)).unwrap();
+        assert!(plan.contains("jobs_created_time"), "{}", plan);
+    }
+
+    #[test]
+    fn dry_run_changes_nothing()
+    {
+        let conn = open(FIXTURE_1);
+        assert_eq!(dry_run(&conn).unwrap().len(), MIGRATIONS.len() - 1);
+        assert_eq!(current_version(&conn).unwrap(), 1);
+        assert!(table_exists(&conn, "response_content").unwrap());
+        assert!(!table_exists(&conn, "jobs").unwrap());
+    }
+}
\ No newline at end of file
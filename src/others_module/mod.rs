@@ -1,3 +1,4 @@
 pub mod config;
 pub mod parameters;
-pub mod sql;
\ No newline at end of file
+pub mod sql;
+pub mod migrations;
\ No newline at end of file
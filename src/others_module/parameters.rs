@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 pub struct Cli 
@@ -11,4 +11,38 @@ pub struct Cli
     pub storage: bool, // activates sql storage mode
     #[clap(short, long)]
     pub reset_storage: bool, // resets sql database
+    #[clap(long)]
+    pub database: Option<String>, // path of the sql database, overrides the config file
+    #[clap(long)]
+    pub distributed: bool, // jobs are judged by oj-judge workers
+    #[clap(long)]
+    pub migrate_only: bool, // migrates the sql database to the latest schema and exits
+    #[clap(long, requires = "migrate_only")]
+    pub dry_run: bool, // with --migrate-only, checks the pending migrations without applying them
+    #[clap(subcommand)]
+    pub command: Option<Command>, // works on the sql database instead of starting the server
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command
+{
+    // writes users, contests, jobs and id counters to an archive
+    Export
+    {
+        path: String,
+    },
+    // loads an archive, replacing the database unless --merge
+    Import
+    {
+        path: String,
+        #[clap(long)]
+        merge: bool, // users are matched by name, contests and jobs get new ids
+    },
+    // creates the users of a CSV or JSON file and prints their generated passwords, see provision
+    ImportUsers
+    {
+        path: String,
+        #[clap(long)]
+        contest: Option<u32>, // the users are enrolled into this contest
+    },
 }
\ No newline at end of file
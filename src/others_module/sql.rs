@@ -1,85 +1,40 @@
 use std::i64::MAX;
 
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, params_from_iter, Connection, Result, Row};
+use rusqlite::types::ToSql;
 use chrono::Utc;
 use chrono::DateTime;
 
-use crate::user::User;
+use crate::user::{User, Profile};
 use crate::contest::Contest;
+use crate::team::Team;
 use crate::contest;
-use crate::config;
 
 use crate::job::ResponseContent;
 use crate::job::PossibleResult;
+use crate::job::Case;
+use crate::job::CaseFeedback;
+use crate::post_job::PostJob;
 
-use crate::sql;
-use crate::IS_SQL;
+use crate::migrations;
+use crate::audit;
+use crate::auth;
+use crate::api_key::ApiKey;
+use crate::config::Role;
+use crate::storage::JobQuery;
+
+// number of cases per problem in the shortest_times of a participant
+const CASE_TIMES_WIDTH: usize = 20;
 
 // initializes database
-// creates tables to store each variable/list in its required format
+// brings the tables to the latest schema, see migrations
 
 pub fn initialize(conn: &Connection) -> Result<()> 
 {
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS user_id_count (
-             key TEXT PRIMARY KEY,
-             value TEXT NOT NULL
-         )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS user_list (
-             id INTEGER PRIMARY KEY,
-             name TEXT NOT NULL
-         )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS job_id_count (
-             key TEXT PRIMARY KEY,
-             value TEXT NOT NULL
-         )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS response_content (
-             id INTEGER PRIMARY KEY,
-             created_time TEXT NOT NULL,
-             updated_time TEXT NOT NULL,
-             submission TEXT NOT NULL,
-             state TEXT NOT NULL,
-             result TEXT NOT NULL,
-             score REAL NOT NULL,
-             cases TEXT NOT NULL
-         )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS contest_id_count (
-             key TEXT PRIMARY KEY,
-             value TEXT NOT NULL
-         )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS contest (
-             id INTEGER PRIMARY KEY,
-             name TEXT NOT NULL,
-             from_time TEXT NOT NULL,
-             to_time TEXT NOT NULL,
-             problem_ids TEXT NOT NULL,
-             user_ids TEXT NOT NULL,
-             submission_limit INTEGER NOT NULL,
-             users TEXT NOT NULL
-         )",
-        [],
-    )?;
+    migrations::migrate(conn)?;
 
+    // SQLite only checks foreign keys when asked to, once per connection
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
     Ok(())
 }
 
@@ -89,29 +44,37 @@ pub fn initialize(conn: &Connection) -> Result<()>
 pub fn clear(conn: &Connection) -> Result<()> 
 {
     conn.execute("DELETE FROM user_id_count", [])?;
-    conn.execute("DELETE FROM user_list", [])?;
 
     conn.execute("DELETE FROM job_id_count", [])?;
-    conn.execute("DELETE FROM response_content", [])?;
+    conn.execute("DELETE FROM job_cases", [])?;
+    conn.execute("DELETE FROM jobs", [])?;
 
     conn.execute("DELETE FROM contest_id_count", [])?;
-    conn.execute("DELETE FROM contest", [])?;
+    conn.execute("DELETE FROM contest_case_times", [])?;
+    conn.execute("DELETE FROM participant_case_times", [])?;
+    conn.execute("DELETE FROM participant_results", [])?;
+    conn.execute("DELETE FROM contest_participants", [])?;
+    conn.execute("DELETE FROM contest_teams", [])?;
+    conn.execute("DELETE FROM contest_problems", [])?;
+    conn.execute("DELETE FROM contests", [])?;
+
+    conn.execute("DELETE FROM team_members", [])?;
+    conn.execute("DELETE FROM teams", [])?;
+
+    conn.execute("DELETE FROM usage_resets", [])?;
+    conn.execute("DELETE FROM api_keys", [])?;
+    conn.execute("DELETE FROM user_roles", [])?;
+    conn.execute("DELETE FROM sessions", [])?;
+    conn.execute("DELETE FROM credentials", [])?;
+
+    // users are referenced by jobs, participants, teams and credentials, so they go last
+    conn.execute("DELETE FROM user_list", [])?;
     Ok(())
 }
 
 // USER ID COUNT
 
-// function first checks if SQL storage is activated, then stores the count
-pub fn push_user_count(connection: &Connection, count: u32)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        store_user_id_count(&connection, count).expect("failed to store user id count");
-    }
-}
-
-// actually performing the action of storing into the database
+// stores the user id count into the database
 pub fn store_user_id_count(conn: &Connection, count: u32) -> Result<()> 
 {
     let count_str = count.to_string();
@@ -122,21 +85,6 @@ pub fn store_user_id_count(conn: &Connection, count: u32) -> Result<()>
     Ok(())
 }
 
-// checks if SQL storage is activated
-// if yes, then it retrieves the count
-pub fn get_user_count(connection: &Connection, count: &mut u32)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        *count = match sql::retrieve_user_id_count(&connection)
-        {
-            Ok(x) => x,
-            Err(_) => {panic!("unsuccessful retrieve");}
-        };
-    }
-}
-
 // retrieves the user id count from the database
 pub fn retrieve_user_id_count(conn: &Connection) -> Result<u32> 
 {
@@ -146,67 +94,38 @@ pub fn retrieve_user_id_count(conn: &Connection) -> Result<u32>
     Ok(count)
 }
 
-// inserts the default value into the database
-// only called when reset mode is on
-pub fn insert_default_user_id_count(conn: &Connection) -> Result<()> 
-{
-    conn.execute(
-        "INSERT OR REPLACE INTO user_id_count (key, value) VALUES (?1, ?2)",
-        params!["USER_ID_COUNT", 1],
-    )?;
-    Ok(())
-}
-
 // USER LIST
 
-// checks if SQL storage mode is activated
-// is yes, then stores the user list
-pub fn push_user(connection: &Connection, list: &Vec<User>)
+// inserts the user into the database, or replaces the row with the same id
+pub fn store_user(conn: &Connection, user: &User) -> Result<()> 
 {
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        store_user_list(&connection, &list).expect("failed to store user list");
-    }
-}
-
-// first clears the original list from the database
-// then store the new list into the database
-pub fn store_user_list(conn: &Connection, user_list: &[User]) -> Result<()> 
-{
-    conn.execute("DELETE FROM user_list", [])?;
-    for user in user_list {
-        conn.execute(
-            "INSERT INTO user_list (id, name) VALUES (?1, ?2)",
-            params![user.id, user.name],
-        )?;
-    }
+    conn.execute(
+        "INSERT OR REPLACE INTO user_list (id, name, display_name, email, organization, country, avatar_url, bio, deactivated)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![user.id, user.name, user.profile.display_name, user.profile.email, user.profile.organization,
+            user.profile.country, user.profile.avatar_url, user.profile.bio, user.deactivated],
+    )?;
     Ok(())
 }
 
-// checks is SQL storage is on
-// if yes, then it retrieves the user list
-pub fn get_user(connection: &Connection, lock_user_list: &mut Vec<User>)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        *lock_user_list = match sql::retrieve_user_list(&connection)
-        {
-            Ok(x) => x,
-            Err(_) => {panic!("unsuccessful retrieve");}
-        };
-    }
-}
-
-// retrieves the user list from the database
+// retrieves the user list from the database, ordered by id
 pub fn retrieve_user_list(conn: &Connection) -> Result<Vec<User>> 
 {
-    let mut stmt = conn.prepare("SELECT id, name FROM user_list")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, name, display_name, email, organization, country, avatar_url, bio, deactivated FROM user_list ORDER BY id")?;
     let user_iter = stmt.query_map([], |row| {
         Ok(User {
             id: row.get(0)?,
             name: row.get(1)?,
+            profile: Profile {
+                display_name: row.get(2)?,
+                email: row.get(3)?,
+                organization: row.get(4)?,
+                country: row.get(5)?,
+                avatar_url: row.get(6)?,
+                bio: row.get(7)?,
+            },
+            deactivated: row.get(8)?,
         })
     })?;
 
@@ -217,31 +136,8 @@ pub fn retrieve_user_list(conn: &Connection) -> Result<Vec<User>>
     Ok(users)
 }
 
-// inserts the default user list
-// only constist of the root use
-// called when reset mode is on
-pub fn insert_default_user_list(conn: &Connection) -> Result<()> 
-{
-    conn.execute(
-        "INSERT INTO user_list (id, name) VALUES (?1, ?2)",
-        params![0, "root".to_string()],
-    )?;
-    Ok(())
-}
-
 // JOB COUNT
 
-// checks if SQL storage is on
-// if yes, then stores the job id count
-pub fn push_job_count(connection: &Connection, count: u32)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        store_job_id_count(&connection, count).expect("failed to store job id count");
-    }
-}
-
 // stores the job id count into the database
 pub fn store_job_id_count(conn: &Connection, count: u32) -> Result<()> 
 {
@@ -253,21 +149,6 @@ pub fn store_job_id_count(conn: &Connection, count: u32) -> Result<()>
     Ok(())
 }
 
-// checks if SQL storage is on
-// if yes, then retrieves the job id count
-pub fn get_job_count(connection: &Connection, count: &mut u32)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        *count = match sql::retrieve_job_id_count(&connection)
-        {
-            Ok(x) => x,
-            Err(_) => {panic!("unsuccessful retrieve");}
-        };
-    }
-}
-
 // retrieves the job id count from the database 
 pub fn retrieve_job_id_count(conn: &Connection) -> Result<u32> 
 {
@@ -277,137 +158,252 @@ pub fn retrieve_job_id_count(conn: &Connection) -> Result<u32>
     Ok(count)
 }
 
-// inserts default job id count, which is 0
-// only called if reset mode is on
-pub fn insert_default_job_id_count(conn: &Connection) -> Result<()> 
+// JOB LIST
+
+// inserts the job and its cases into the database, or updates the rows with the same ids
+pub fn store_job(conn: &Connection, response_content: &ResponseContent) -> Result<()> 
 {
     conn.execute(
-        "INSERT OR REPLACE INTO job_id_count (key, value) VALUES (?1, ?2)",
-        params!["JOB_ID_COUNT", 0],
+        "INSERT INTO jobs (id, created_time, updated_time, source_code, language, user_id, contest_id, problem_id, state, result, score)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+         ON CONFLICT (id) DO UPDATE SET created_time = ?2, updated_time = ?3, source_code = ?4, language = ?5,
+             user_id = ?6, contest_id = ?7, problem_id = ?8, state = ?9, result = ?10, score = ?11",
+        params![
+            response_content.id,
+            response_content.created_time.to_rfc3339(),
+            response_content.updated_time.to_rfc3339(),
+            response_content.submission.source_code,
+            response_content.submission.language,
+            response_content.submission.user_id,
+            response_content.submission.contest_id,
+            response_content.submission.problem_id,
+            response_content.state,
+            result_name(&response_content.result),
+            response_content.score,
+        ],
     )?;
-    Ok(())
-}
 
-// JOB LIST
-
-// checks if SQL storage is on
-// if yes, then stores the job list
-pub fn push_job(connection: &Connection, list: &Vec<ResponseContent>)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
+    for case in response_content.cases.iter()
     {
-        store_job(&connection, &list).expect("failed to store job list");
-    }
-}
-
-// stores the job list from the database
-pub fn store_job(conn: &Connection, response_contents: &Vec<ResponseContent>) -> Result<()> 
-{
-    // clears original list
-    conn.execute("DELETE FROM response_content", [])?;
-    // inserts each element of the new list
-    for response_content in response_contents {
-        let submission_json = serde_json::to_string(&response_content.submission).expect("error storing jobs");
-        let cases_json = serde_json::to_string(&response_content.cases).expect("error storing jobs");
+        let feedback = case.feedback.as_ref();
         conn.execute(
-            "INSERT INTO response_content (id, created_time, updated_time, submission, state, result, score, cases) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            "INSERT OR REPLACE INTO job_cases (job_id, id, result, info, time, memory, input, answer, output, stderr)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
             params![
                 response_content.id,
-                response_content.created_time.to_rfc3339(),
-                response_content.updated_time.to_rfc3339(),
-                submission_json,
-                response_content.state,
-                format!("{:?}", response_content.result),
-                response_content.score,
-                cases_json
+                case.id,
+                result_name(&case.result),
+                case.info,
+                case.time,
+                case.memory,
+                feedback.map(|feedback| feedback.input.clone()),
+                feedback.map(|feedback| feedback.answer.clone()),
+                feedback.map(|feedback| feedback.output.clone()),
+                feedback.map(|feedback| feedback.stderr.clone()),
             ],
         )?;
     }
     Ok(())
 }
 
-// checks if SQL storage is on
-// if yes, then retrieves the job list
-pub fn get_job(connection: &Connection, lock_job_list: &mut Vec<ResponseContent>)
+// results are stored the way they are shown in responses, e.g. "Wrong Answer"
+fn result_name(result: &PossibleResult) -> String
 {
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        *lock_job_list = match sql::retrieve_job(&connection)
-        {
-            Ok(x) => 
-            {
-                x
-            }
-            Err(_) => {panic!("unsuccessful retrieve");}
-        };
-        log::info!("{:?}", lock_job_list);
-    }
+    return serde_json::to_value(result).expect("error storing result").as_str().unwrap_or_default().to_string();
 }
 
-// retrieves the job list from the database
-pub fn retrieve_job(conn: &Connection) -> Result<Vec<ResponseContent>> 
+// reads back a result stored by result_name
+fn parse_result(name: &str) -> Result<PossibleResult>
 {
-    let mut stmt = conn.prepare("SELECT id, created_time, updated_time, submission, state, result, score, cases FROM response_content")?;
-    let mut response_contents = Vec::new();
+    let result = match name {
+        "Waiting" => PossibleResult::Waiting,
+        "Running" => PossibleResult::Running,
+        "Accepted" => PossibleResult::Accepted,
+        "Compilation Error" => PossibleResult::CompilationError,
+        "Compilation Success" => PossibleResult::CompilationSuccess,
+        "Wrong Answer" => PossibleResult::WrongAnswer,
+        "Runtime Error" => PossibleResult::RuntimeError,
+        "Time Limit Exceeded" => PossibleResult::TimeLimitExceeded,
+        "Memory Limit Exceeded" => PossibleResult::MemoryLimitExceeded,
+        "System Error" => PossibleResult::SystemError,
+        "SPJ Error" => PossibleResult::SPJError,
+        "Skipped" => PossibleResult::Skipped,
+        _ => return Err(rusqlite::Error::InvalidQuery),
+    };
+    Ok(result)
+}
 
-    let response_iter = stmt.query_map(params![], |row| {
-        let created_time: String = row.get(1)?;
-        let updated_time: String = row.get(2)?;
-        let submission_json: String = row.get(3)?;
-        let cases_json: String = row.get(7)?;
+// reads back a time stored with to_rfc3339, index is the column it was read from
+fn parse_time(index: usize, time: &str) -> Result<DateTime<Utc>>
+{
+    time.parse::<DateTime<Utc>>().map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(err)))
+}
 
-        Ok(ResponseContent {
-            id: row.get(0)?,
-            created_time: created_time.parse::<DateTime<Utc>>().unwrap(),
-            updated_time: updated_time.parse::<DateTime<Utc>>().unwrap(),
-            submission: serde_json::from_str(&submission_json).unwrap(),
-            state: row.get(4)?,
-            result: match row.get::<_, String>(5)?.as_str() {
-                "Waiting" => PossibleResult::Waiting,
-                "Running" => PossibleResult::Running,
-                "Accepted" => PossibleResult::Accepted,
-                "Compilation Error" => PossibleResult::CompilationError,
-                "Compilation Success" => PossibleResult::CompilationSuccess,
-                "Wrong Answer" => PossibleResult::WrongAnswer,
-                "Runtime Error" => PossibleResult::RuntimeError,
-                "Time Limit Exceeded" => PossibleResult::TimeLimitExceeded,
-                "Memory Limit Exceeded" => PossibleResult::MemoryLimitExceeded,
-                "System Error" => PossibleResult::SystemError,
-                "SPJ Error" => PossibleResult::SPJError,
-                "Skipped" => PossibleResult::Skipped,
-                _ => return Err(rusqlite::Error::InvalidQuery),
-            },
-            score: row.get(6)?,
-            cases: serde_json::from_str(&cases_json).unwrap(),
-        })
-    })?;
+// reads a case from a row starting with the columns of CASE_COLUMNS
+fn case_from_row(row: &Row) -> Result<Case>
+{
+    let input: Option<String> = row.get(5)?;
+    let feedback = match input {
+        Some(input) => Some(CaseFeedback {
+            input,
+            answer: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+            output: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+            stderr: row.get::<_, Option<String>>(8)?.unwrap_or_default(),
+        }),
+        None => None,
+    };
+    Ok(Case {
+        id: row.get(0)?,
+        result: parse_result(&row.get::<_, String>(1)?)?,
+        info: row.get(2)?,
+        time: row.get(3)?,
+        memory: row.get(4)?,
+        feedback,
+    })
+}
+
+// reads a job without its cases from a row with the columns of JOB_COLUMNS
+fn job_from_row(row: &Row) -> Result<ResponseContent>
+{
+    let created_time: String = row.get(1)?;
+    let updated_time: String = row.get(2)?;
+
+    Ok(ResponseContent {
+        id: row.get(0)?,
+        created_time: parse_time(1, &created_time)?,
+        updated_time: parse_time(2, &updated_time)?,
+        submission: PostJob {
+            source_code: row.get(3)?,
+            language: row.get(4)?,
+            user_id: row.get(5)?,
+            contest_id: row.get(6)?,
+            problem_id: row.get(7)?,
+        },
+        state: row.get(8)?,
+        result: parse_result(&row.get::<_, String>(9)?)?,
+        score: row.get(10)?,
+        cases: vec![],
+    })
+}
 
-    // stores each entry into a vector
-    for response in response_iter {
+const JOB_COLUMNS: &str = "id, created_time, updated_time, source_code, language, user_id, contest_id, problem_id, state, result, score";
+const CASE_COLUMNS: &str = "id, result, info, time, memory, input, answer, output, stderr";
+
+// retrieves the jobs that are not finished, ordered by id
+// finished jobs are read when they are needed, see storage::Storage::keep_job
+pub fn retrieve_unfinished_jobs(conn: &Connection) -> Result<Vec<ResponseContent>> 
+{
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM jobs WHERE state != 'Finished' ORDER BY id", JOB_COLUMNS))?;
+    let job_iter = stmt.query_map([], |row| job_from_row(row))?;
+    let mut response_contents = Vec::new();
+    for response in job_iter {
         response_contents.push(response?);
     }
-
-    //returns the vector
+    retrieve_cases(conn, &mut response_contents)?;
     Ok(response_contents)
 }
 
-// no default value neede for job list
+// retrieves a single job with its cases
+pub fn retrieve_job(conn: &Connection, job_id: u32) -> Result<Option<ResponseContent>> 
+{
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM jobs WHERE id = ?1", JOB_COLUMNS))?;
+    let job_iter = stmt.query_map(params![job_id], |row| job_from_row(row))?;
+    let mut response_contents = Vec::new();
+    for response in job_iter {
+        response_contents.push(response?);
+    }
+    retrieve_cases(conn, &mut response_contents)?;
+    Ok(response_contents.pop())
+}
 
-// CONTEST ID COUNT 
+// fills in the cases of the given jobs
+fn retrieve_cases(conn: &Connection, response_contents: &mut Vec<ResponseContent>) -> Result<()>
+{
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM job_cases WHERE job_id = ?1 ORDER BY id", CASE_COLUMNS))?;
+    for response_content in response_contents.iter_mut() {
+        let case_iter = stmt.query_map(params![response_content.id], |row| case_from_row(row))?;
+        for case in case_iter {
+            response_content.cases.push(case?);
+        }
+    }
+    Ok(())
+}
 
-// checks if SQL storage is on
-// if yes, then stores the count
-pub fn push_contest_count(connection: &Connection, count: u32)
+// retrieves the jobs matching the query, see storage::JobQuery
+// each filter is a condition on an indexed column, and the cases are only read for the jobs returned
+pub fn query_jobs(conn: &Connection, query: &JobQuery) -> Result<Vec<ResponseContent>>
 {
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        store_contest_id_count(&connection, count).expect("failed to store contest id count");
+    let mut conditions: Vec<&str> = vec![];
+    let mut values: Vec<Box<dyn ToSql>> = vec![];
+
+    if let Some(user_id) = query.user_id {
+        conditions.push("user_id = ?");
+        values.push(Box::new(user_id));
+    }
+    if let Some(user_name) = &query.user_name {
+        conditions.push("user_id IN (SELECT id FROM user_list WHERE name = ? COLLATE NOCASE)");
+        values.push(Box::new(user_name.clone()));
+    }
+    if let Some(contest_id) = query.contest_id {
+        conditions.push("contest_id = ?");
+        values.push(Box::new(contest_id));
+    }
+    if let Some(problem_id) = query.problem_id {
+        conditions.push("problem_id = ?");
+        values.push(Box::new(problem_id));
+    }
+    if let Some(language) = &query.language {
+        conditions.push("language = ?");
+        values.push(Box::new(language.clone()));
+    }
+    // times are stored by to_rfc3339 in UTC, so their text sorts like the times and the index on it is used
+    if let Some(from) = query.from {
+        conditions.push("created_time >= ?");
+        values.push(Box::new(from.to_rfc3339()));
+    }
+    if let Some(to) = query.to {
+        conditions.push("created_time <= ?");
+        values.push(Box::new(to.to_rfc3339()));
     }
+    if let Some(state) = &query.state {
+        conditions.push("state = ?");
+        values.push(Box::new(state.clone()));
+    }
+    if let Some(result) = &query.result {
+        conditions.push("result = ?");
+        values.push(Box::new(result_name(result)));
+    }
+    if let Some(after_id) = query.after_id {
+        conditions.push(if query.descending {"id < ?"} else {"id > ?"});
+        values.push(Box::new(after_id));
+    }
+
+    let mut sql = format!("SELECT {} FROM jobs", JOB_COLUMNS);
+    if !query.with_source {
+        sql = sql.replace("source_code", "'' AS source_code");
+    }
+    if !conditions.is_empty() {
+        sql += &format!(" WHERE {}", conditions.join(" AND "));
+    }
+    sql += if query.descending {" ORDER BY id DESC"} else {" ORDER BY id"};
+    if let Some(limit) = query.limit {
+        sql += " LIMIT ?";
+        values.push(Box::new(limit));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let job_iter = stmt.query_map(params_from_iter(values.iter()), |row| job_from_row(row))?;
+    let mut response_contents = Vec::new();
+    for response in job_iter {
+        response_contents.push(response?);
+    }
+    retrieve_cases(conn, &mut response_contents)?;
+
+    Ok(response_contents)
 }
 
+// CONTEST ID COUNT 
+
 // stores count into database
 pub fn store_contest_id_count(conn: &Connection, count: u32) -> Result<()> {
     let count_str = count.to_string();
@@ -418,21 +414,6 @@ pub fn store_contest_id_count(conn: &Connection, count: u32) -> Result<()> {
     Ok(())
 }
 
-// checks if SQL storage is on
-// if yes, then retrieves the count
-pub fn get_contest_count(connection: &Connection, count: &mut u32)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        *count = match sql::retrieve_contest_id_count(&connection)
-        {
-            Ok(x) => x,
-            Err(_) => {panic!("unsuccessful retrieve");}
-        };
-    }
-}
-
 // retrieves the count from the database
 pub fn retrieve_contest_id_count(conn: &Connection) -> Result<u32>
 {
@@ -442,90 +423,154 @@ pub fn retrieve_contest_id_count(conn: &Connection) -> Result<u32>
     Ok(count)
 }
 
-// inserts the default value of the contest count, which is 1
-pub fn insert_default_contest_id_count(conn: &Connection) -> Result<()> 
-{
+
+// CONTEST LIST
+
+// inserts the contest, its problems and its participants into the database
+// rows of the contest that were stored before are replaced
+pub fn store_contest(conn: &Connection, contest: &Contest) -> Result<()> {
     conn.execute(
-        "INSERT OR REPLACE INTO contest_id_count (key, value) VALUES (?1, ?2)",
-        params!["CONTEST_ID_COUNT", 1],
+        "INSERT INTO contests (id, name, from_time, to_time, submission_limit) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (id) DO UPDATE SET name = ?2, from_time = ?3, to_time = ?4, submission_limit = ?5",
+        params![
+            contest.id,
+            contest.name,
+            contest.from.to_rfc3339(),
+            contest.to.to_rfc3339(),
+            contest.submission_limit,
+        ],
     )?;
+
+    conn.execute("DELETE FROM contest_problems WHERE contest_id = ?1", params![contest.id])?;
+    for (position, problem_id) in contest.problem_ids.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO contest_problems (contest_id, position, problem_id) VALUES (?1, ?2, ?3)",
+            params![contest.id, position, problem_id],
+        )?;
+    }
+
+    conn.execute("DELETE FROM contest_teams WHERE contest_id = ?1", params![contest.id])?;
+    for (position, team_id) in contest.team_ids.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO contest_teams (contest_id, position, team_id) VALUES (?1, ?2, ?3)",
+            params![contest.id, position, team_id],
+        )?;
+    }
+
+    // participants that are not in the contest anymore are removed
+    conn.execute("DELETE FROM participant_case_times WHERE contest_id = ?1", params![contest.id])?;
+    conn.execute("DELETE FROM participant_results WHERE contest_id = ?1", params![contest.id])?;
+    conn.execute("DELETE FROM contest_participants WHERE contest_id = ?1", params![contest.id])?;
+    for rank_info in contest.users.iter() {
+        store_participant(conn, contest, rank_info.user.id)?;
+    }
+
+    store_case_times(conn, contest)?;
     Ok(())
 }
 
+// replaces the best case times of the contest
+// cases that were never run keep the MAX time and are not stored
+pub fn store_case_times(conn: &Connection, contest: &Contest) -> Result<()> {
+    conn.execute("DELETE FROM contest_case_times WHERE contest_id = ?1", params![contest.id])?;
+    for (problem_index, times) in contest.case_times.iter().enumerate() {
+        for (case_index, time) in times.iter().enumerate() {
+            if *time == MAX {continue;}
+            conn.execute(
+                "INSERT INTO contest_case_times (contest_id, problem_index, case_index, time) VALUES (?1, ?2, ?3, ?4)",
+                params![contest.id, problem_index, case_index, time],
+            )?;
+        }
+    }
+    Ok(())
+}
 
-// CONTEST LIST
+// retrieves the best case times of a contest as (problem index, case index, time)
+pub fn retrieve_case_times(conn: &Connection, contest_id: u32) -> Result<Vec<(usize, usize, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT problem_index, case_index, time FROM contest_case_times WHERE contest_id = ?1 ORDER BY problem_index, case_index")?;
+    let time_iter = stmt.query_map(params![contest_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
 
-// checks if SQL storage is on
-// if yes, then stores the the contest list
-pub fn push_contest(connection: &Connection, lock_contest_list: &Vec<contest::Contest>)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        store_contests(&connection, &lock_contest_list).expect("failed to store contest list");
+    let mut times = Vec::new();
+    for time in time_iter {
+        times.push(time?);
     }
+    Ok(times)
 }
 
-// stores the the contest list
-pub fn store_contests(conn: &Connection, contests: &[Contest]) -> Result<()> {
-    // first deletes the original list
-    conn.execute("DELETE FROM contest", [])?;
-    // the inserts each element of the new list
-    for contest in contests {
-        let problem_ids_json = serde_json::to_string(&contest.problem_ids).expect("error store contest");
-        let user_ids_json = serde_json::to_string(&contest.user_ids).expect("error store contest");
-        let users_json = serde_json::to_string(&contest.users).expect("error store contest");
+// inserts the progress of a user in the contest, or updates the rows that were stored before
+pub fn store_participant(conn: &Connection, contest: &Contest, user_id: u32) -> Result<()> {
+    let position = match contest.users.iter().position(|rank_info| rank_info.user.id == user_id) {
+        Some(position) => position,
+        None => return Ok(()),
+    };
+    let rank_info = &contest.users[position];
+
+    conn.execute(
+        "INSERT INTO contest_participants (contest_id, user_id, position, rank, score, submission_count, competitive_score_sum, latest_submission)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT (contest_id, user_id) DO UPDATE SET position = ?3, rank = ?4, score = ?5, submission_count = ?6,
+             competitive_score_sum = ?7, latest_submission = ?8",
+        params![
+            contest.id,
+            user_id,
+            position,
+            rank_info.rank,
+            rank_info.score,
+            rank_info.submission_count,
+            rank_info.competitive_score_sum,
+            rank_info.latest_submission.to_rfc3339(),
+        ],
+    )?;
+
+    for problem_index in 0..rank_info.highest_scores.len() {
         conn.execute(
-            "INSERT INTO contest (id, name, from_time, to_time, problem_ids, user_ids, submission_limit, users) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            "INSERT OR REPLACE INTO participant_results (contest_id, user_id, problem_index, score, highest_score, latest_score)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
                 contest.id,
-                contest.name,
-                contest.from.to_rfc3339(),
-                contest.to.to_rfc3339(),
-                problem_ids_json,
-                user_ids_json,
-                contest.submission_limit,
-                users_json
+                user_id,
+                problem_index,
+                rank_info.scores.get(problem_index).copied().unwrap_or(0.0),
+                rank_info.highest_scores[problem_index],
+                rank_info.latest_scores.get(problem_index).copied().unwrap_or(0.0),
             ],
         )?;
     }
-    Ok(())
-}
 
-// checks if SQL storage is on
-// if yes, then retrieves contest list
-pub fn get_contest(connection: &Connection, lock_contest_list: &mut Vec<contest::Contest>)
-{
-    let is_sql = IS_SQL.lock().unwrap();
-    if *is_sql
-    {
-        *lock_contest_list = match sql::retrieve_contests(&connection)
-        {
-            Ok(x) => x,
-            Err(_) => {panic!("unsuccessful retrieve");}
-        };
+    // cases that were never run keep the MAX time and are not stored
+    for (problem_index, times) in rank_info.shortest_times.iter().enumerate() {
+        for (case_index, time) in times.iter().enumerate() {
+            if *time == MAX {continue;}
+            conn.execute(
+                "INSERT OR REPLACE INTO participant_case_times (contest_id, user_id, problem_index, case_index, time)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![contest.id, user_id, problem_index, case_index, time],
+            )?;
+        }
     }
+    Ok(())
 }
 
-// retrieves contest list
+// retrieves contest list, ordered by id
 pub fn retrieve_contests(conn: &Connection) -> Result<Vec<Contest>> {
-    let mut stmt = conn.prepare("SELECT id, name, from_time, to_time, problem_ids, user_ids, submission_limit, users FROM contest")?;
+    let mut stmt = conn.prepare("SELECT id, name, from_time, to_time, submission_limit FROM contests ORDER BY id")?;
     let contest_iter = stmt.query_map([], |row| {
         let from_time: String = row.get(2)?;
         let to_time: String = row.get(3)?;
-        let problem_ids_json: String = row.get(4)?;
-        let user_ids_json: String = row.get(5)?;
-        let users_json: String = row.get(7)?;
 
         Ok(Contest {
             id: row.get(0)?,
             name: row.get(1)?,
-            from: from_time.parse::<DateTime<Utc>>().unwrap(),
-            to: to_time.parse::<DateTime<Utc>>().unwrap(),
-            problem_ids: serde_json::from_str(&problem_ids_json).unwrap(),
-            user_ids: serde_json::from_str(&user_ids_json).unwrap(),
-            submission_limit: row.get(6)?,
-            users: serde_json::from_str(&users_json).unwrap(),
+            from: parse_time(2, &from_time)?,
+            to: parse_time(3, &to_time)?,
+            problem_ids: vec![],
+            user_ids: vec![],
+            team_ids: vec![],
+            submission_limit: row.get(4)?,
+            users: vec![],
+            // sized by the problems of the config, see SqliteStorage::open
+            case_times: vec![],
         })
     })?;
 
@@ -534,60 +579,389 @@ pub fn retrieve_contests(conn: &Connection) -> Result<Vec<Contest>> {
         contests.push(contest?);
     }
 
+    for contest in contests.iter_mut() {
+        retrieve_contest_rows(conn, contest)?;
+    }
+
     Ok(contests)
 }
 
-// stores the default value of the contest list
-// which only consists of contest 0
-pub fn insert_default_contest(conn: &Connection, config: &config::Config) -> Result<()> 
-{
-    // first create contest 0, then push into database
-    let mut problem_ids: Vec<u32> = vec![];
-    let user_ids: Vec<u32> = vec![0];
-    let mut users: Vec<contest::RankInfo> = vec![];
+// fills in the problems and participants of a contest
+fn retrieve_contest_rows(conn: &Connection, contest: &mut Contest) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT problem_id FROM contest_problems WHERE contest_id = ?1 ORDER BY position")?;
+    let problem_iter = stmt.query_map(params![contest.id], |row| row.get::<_, u32>(0))?;
+    for problem_id in problem_iter {
+        contest.problem_ids.push(problem_id?);
+    }
 
-    for i in 0..config.problems.len()
-    {
-        problem_ids.push(i as u32);
-    }
-    users.push(
-        contest::RankInfo
-        {
-            user: User {
-                id: 0,
-                name: "root".to_string(),
-                },
-            scores: vec![0.0; config.problems.len()],
-            rank: 0,
-            
-            highest_scores: vec![0.0 ; config.problems.len()],
-            latest_scores: vec![0.0 ; config.problems.len()],
-
-            competitive_score_sum: 0.0,
-            shortest_times: vec![vec![MAX; 20]; config.problems.len()], // possible error
-            
-            latest_submission: DateTime::<Utc>::MAX_UTC,
-            score: 0,
-            submission_count: 0,
+    let mut stmt = conn.prepare("SELECT team_id FROM contest_teams WHERE contest_id = ?1 ORDER BY position")?;
+    let team_iter = stmt.query_map(params![contest.id], |row| row.get::<_, u32>(0))?;
+    for team_id in team_iter {
+        contest.team_ids.push(team_id?);
+    }
+    let problem_count = contest.problem_ids.len();
+
+    let mut stmt = conn.prepare(
+        "SELECT p.user_id, u.name, p.rank, p.score, p.submission_count, p.competitive_score_sum, p.latest_submission
+         FROM contest_participants p JOIN user_list u ON u.id = p.user_id
+         WHERE p.contest_id = ?1 ORDER BY p.position")?;
+    let participant_iter = stmt.query_map(params![contest.id], |row| {
+        let latest_submission: String = row.get(6)?;
+        Ok(contest::RankInfo {
+            user: User::new(row.get(0)?, row.get(1)?),
+            rank: row.get(2)?,
+            scores: vec![0.0; problem_count],
+
+            highest_scores: vec![0.0; problem_count],
+            latest_scores: vec![0.0; problem_count],
+
+            competitive_score_sum: row.get(5)?,
+            shortest_times: vec![vec![MAX; CASE_TIMES_WIDTH]; problem_count],
+
+            latest_submission: parse_time(6, &latest_submission)?,
+            score: row.get(3)?,
+            submission_count: row.get(4)?,
+        })
+    })?;
+    for rank_info in participant_iter {
+        let rank_info = rank_info?;
+        contest.user_ids.push(rank_info.user.id);
+        contest.users.push(rank_info);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT user_id, problem_index, score, highest_score, latest_score FROM participant_results WHERE contest_id = ?1")?;
+    let mut rows = stmt.query(params![contest.id])?;
+    while let Some(row) = rows.next()? {
+        let user_id: u32 = row.get(0)?;
+        let problem_index: usize = row.get(1)?;
+        if let Some(rank_info) = contest.users.iter_mut().find(|rank_info| rank_info.user.id == user_id) {
+            if problem_index >= problem_count {continue;}
+            rank_info.scores[problem_index] = row.get(2)?;
+            rank_info.highest_scores[problem_index] = row.get(3)?;
+            rank_info.latest_scores[problem_index] = row.get(4)?;
+        }
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT user_id, problem_index, case_index, time FROM participant_case_times WHERE contest_id = ?1")?;
+    let mut rows = stmt.query(params![contest.id])?;
+    while let Some(row) = rows.next()? {
+        let user_id: u32 = row.get(0)?;
+        let problem_index: usize = row.get(1)?;
+        let case_index: usize = row.get(2)?;
+        if let Some(rank_info) = contest.users.iter_mut().find(|rank_info| rank_info.user.id == user_id) {
+            if problem_index >= problem_count || case_index >= CASE_TIMES_WIDTH {continue;}
+            rank_info.shortest_times[problem_index][case_index] = row.get(3)?;
         }
-    );
+    }
+    Ok(())
+}
 
-    let problem_ids_json = serde_json::to_string(&problem_ids).expect("error store contest");
-    let user_ids_json = serde_json::to_string(&user_ids).expect("error store contest");
-    let users_json = serde_json::to_string(&users).expect("error store contest");
 
+// AUDIT LOG
+
+// appends a change to the audit log, the id is given by the database
+pub fn store_event(conn: &Connection, actor: &str, change: &audit::Change) -> Result<()> {
     conn.execute(
-        "INSERT INTO contest (id, name, from_time, to_time, problem_ids, user_ids, submission_limit, users) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        "INSERT INTO audit_log (time, actor, kind, change) VALUES (?1, ?2, ?3, ?4)",
         params![
-            0,
-            "root".to_string(),
-            DateTime::<Utc>::MAX_UTC.to_rfc3339(),
-            DateTime::<Utc>::MIN_UTC.to_rfc3339(),
-            problem_ids_json,
-            user_ids_json,
-            0,
-            users_json
+            Utc::now().to_rfc3339(),
+            actor,
+            change.kind(),
+            serde_json::to_string(change).expect("error serializing change"),
         ],
     )?;
     Ok(())
+}
+
+// retrieves the entries of the audit log matching the query, ordered by id
+pub fn query_events(conn: &Connection, query: &audit::EventQuery) -> Result<Vec<audit::Event>> {
+    let mut conditions: Vec<&str> = vec![];
+    let mut values: Vec<Box<dyn ToSql>> = vec![];
+
+    if let Some(kind) = &query.kind {
+        conditions.push("kind = ?");
+        values.push(Box::new(kind.clone()));
+    }
+    if let Some(actor) = &query.actor {
+        conditions.push("actor = ?");
+        values.push(Box::new(actor.clone()));
+    }
+    if let Some(from) = query.from {
+        conditions.push("time >= ?");
+        values.push(Box::new(from.to_rfc3339()));
+    }
+    if let Some(to) = query.to {
+        conditions.push("time <= ?");
+        values.push(Box::new(to.to_rfc3339()));
+    }
+    if let Some(after_id) = query.after_id {
+        conditions.push("id > ?");
+        values.push(Box::new(after_id as i64));
+    }
+
+    let mut sql = "SELECT id, time, actor, change FROM audit_log".to_string();
+    if !conditions.is_empty() {
+        sql += &format!(" WHERE {}", conditions.join(" AND "));
+    }
+    sql += " ORDER BY id";
+    if let Some(limit) = query.limit {
+        sql += " LIMIT ?";
+        values.push(Box::new(limit));
+    }
+
+    let mut stmt = conn.prepare(&sql)?;
+    let event_iter = stmt.query_map(params_from_iter(values.iter()), |row| {
+        let time: String = row.get(1)?;
+        let change: String = row.get(3)?;
+        Ok(audit::Event {
+            id: row.get::<_, i64>(0)? as u64,
+            time: parse_time(1, &time)?,
+            actor: row.get(2)?,
+            change: serde_json::from_str(&change).map_err(|err| rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(err)))?,
+        })
+    })?;
+
+    let mut events = Vec::new();
+    for event in event_iter {
+        events.push(event?);
+    }
+    Ok(events)
+}
+
+
+// AUTHENTICATION
+
+// stores the password hash of a user, replacing the previous one
+pub fn store_password(conn: &Connection, user_id: u32, password_hash: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO credentials (user_id, password_hash) VALUES (?1, ?2)",
+        params![user_id, password_hash],
+    )?;
+    Ok(())
+}
+
+// retrieves the password hash of a user, None if the user has no password
+pub fn retrieve_password(conn: &Connection, user_id: u32) -> Result<Option<String>> {
+    let mut stmt = conn.prepare("SELECT password_hash FROM credentials WHERE user_id = ?1")?;
+    let mut rows = stmt.query(params![user_id])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+// stores a session, replacing the one with the same token, e.g. when it is revoked
+pub fn store_session(conn: &Connection, session: &auth::Session) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sessions (token_hash, user_id, created_time, expires_time, revoked) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            session.token_hash,
+            session.user_id,
+            session.created_time.to_rfc3339(),
+            session.expires_time.to_rfc3339(),
+            session.revoked,
+        ],
+    )?;
+    Ok(())
+}
+
+// retrieves the session with the given token hash
+pub fn retrieve_session(conn: &Connection, token_hash: &str) -> Result<Option<auth::Session>> {
+    let mut stmt = conn.prepare("SELECT token_hash, user_id, created_time, expires_time, revoked FROM sessions WHERE token_hash = ?1")?;
+    let mut rows = stmt.query(params![token_hash])?;
+    let row = match rows.next()? {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let created_time: String = row.get(2)?;
+    let expires_time: String = row.get(3)?;
+    Ok(Some(auth::Session {
+        token_hash: row.get(0)?,
+        user_id: row.get(1)?,
+        created_time: parse_time(2, &created_time)?,
+        expires_time: parse_time(3, &expires_time)?,
+        revoked: row.get(4)?,
+    }))
+}
+
+// stores the role of a user, replacing the previous one
+pub fn store_role(conn: &Connection, user_id: u32, role: Role) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO user_roles (user_id, role) VALUES (?1, ?2)",
+        params![user_id, serde_json::to_value(role).expect("error serializing role").as_str()],
+    )?;
+    Ok(())
+}
+
+// retrieves the role of a user, None if the user has the default role
+pub fn retrieve_role(conn: &Connection, user_id: u32) -> Result<Option<Role>> {
+    let mut stmt = conn.prepare("SELECT role FROM user_roles WHERE user_id = ?1")?;
+    let mut rows = stmt.query(params![user_id])?;
+    match rows.next()? {
+        Some(row) => {
+            let role: String = row.get(0)?;
+            Ok(serde_json::from_value(serde_json::Value::String(role)).ok())
+        }
+        None => Ok(None),
+    }
+}
+
+// stores an API key, replacing the one with the same id, e.g. when it is revoked
+pub fn store_api_key(conn: &Connection, api_key: &ApiKey) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO api_keys (id, key_hash, user_id, name, scopes, created_time, expires_time, last_used_time, revoked)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            api_key.id,
+            api_key.key_hash,
+            api_key.user_id,
+            api_key.name,
+            serde_json::to_string(&api_key.scopes).expect("error serializing scopes"),
+            api_key.created_time.to_rfc3339(),
+            api_key.expires_time.map(|time| time.to_rfc3339()),
+            api_key.last_used_time.map(|time| time.to_rfc3339()),
+            api_key.revoked,
+        ],
+    )?;
+    Ok(())
+}
+
+// records when an API key was last used
+pub fn store_api_key_use(conn: &Connection, id: u32, time: DateTime<Utc>) -> Result<()> {
+    conn.execute("UPDATE api_keys SET last_used_time = ?1 WHERE id = ?2", params![time.to_rfc3339(), id])?;
+    Ok(())
+}
+
+fn api_key_from_row(row: &Row) -> Result<ApiKey> {
+    let scopes: String = row.get(4)?;
+    let created_time: String = row.get(5)?;
+    let expires_time: Option<String> = row.get(6)?;
+    let last_used_time: Option<String> = row.get(7)?;
+    Ok(ApiKey {
+        id: row.get(0)?,
+        key_hash: row.get(1)?,
+        user_id: row.get(2)?,
+        name: row.get(3)?,
+        scopes: serde_json::from_str(&scopes).map_err(|err| rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(err)))?,
+        created_time: parse_time(5, &created_time)?,
+        expires_time: expires_time.map(|time| parse_time(6, &time)).transpose()?,
+        last_used_time: last_used_time.map(|time| parse_time(7, &time)).transpose()?,
+        revoked: row.get(8)?,
+    })
+}
+
+const API_KEY_COLUMNS: &str = "id, key_hash, user_id, name, scopes, created_time, expires_time, last_used_time, revoked";
+
+// retrieves the API keys, of a single user if user_id is given, ordered by id
+pub fn retrieve_api_keys(conn: &Connection, user_id: Option<u32>) -> Result<Vec<ApiKey>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM api_keys WHERE ?1 IS NULL OR user_id = ?1 ORDER BY id", API_KEY_COLUMNS))?;
+    let api_key_iter = stmt.query_map(params![user_id], |row| api_key_from_row(row))?;
+    api_key_iter.collect()
+}
+
+// retrieves the API key with the given key hash
+pub fn retrieve_api_key(conn: &Connection, key_hash: &str) -> Result<Option<ApiKey>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM api_keys WHERE key_hash = ?1", API_KEY_COLUMNS))?;
+    let mut rows = stmt.query(params![key_hash])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(api_key_from_row(row)?)),
+        None => Ok(None),
+    }
+}
+
+// TEAMS
+
+// inserts the team and its members into the database, or updates the rows that were stored before
+pub fn store_team(conn: &Connection, team: &Team) -> Result<()> {
+    conn.execute(
+        "INSERT INTO teams (id, name, coach_id) VALUES (?1, ?2, ?3)
+         ON CONFLICT (id) DO UPDATE SET name = ?2, coach_id = ?3",
+        params![team.id, team.name, team.coach_id],
+    )?;
+    conn.execute("DELETE FROM team_members WHERE team_id = ?1", params![team.id])?;
+    for (position, user_id) in team.member_ids.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO team_members (team_id, position, user_id) VALUES (?1, ?2, ?3)",
+            params![team.id, position, user_id],
+        )?;
+    }
+    Ok(())
+}
+
+// retrieves the teams with their members, ordered by id
+pub fn retrieve_teams(conn: &Connection) -> Result<Vec<Team>> {
+    let mut stmt = conn.prepare("SELECT id, name, coach_id FROM teams ORDER BY id")?;
+    let team_iter = stmt.query_map([], |row| {
+        Ok(Team {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            member_ids: vec![],
+            coach_id: row.get(2)?,
+        })
+    })?;
+    let mut teams = Vec::new();
+    for team in team_iter {
+        teams.push(team?);
+    }
+
+    let mut stmt = conn.prepare("SELECT user_id FROM team_members WHERE team_id = ?1 ORDER BY position")?;
+    for team in teams.iter_mut() {
+        let member_iter = stmt.query_map(params![team.id], |row| row.get::<_, u32>(0))?;
+        for user_id in member_iter {
+            team.member_ids.push(user_id?);
+        }
+    }
+    Ok(teams)
+}
+
+// QUOTAS
+
+// stores when the usage of a user was last reset, replacing the previous time
+pub fn store_usage_reset(conn: &Connection, user_id: u32, time: DateTime<Utc>) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO usage_resets (user_id, reset_time) VALUES (?1, ?2)",
+        params![user_id, time.to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+// retrieves when the usage of a user was last reset, None if it never was
+pub fn retrieve_usage_reset(conn: &Connection, user_id: u32) -> Result<Option<DateTime<Utc>>> {
+    let mut stmt = conn.prepare("SELECT reset_time FROM usage_resets WHERE user_id = ?1")?;
+    let mut rows = stmt.query(params![user_id])?;
+    match rows.next()? {
+        Some(row) => {
+            let reset_time: String = row.get(0)?;
+            Ok(Some(parse_time(0, &reset_time)?))
+        }
+        None => Ok(None),
+    }
+}
+
+
+// DELETION
+
+// removes the password, the role, the sessions, the API keys and the usage reset of a user
+pub fn delete_credentials(conn: &Connection, user_id: u32) -> Result<()> {
+    conn.execute("DELETE FROM credentials WHERE user_id = ?1", params![user_id])?;
+    conn.execute("DELETE FROM user_roles WHERE user_id = ?1", params![user_id])?;
+    conn.execute("DELETE FROM sessions WHERE user_id = ?1", params![user_id])?;
+    conn.execute("DELETE FROM api_keys WHERE user_id = ?1", params![user_id])?;
+    conn.execute("DELETE FROM usage_resets WHERE user_id = ?1", params![user_id])?;
+    Ok(())
+}
+
+// removes a user, after its credentials, jobs, contest participations and team memberships
+pub fn delete_user(conn: &Connection, user_id: u32) -> Result<()> {
+    conn.execute("DELETE FROM user_list WHERE id = ?1", params![user_id])?;
+    Ok(())
+}
+
+// removes a job, its cases are removed with it
+pub fn delete_job(conn: &Connection, job_id: u32) -> Result<()> {
+    conn.execute("DELETE FROM jobs WHERE id = ?1", params![job_id])?;
+    Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,44 @@
+use actix_web::{get, Responder, HttpResponse, web};
+use std::sync::Mutex;
+use std::sync::Arc;
+
+use crate::config;
+use crate::job;
+use crate::problem;
+use crate::permission;
+
+// downloads the sample cases of a problem, hidden cases are never returned
+#[get("/problems/{problemId}/samples", wrap = "permission::Require(permission::Permission::view)")]
+#[allow(non_snake_case)]
+async fn get_problems_problemId_samples(problemId: web::Path<u32>, data_config: web::Data<Arc<Mutex<config::Config>>>) -> impl Responder
+{
+    let config = data_config.lock().unwrap().clone();
+
+    let problem = match config.problems.iter().find(|problem| problem.id == *problemId)
+    {
+        Some(problem) => problem,
+        None =>
+        {
+            return HttpResponse::NotFound().json(job::Error::
+                new(3, "ERR_NOT_FOUND".to_string(), format!("Problem {} not found.", problemId.to_owned()).to_string()));
+        }
+    };
+
+    let mut samples: Vec<problem::Sample> = vec![];
+    for (i, case) in problem.cases.iter().enumerate()
+    {
+        if !case.sample {continue;}
+
+        match (std::fs::read_to_string(&case.input_file), std::fs::read_to_string(&case.answer_file))
+        {
+            (Ok(input), Ok(answer)) => samples.push(problem::Sample { id: i as u32 + 1, input, answer }),
+            _ =>
+            {
+                return HttpResponse::InternalServerError().json(job::Error::
+                    new(6, "ERR_INTERNAL".to_string(), format!("Data of case {} could not be read.", i + 1).to_string()));
+            }
+        }
+    }
+
+    return HttpResponse::Ok().json(samples);
+}
\ No newline at end of file
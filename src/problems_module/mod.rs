@@ -0,0 +1,2 @@
+pub mod problem;
+pub mod function_get_problems;
\ No newline at end of file
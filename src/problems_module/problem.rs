@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+// a public case of a problem, id is the same as in the cases of a job
+#[derive(Serialize, Debug, Clone)]
+pub struct Sample
+{
+    pub id: u32,
+    pub input: String,
+    pub answer: String,
+}
\ No newline at end of file
@@ -0,0 +1,143 @@
+use actix_web::{post, Responder, HttpResponse, HttpRequest, web};
+use actix_web::http::header;
+use std::fs::File;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::sync::Arc;
+
+use crate::config;
+use crate::job;
+use crate::judge;
+use crate::run;
+use crate::rate_limit;
+
+use crate::RUN_ID_COUNT;
+use crate::permission;
+
+// compiles the source code in dir and runs it once on the given input
+// the directory is deleted afterwards
+fn run_code(body: &run::PostRun, language: &config::Language, dir: &str, time_limit: u64, memory_limit: u64,
+    output_limit: usize) -> run::RunResponse
+{
+    let mut response = run::RunResponse
+    {
+        compiled: false,
+        compiler_output: "".to_string(),
+        stdout: "".to_string(),
+        stdout_truncated: false,
+        stderr: "".to_string(),
+        stderr_truncated: false,
+        time: 0,
+        memory: 0,
+        exit_code: None,
+        signal: None,
+        time_limit_exceeded: false,
+        memory_limit_exceeded: false,
+    };
+
+    match judge::compile(&body.source_code, language, dir)
+    {
+        Ok(_) => response.compiled = true,
+        Err(output) => response.compiler_output = output.chars().take(output_limit).collect(),
+    }
+
+    if response.compiled
+    {
+        let files = (std::fs::write(format!("{}/run.in", dir), body.stdin.clone()),
+            File::open(format!("{}/run.in", dir)),
+            File::create(format!("{}/run.out", dir)),
+            File::create(format!("{}/run.err", dir)));
+
+        if let (Ok(_), Ok(stdin), Ok(stdout), Ok(stderr)) = files
+        {
+            let execution = judge::execute(&format!("{}/test.exe", dir), Stdio::from(stdin), Stdio::from(stdout),
+                Stdio::from(stderr), time_limit, memory_limit);
+
+            (response.stdout, response.stdout_truncated) = judge::read_excerpt(&format!("{}/run.out", dir), output_limit);
+            (response.stderr, response.stderr_truncated) = judge::read_excerpt(&format!("{}/run.err", dir), output_limit);
+            response.time = execution.time;
+            response.memory = execution.memory;
+            response.exit_code = execution.exit_code;
+            response.signal = execution.signal;
+            response.time_limit_exceeded = execution.timed_out;
+            response.memory_limit_exceeded = memory_limit > 0 && execution.memory as u64 * 1024 > memory_limit;
+        }
+    }
+
+    // delete temporary directory
+    match std::fs::remove_dir_all(dir)
+    {
+        Ok(_) => {}
+        Err(_) => {}
+    }
+
+    return response;
+}
+
+// runs source code on custom input
+// nothing is stored: no job is created and no ranklist is updated
+// runs take from their own rate limits, see config::Runs
+#[post("/runs", wrap = "permission::Require(permission::Permission::submit)")]
+async fn post_runs(req: HttpRequest, body: web::Json<run::PostRun>, caller: web::ReqData<permission::Caller>,
+    data_config: web::Data<Arc<Mutex<config::Config>>>) -> impl Responder
+{
+    let config = data_config.lock().unwrap().clone();
+
+    // unlike jobs, unknown languages and problems are rejected
+    let language = match config.languages.iter().find(|language| language.name == body.language)
+    {
+        Some(language) => language.clone(),
+        None =>
+        {
+            return HttpResponse::NotFound().json(job::Error::
+                new(3, "ERR_NOT_FOUND".to_string(), format!("Language {} not found.", body.language).to_string()));
+        }
+    };
+
+    // uses the most permissive limits among the cases of the problem
+    let mut time_limit = config.runs.time_limit;
+    let mut memory_limit = config.runs.memory_limit;
+    if let Some(problem_id) = body.problem_id
+    {
+        match config.problems.iter().find(|problem| problem.id == problem_id)
+        {
+            Some(problem) =>
+            {
+                time_limit = problem.cases.iter().map(|case| case.time_limit).max().unwrap_or(time_limit);
+                memory_limit = problem.cases.iter().map(|case| case.memory_limit).max().unwrap_or(memory_limit);
+            }
+            None =>
+            {
+                return HttpResponse::NotFound().json(job::Error::
+                    new(3, "ERR_NOT_FOUND".to_string(), format!("Problem {} not found.", problem_id).to_string()));
+            }
+        }
+    }
+
+    // rate limits are checked last, so that runs rejected for other reasons do not use them up
+    let user_id = caller.principal.as_ref().map(|principal| principal.user_id);
+    let ip = req.peer_addr().map(|address| address.ip().to_string());
+    if let Err(retry_after) = rate_limit::take(&config.runs.rate_limits, "runs", user_id, ip.as_deref())
+    {
+        return HttpResponse::TooManyRequests().insert_header((header::RETRY_AFTER, retry_after.to_string()))
+            .json(rate_limit::error(retry_after));
+    }
+
+    // each run uses its own directory so that it does not interfere with judging
+    let dir: String;
+    {
+        let mut lock_run_id_count = RUN_ID_COUNT.lock().unwrap();
+        dir = format!("RUNDIR_{}", *lock_run_id_count);
+        *lock_run_id_count += 1;
+    }
+
+    // compiling and running blocks, so it happens on the blocking thread pool
+    let output_limit = config.runs.output_limit;
+    let body = body.into_inner();
+    match web::block(move || run_code(&body, &language, &dir, time_limit, memory_limit, output_limit)).await
+    {
+        Ok(response) => return HttpResponse::Ok().json(response),
+        Err(_) => return HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Code could not be run.".to_string())),
+    }
+}
\ No newline at end of file
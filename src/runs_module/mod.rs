@@ -0,0 +1,2 @@
+pub mod run;
+pub mod function_post_runs;
\ No newline at end of file
@@ -0,0 +1,33 @@
+use serde::{Serialize, Deserialize};
+
+// runs source code on custom input, without creating a job
+// the limits of the problem are used if problem_id is given
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PostRun
+{
+    pub source_code: String,
+    pub language: String,
+    pub problem_id: Option<u32>,
+    pub stdin: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RunResponse
+{
+    pub compiled: bool,
+    pub compiler_output: String,
+
+    pub stdout: String,
+    pub stdout_truncated: bool,
+    pub stderr: String,
+    pub stderr_truncated: bool,
+
+    pub time: i64, // in microseconds
+    pub memory: i64, // in kilobytes
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub time_limit_exceeded: bool,
+    // the address space of the program is capped at the memory limit, so a program needing more
+    // usually fails to allocate and ends with an exit code or a signal before its memory gets over the limit
+    pub memory_limit_exceeded: bool,
+}
\ No newline at end of file
@@ -0,0 +1,228 @@
+use std::sync::Mutex;
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+use crate::audit;
+use crate::auth;
+use crate::api_key;
+use crate::config;
+use crate::contest;
+use crate::job;
+use crate::team;
+use crate::user;
+use crate::storage;
+use crate::storage::{Counter, Storage, Tables};
+
+// non-persistent storage, everything is lost when the server stops
+pub struct MemoryStorage
+{
+    tables: Tables,
+    events: Mutex<Vec<audit::Event>>,
+    passwords: Mutex<HashMap<u32, String>>,
+    roles: Mutex<HashMap<u32, config::Role>>,
+    sessions: Mutex<HashMap<String, auth::Session>>,
+    api_keys: Mutex<Vec<api_key::ApiKey>>,
+    teams: Mutex<Vec<team::Team>>,
+    usage_resets: Mutex<HashMap<u32, DateTime<Utc>>>,
+}
+
+impl MemoryStorage
+{
+    pub fn new(config: &config::Config) -> MemoryStorage
+    {
+        MemoryStorage
+        {
+            tables: Tables::new(config),
+            events: Mutex::new(vec![]),
+            passwords: Mutex::new(HashMap::new()),
+            roles: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+            api_keys: Mutex::new(vec![]),
+            teams: Mutex::new(vec![]),
+            usage_resets: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+// the lists in memory are all there is, so nothing has to be saved
+// passwords, roles, sessions, API keys, teams and usage resets are not part of the lists, they are kept here
+impl Storage for MemoryStorage
+{
+    fn tables(&self) -> &Tables
+    {
+        &self.tables
+    }
+
+    fn save_user(&self, _user: &user::User) -> Result<(), storage::Error>
+    {
+        Ok(())
+    }
+
+    fn save_contest(&self, _contest: &contest::Contest) -> Result<(), storage::Error>
+    {
+        Ok(())
+    }
+
+    fn save_participant(&self, _contest: &contest::Contest, _user_id: u32) -> Result<(), storage::Error>
+    {
+        Ok(())
+    }
+
+    fn save_case_times(&self, _contest: &contest::Contest) -> Result<(), storage::Error>
+    {
+        Ok(())
+    }
+
+    fn save_job(&self, _job: &job::ResponseContent) -> Result<(), storage::Error>
+    {
+        Ok(())
+    }
+
+    fn save_counter(&self, _counter: Counter, _count: u32) -> Result<(), storage::Error>
+    {
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), storage::Error>
+    {
+        self.passwords.lock().unwrap().clear();
+        self.roles.lock().unwrap().clear();
+        self.sessions.lock().unwrap().clear();
+        self.api_keys.lock().unwrap().clear();
+        self.teams.lock().unwrap().clear();
+        self.usage_resets.lock().unwrap().clear();
+        Ok(())
+    }
+
+    fn append_event(&self, actor: &str, change: audit::Change) -> Result<(), storage::Error>
+    {
+        let mut events = self.events.lock().unwrap();
+        let id = events.len() as u64 + 1;
+        events.push(audit::Event { id, time: Utc::now(), actor: actor.to_string(), change });
+        Ok(())
+    }
+
+    fn query_events(&self, query: &audit::EventQuery) -> Result<Vec<audit::Event>, storage::Error>
+    {
+        let events = self.events.lock().unwrap();
+        let matching = events.iter().filter(|event| query.matches(event));
+        let limit = query.limit.map(|limit| limit as usize).unwrap_or(usize::MAX);
+        return Ok(matching.take(limit).cloned().collect());
+    }
+
+    fn save_password(&self, user_id: u32, password_hash: &str) -> Result<(), storage::Error>
+    {
+        self.passwords.lock().unwrap().insert(user_id, password_hash.to_string());
+        Ok(())
+    }
+
+    fn password_hash(&self, user_id: u32) -> Result<Option<String>, storage::Error>
+    {
+        Ok(self.passwords.lock().unwrap().get(&user_id).cloned())
+    }
+
+    fn save_role(&self, user_id: u32, role: config::Role) -> Result<(), storage::Error>
+    {
+        self.roles.lock().unwrap().insert(user_id, role);
+        Ok(())
+    }
+
+    fn role(&self, user_id: u32) -> Result<Option<config::Role>, storage::Error>
+    {
+        Ok(self.roles.lock().unwrap().get(&user_id).copied())
+    }
+
+    fn save_session(&self, session: &auth::Session) -> Result<(), storage::Error>
+    {
+        self.sessions.lock().unwrap().insert(session.token_hash.clone(), session.clone());
+        Ok(())
+    }
+
+    fn session(&self, token_hash: &str) -> Result<Option<auth::Session>, storage::Error>
+    {
+        Ok(self.sessions.lock().unwrap().get(token_hash).cloned())
+    }
+
+    fn save_api_key(&self, api_key: &api_key::ApiKey) -> Result<(), storage::Error>
+    {
+        let mut api_keys = self.api_keys.lock().unwrap();
+        match api_keys.iter_mut().find(|other| other.id == api_key.id)
+        {
+            Some(other) => *other = api_key.clone(),
+            None => api_keys.push(api_key.clone()),
+        }
+        Ok(())
+    }
+
+    fn touch_api_key(&self, id: u32, time: DateTime<Utc>) -> Result<(), storage::Error>
+    {
+        for api_key in self.api_keys.lock().unwrap().iter_mut()
+        {
+            if api_key.id == id {api_key.last_used_time = Some(time);}
+        }
+        Ok(())
+    }
+
+    fn api_keys(&self, user_id: Option<u32>) -> Result<Vec<api_key::ApiKey>, storage::Error>
+    {
+        let api_keys = self.api_keys.lock().unwrap();
+        return Ok(api_keys.iter().filter(|api_key| user_id.map_or(true, |user_id| api_key.user_id == user_id)).cloned().collect());
+    }
+
+    fn api_key(&self, key_hash: &str) -> Result<Option<api_key::ApiKey>, storage::Error>
+    {
+        Ok(self.api_keys.lock().unwrap().iter().find(|api_key| api_key.key_hash == key_hash).cloned())
+    }
+
+    fn save_team(&self, team: &team::Team) -> Result<(), storage::Error>
+    {
+        let mut teams = self.teams.lock().unwrap();
+        match teams.iter_mut().find(|other| other.id == team.id)
+        {
+            Some(other) => *other = team.clone(),
+            None => teams.push(team.clone()),
+        }
+        Ok(())
+    }
+
+    fn teams(&self) -> Result<Vec<team::Team>, storage::Error>
+    {
+        Ok(self.teams.lock().unwrap().clone())
+    }
+
+    fn save_usage_reset(&self, user_id: u32, time: DateTime<Utc>) -> Result<(), storage::Error>
+    {
+        self.usage_resets.lock().unwrap().insert(user_id, time);
+        Ok(())
+    }
+
+    fn usage_reset(&self, user_id: u32) -> Result<Option<DateTime<Utc>>, storage::Error>
+    {
+        Ok(self.usage_resets.lock().unwrap().get(&user_id).copied())
+    }
+
+    fn delete_credentials(&self, user_id: u32) -> Result<(), storage::Error>
+    {
+        self.passwords.lock().unwrap().remove(&user_id);
+        self.roles.lock().unwrap().remove(&user_id);
+        self.sessions.lock().unwrap().retain(|_, session| session.user_id != user_id);
+        self.api_keys.lock().unwrap().retain(|api_key| api_key.user_id != user_id);
+        self.usage_resets.lock().unwrap().remove(&user_id);
+        Ok(())
+    }
+
+    fn delete_user(&self, _user_id: u32) -> Result<(), storage::Error>
+    {
+        Ok(())
+    }
+
+    fn delete_job(&self, _job_id: u32) -> Result<(), storage::Error>
+    {
+        Ok(())
+    }
+
+    fn transaction(&self, action: &mut dyn FnMut() -> Result<(), storage::Error>) -> Result<(), storage::Error>
+    {
+        action()
+    }
+}
\ No newline at end of file
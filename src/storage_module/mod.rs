@@ -0,0 +1,3 @@
+pub mod storage;
+pub mod memory_storage;
+pub mod sqlite_storage;
\ No newline at end of file
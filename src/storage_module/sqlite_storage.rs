@@ -0,0 +1,411 @@
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use std::sync::Condvar;
+use std::time::Duration;
+use rusqlite::{Connection, OpenFlags, Result};
+use chrono::{DateTime, Utc};
+
+use crate::audit;
+use crate::auth;
+use crate::api_key;
+use crate::config;
+use crate::contest;
+use crate::job;
+use crate::team;
+use crate::user;
+use crate::sql;
+use crate::function_post_jobs;
+use crate::storage;
+use crate::storage::{Counter, JobQuery, Storage, Tables};
+
+// how long a connection waits for the database to be unlocked before failing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// persistent storage in a SQLite database
+// the lists are loaded once at startup, afterwards every change is written to the database as well
+// only the jobs that are not finished are kept in memory, finished jobs are read from the database when needed
+// the database is in WAL mode, so the read connections are never blocked by the single write connection
+pub struct SqliteStorage
+{
+    tables: Tables,
+    connection: Mutex<Connection>,
+
+    readers: Mutex<Vec<Connection>>,
+    reader_returned: Condvar,
+}
+
+impl SqliteStorage
+{
+    // opens the database at config.database.path and brings it to the latest schema
+    // the database is filled with the default values if it is empty, or cleared first if reset is set
+    pub fn open(config: &config::Config, reset: bool) -> Result<SqliteStorage>
+    {
+        let path = &config.database.path;
+        let connection = Connection::open(path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        sql::initialize(&connection)?;
+
+        if reset || sql::retrieve_user_list(&connection)?.is_empty()
+        {
+            let tx = connection.unchecked_transaction()?;
+            sql::clear(&tx)?;
+            insert_defaults(&tx, config)?;
+            sql::store_event(&tx, audit::ACTOR_ADMIN, &audit::Change::state_cleared)?;
+            tx.commit()?;
+        }
+
+        // read connections are opened once the schema is up to date
+        let mut readers: Vec<Connection> = vec![];
+        for _ in 0..config.database.readers.max(1)
+        {
+            let reader = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+            reader.busy_timeout(BUSY_TIMEOUT)?;
+            readers.push(reader);
+        }
+
+        // the lists are empty until they are loaded through a read connection
+        let mut storage = SqliteStorage
+        {
+            tables: Tables::from(vec![], 0, vec![], 0, vec![], 0),
+            connection: Mutex::new(connection),
+            readers: Mutex::new(readers),
+            reader_returned: Condvar::new(),
+        };
+        storage.tables = storage.read(|conn| {
+            Ok(Tables::from(
+                sql::retrieve_user_list(conn)?,
+                sql::retrieve_user_id_count(conn)?,
+                sql::retrieve_contests(conn)?,
+                sql::retrieve_contest_id_count(conn)?,
+                sql::retrieve_unfinished_jobs(conn)?,
+                sql::retrieve_job_id_count(conn)?,
+            ))
+        })?;
+        storage.load_case_times(config)?;
+        Ok(storage)
+    }
+
+    // fills in the best case times of each contest
+    // the database does not know the cases of each problem, so the times are sized by the config
+    // contests without stored times, e.g. from databases older than the table, get them from their jobs
+    fn load_case_times(&self, config: &config::Config) -> Result<()>
+    {
+        let mut contests = self.contests();
+        for contest in contests.iter_mut()
+        {
+            contest.case_times = contest::empty_case_times(&config.problems);
+
+            let stored = self.read(|conn| sql::retrieve_case_times(conn, contest.id))?;
+            if stored.is_empty()
+            {
+                let query = JobQuery { contest_id: Some(contest.id), ..Default::default() };
+                let jobs = self.read(|conn| sql::query_jobs(conn, &query))?;
+                function_post_jobs::rebuild_case_times(contest, &jobs, &config.problems);
+                sql::store_case_times(&self.connection(), contest)?;
+                continue;
+            }
+
+            // problems or cases removed from the config are ignored
+            for (problem_index, case_index, time) in stored
+            {
+                if let Some(case_time) = contest.case_times.get_mut(problem_index).and_then(|times| times.get_mut(case_index))
+                {
+                    *case_time = time;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // runs action on one of the read connections, waiting for one to be free if all of them are in use
+    // the connection cannot write, and sees the database as of the last finished transaction
+    pub fn read<T>(&self, action: impl FnOnce(&Connection) -> Result<T>) -> Result<T>
+    {
+        let reader = {
+            let mut readers = self.readers.lock().unwrap();
+            loop
+            {
+                if let Some(reader) = readers.pop() {break reader;}
+                readers = self.reader_returned.wait(readers).unwrap();
+            }
+        };
+
+        let output = action(&reader);
+
+        self.readers.lock().unwrap().push(reader);
+        self.reader_returned.notify_one();
+        return output;
+    }
+
+    // the write connection, which stays usable if a request panicked while holding it
+    fn connection(&self) -> MutexGuard<'_, Connection>
+    {
+        self.connection.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl From<rusqlite::Error> for storage::Error
+{
+    fn from(err: rusqlite::Error) -> storage::Error
+    {
+        storage::Error(err.to_string())
+    }
+}
+
+// stores the root user, contest 0 and the first ids
+fn insert_defaults(conn: &Connection, config: &config::Config) -> Result<()>
+{
+    sql::store_user(conn, &storage::root_user())?;
+    sql::store_user_id_count(conn, 1)?;
+
+    sql::store_contest(conn, &storage::root_contest(config))?;
+    sql::store_contest_id_count(conn, 1)?;
+
+    sql::store_job_id_count(conn, 0)?;
+    Ok(())
+}
+
+// rolls back the transaction it was created for, unless it is finished
+struct OpenTransaction<'a>
+{
+    connection: &'a Mutex<Connection>,
+    finished: bool,
+}
+
+impl Drop for OpenTransaction<'_>
+{
+    fn drop(&mut self)
+    {
+        if !self.finished
+        {
+            let connection = self.connection.lock().unwrap_or_else(PoisonError::into_inner);
+            connection.execute_batch("ROLLBACK").ok();
+        }
+    }
+}
+
+impl Storage for SqliteStorage
+{
+    fn tables(&self) -> &Tables
+    {
+        &self.tables
+    }
+
+    fn save_user(&self, user: &user::User) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_user(&connection, user)?;
+        Ok(())
+    }
+
+    fn save_contest(&self, contest: &contest::Contest) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_contest(&connection, contest)?;
+        Ok(())
+    }
+
+    fn save_participant(&self, contest: &contest::Contest, user_id: u32) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_participant(&connection, contest, user_id)?;
+        Ok(())
+    }
+
+    fn save_case_times(&self, contest: &contest::Contest) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_case_times(&connection, contest)?;
+        Ok(())
+    }
+
+    fn save_job(&self, job: &job::ResponseContent) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_job(&connection, job)?;
+        Ok(())
+    }
+
+    // finished jobs are left out of the list, since they are read back from the database
+    fn keep_job(&self, content: &job::ResponseContent)
+    {
+        let mut lock_job_list = self.jobs();
+        lock_job_list.retain(|other| other.id != content.id);
+        if content.state != "Finished"
+        {
+            let index = lock_job_list.partition_point(|other| other.id < content.id);
+            lock_job_list.insert(index, content.clone());
+        }
+    }
+
+    fn job(&self, job_id: u32) -> Result<Option<job::ResponseContent>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::retrieve_job(conn, job_id))?)
+    }
+
+    // filtered by SQL on a read connection, so that only the jobs listed are read
+    fn query_jobs(&self, query: &JobQuery) -> Result<Vec<job::ResponseContent>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::query_jobs(conn, query))?)
+    }
+
+    fn save_counter(&self, counter: Counter, count: u32) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        match counter
+        {
+            Counter::User => sql::store_user_id_count(&connection, count),
+            Counter::Contest => sql::store_contest_id_count(&connection, count),
+            Counter::Job => sql::store_job_id_count(&connection, count),
+        }?;
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::clear(&connection)?;
+        Ok(())
+    }
+
+    fn append_event(&self, actor: &str, change: audit::Change) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_event(&connection, actor, &change)?;
+        Ok(())
+    }
+
+    fn query_events(&self, query: &audit::EventQuery) -> Result<Vec<audit::Event>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::query_events(conn, query))?)
+    }
+
+    fn save_password(&self, user_id: u32, password_hash: &str) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_password(&connection, user_id, password_hash)?;
+        Ok(())
+    }
+
+    fn password_hash(&self, user_id: u32) -> Result<Option<String>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::retrieve_password(conn, user_id))?)
+    }
+
+    fn save_role(&self, user_id: u32, role: config::Role) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_role(&connection, user_id, role)?;
+        Ok(())
+    }
+
+    fn role(&self, user_id: u32) -> Result<Option<config::Role>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::retrieve_role(conn, user_id))?)
+    }
+
+    fn save_session(&self, session: &auth::Session) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_session(&connection, session)?;
+        Ok(())
+    }
+
+    fn session(&self, token_hash: &str) -> Result<Option<auth::Session>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::retrieve_session(conn, token_hash))?)
+    }
+
+    fn save_api_key(&self, api_key: &api_key::ApiKey) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_api_key(&connection, api_key)?;
+        Ok(())
+    }
+
+    fn touch_api_key(&self, id: u32, time: DateTime<Utc>) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_api_key_use(&connection, id, time)?;
+        Ok(())
+    }
+
+    fn api_keys(&self, user_id: Option<u32>) -> Result<Vec<api_key::ApiKey>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::retrieve_api_keys(conn, user_id))?)
+    }
+
+    fn api_key(&self, key_hash: &str) -> Result<Option<api_key::ApiKey>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::retrieve_api_key(conn, key_hash))?)
+    }
+
+    fn save_team(&self, team: &team::Team) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_team(&connection, team)?;
+        Ok(())
+    }
+
+    fn teams(&self) -> Result<Vec<team::Team>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::retrieve_teams(conn))?)
+    }
+
+    fn save_usage_reset(&self, user_id: u32, time: DateTime<Utc>) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::store_usage_reset(&connection, user_id, time)?;
+        Ok(())
+    }
+
+    fn usage_reset(&self, user_id: u32) -> Result<Option<DateTime<Utc>>, storage::Error>
+    {
+        Ok(self.read(|conn| sql::retrieve_usage_reset(conn, user_id))?)
+    }
+
+    fn delete_credentials(&self, user_id: u32) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::delete_credentials(&connection, user_id)?;
+        Ok(())
+    }
+
+    fn delete_user(&self, user_id: u32) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::delete_user(&connection, user_id)?;
+        Ok(())
+    }
+
+    fn delete_job(&self, job_id: u32) -> Result<(), storage::Error>
+    {
+        let connection = self.connection();
+        sql::delete_job(&connection, job_id)?;
+        Ok(())
+    }
+
+    // the connection is not held while action runs, since action saves rows through it
+    // requests holding Storage::lock are the only ones writing, so nothing else joins the transaction
+    fn transaction(&self, action: &mut dyn FnMut() -> Result<(), storage::Error>) -> Result<(), storage::Error>
+    {
+        {
+            let connection = self.connection();
+            if !connection.is_autocommit()
+            {
+                drop(connection);
+                return action();
+            }
+            connection.execute_batch("BEGIN")?;
+        }
+
+        // rolled back when dropped, if action or the commit fails
+        let mut open = OpenTransaction { connection: &self.connection, finished: false };
+        action()?;
+        self.connection().execute_batch("COMMIT")?;
+        open.finished = true;
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,349 @@
+use std::i64::MAX;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use actix_web::http::StatusCode;
+use chrono::{DateTime, Utc};
+
+use crate::audit;
+use crate::auth;
+use crate::api_key;
+use crate::config;
+use crate::contest;
+use crate::job;
+use crate::team;
+use crate::user;
+
+// the counters used to give ids to new users, contests and jobs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Counter
+{
+    User,
+    Contest,
+    Job,
+}
+
+// which jobs GET /jobs lists, and in which order
+// jobs are ordered by id, after_id is the last id of the previous page
+// the default query lists every job in ascending order, without the source code
+#[derive(Debug, Clone, Default)]
+pub struct JobQuery
+{
+    pub user_id: Option<u32>,
+    pub user_name: Option<String>,
+    pub contest_id: Option<u32>,
+    pub problem_id: Option<u32>,
+    pub language: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub state: Option<String>,
+    pub result: Option<job::PossibleResult>,
+
+    pub descending: bool,
+    pub after_id: Option<u32>,
+    pub limit: Option<u32>,
+    // the source code may be left empty when it is not shown
+    pub with_source: bool,
+}
+
+impl JobQuery
+{
+    // checks the filters of the query, name_id is the id of user_name
+    pub fn matches(&self, job: &job::ResponseContent, name_id: Option<u32>) -> bool
+    {
+        if self.user_id.is_some() && self.user_id != Some(job.submission.user_id) {return false;}
+        if self.user_name.is_some() && name_id != Some(job.submission.user_id) {return false;}
+        if self.contest_id.is_some() && self.contest_id != Some(job.submission.contest_id) {return false;}
+        if self.problem_id.is_some() && self.problem_id != Some(job.submission.problem_id) {return false;}
+        if let Some(language) = &self.language
+        {
+            if job.submission.language != *language {return false;}
+        }
+        if let Some(from) = self.from
+        {
+            if job.created_time < from {return false;}
+        }
+        if let Some(to) = self.to
+        {
+            if job.created_time > to {return false;}
+        }
+        if let Some(state) = &self.state
+        {
+            if job.state != *state {return false;}
+        }
+        if let Some(result) = &self.result
+        {
+            if job.result != *result {return false;}
+        }
+        if let Some(after_id) = self.after_id
+        {
+            if self.descending && job.id >= after_id {return false;}
+            if !self.descending && job.id <= after_id {return false;}
+        }
+        return true;
+    }
+}
+
+// a change that could not be saved, e.g. because the database stayed locked or a constraint failed
+#[derive(Debug, Clone)]
+pub struct Error(pub String);
+
+impl std::fmt::Display for Error
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "{}", self.0)
+    }
+}
+
+// the response to a request whose changes could not be saved
+pub fn internal(error: Error) -> (StatusCode, job::Error)
+{
+    log::error!("Storage failed: {}", error);
+    (StatusCode::INTERNAL_SERVER_ERROR, job::Error::new(6, "ERR_INTERNAL".to_string(), "The change could not be saved.".to_string()))
+}
+
+// the response to a request that could not read what it needed from the storage
+pub fn unreadable(error: Error) -> (StatusCode, job::Error)
+{
+    log::error!("Storage failed: {}", error);
+    (StatusCode::INTERNAL_SERVER_ERROR, job::Error::new(6, "ERR_INTERNAL".to_string(), "The storage could not be read.".to_string()))
+}
+
+// users, contests and jobs kept in memory while the server is running
+// every list has its own lock, requests that change the storage take Storage::lock first
+// a request that panicked while holding a lock does not make it unusable for the requests after it
+// requests that only read lock the lists they need, one at a time, so they never wait for judging
+pub struct Tables
+{
+    pub users: Mutex<Vec<user::User>>,
+    pub user_id_count: Mutex<u32>,
+
+    pub contests: Mutex<Vec<contest::Contest>>,
+    pub contest_id_count: Mutex<u32>,
+
+    // the jobs that are not finished, storages that cannot read their jobs back keep the finished ones too, see Storage::keep_job
+    pub jobs: Mutex<Vec<job::ResponseContent>>,
+    pub job_id_count: Mutex<u32>,
+
+    requests: Mutex<()>,
+}
+
+impl Tables
+{
+    // the tables of a new server, which only contain the root user and contest 0
+    pub fn new(config: &config::Config) -> Tables
+    {
+        // user 0 is root and contest 0 has special use
+        return Tables::from(vec![root_user()], 1, vec![root_contest(config)], 1, vec![], 0);
+    }
+
+    pub fn from(users: Vec<user::User>, user_id_count: u32, contests: Vec<contest::Contest>, contest_id_count: u32,
+        jobs: Vec<job::ResponseContent>, job_id_count: u32) -> Tables
+    {
+        Tables
+        {
+            users: Mutex::new(users),
+            user_id_count: Mutex::new(user_id_count),
+            contests: Mutex::new(contests),
+            contest_id_count: Mutex::new(contest_id_count),
+            jobs: Mutex::new(jobs),
+            job_id_count: Mutex::new(job_id_count),
+            requests: Mutex::new(()),
+        }
+    }
+}
+
+// the root user is added by default
+pub fn root_user() -> user::User
+{
+    user::User::new(0, "root".to_string())
+}
+
+// contest 0 is added by default
+// it contains every problem of the config file and every user
+pub fn root_contest(config: &config::Config) -> contest::Contest
+{
+    let mut problem_ids: Vec<u32> = vec![];
+    for i in 0..config.problems.len()
+    {
+        problem_ids.push(i as u32);
+    }
+
+    // inserts the root user (id = 0) into the list of users
+    // other users are inserted as they are posted
+    let users = vec![
+        contest::RankInfo
+        {
+            user: root_user(),
+            scores: vec![0.0; config.problems.len()],
+            rank: 0,
+
+            highest_scores: vec![0.0 ; config.problems.len()],
+            latest_scores: vec![0.0 ; config.problems.len()],
+
+            competitive_score_sum: 0.0,
+
+            // assuming each problem has a maximum of 20 test cases
+            // can be changed if needed
+            shortest_times: vec![vec![MAX; 20]; config.problems.len()],
+
+            latest_submission: DateTime::<Utc>::MAX_UTC,
+            score: 0,
+            submission_count: 0,
+        }
+    ];
+
+    contest::Contest {
+        id: 0,
+        name: "root".to_string(),
+        from: DateTime::<Utc>::MAX_UTC,
+        to: DateTime::<Utc>::MIN_UTC,
+        problem_ids,
+        user_ids: vec![0],
+        team_ids: vec![],
+        submission_limit: 0,
+
+        users,
+        case_times: contest::empty_case_times(&config.problems),
+    }
+}
+
+// where users, contests, jobs and the id counters are kept
+// handlers save the rows they change, and change the lists in memory once they are saved
+// the implementation is chosen at startup, see MemoryStorage and SqliteStorage
+pub trait Storage: Send + Sync
+{
+    fn tables(&self) -> &Tables;
+
+    fn save_user(&self, user: &user::User) -> Result<(), Error>;
+    fn save_contest(&self, contest: &contest::Contest) -> Result<(), Error>;
+    // saves the progress of a single user in the contest
+    fn save_participant(&self, contest: &contest::Contest, user_id: u32) -> Result<(), Error>;
+    // saves the best case times of the contest
+    fn save_case_times(&self, contest: &contest::Contest) -> Result<(), Error>;
+    fn save_job(&self, job: &job::ResponseContent) -> Result<(), Error>;
+    fn save_counter(&self, counter: Counter, count: u32) -> Result<(), Error>;
+    // removes everything saved, before the lists are replaced as a whole
+    // passwords, roles, sessions, API keys, teams and usage resets are removed too, the audit log is kept
+    fn clear(&self) -> Result<(), Error>;
+
+    // appends a change to the audit log, in the transaction of the change if there is one
+    fn append_event(&self, actor: &str, change: audit::Change) -> Result<(), Error>;
+    // the entries of the audit log matching the query
+    fn query_events(&self, query: &audit::EventQuery) -> Result<Vec<audit::Event>, Error>;
+
+    // the argon2 hash of the password of a user, users created by POST /users have none
+    fn save_password(&self, user_id: u32, password_hash: &str) -> Result<(), Error>;
+    fn password_hash(&self, user_id: u32) -> Result<Option<String>, Error>;
+    // users without a saved role have the default one, see permission::role_of
+    fn save_role(&self, user_id: u32, role: config::Role) -> Result<(), Error>;
+    fn role(&self, user_id: u32) -> Result<Option<config::Role>, Error>;
+    // sessions are saved again when they are revoked
+    fn save_session(&self, session: &auth::Session) -> Result<(), Error>;
+    fn session(&self, token_hash: &str) -> Result<Option<auth::Session>, Error>;
+
+    // API keys are saved again when they are revoked, keys are only removed with their user
+    fn save_api_key(&self, api_key: &api_key::ApiKey) -> Result<(), Error>;
+    // records when a key was last used, the caller holds Storage::lock like for other changes
+    fn touch_api_key(&self, id: u32, time: DateTime<Utc>) -> Result<(), Error>;
+    // the keys of a single user if user_id is given, ordered by id
+    fn api_keys(&self, user_id: Option<u32>) -> Result<Vec<api_key::ApiKey>, Error>;
+    fn api_key(&self, key_hash: &str) -> Result<Option<api_key::ApiKey>, Error>;
+
+    // teams are saved again when they change, ids are the positions in the list
+    fn save_team(&self, team: &team::Team) -> Result<(), Error>;
+    fn teams(&self) -> Result<Vec<team::Team>, Error>;
+
+    // when the usage of a user was last reset, jobs submitted before do not count for its quotas, see quota
+    fn save_usage_reset(&self, user_id: u32, time: DateTime<Utc>) -> Result<(), Error>;
+    fn usage_reset(&self, user_id: u32) -> Result<Option<DateTime<Utc>>, Error>;
+
+    // removes the password, the role, the sessions, the API keys and the usage reset of a user, see deletion
+    fn delete_credentials(&self, user_id: u32) -> Result<(), Error>;
+    // removes a user or a job that was removed from its list
+    // rows referencing the user are removed or saved without it first, see deletion::cascade
+    fn delete_user(&self, user_id: u32) -> Result<(), Error>;
+    fn delete_job(&self, job_id: u32) -> Result<(), Error>;
+
+    // the changes saved by action are kept together, and none of them if it fails or panics
+    // nested calls join the transaction that is already open
+    // the lists in memory are not part of it, callers change them once it returned Ok, or put them back if it failed
+    fn transaction(&self, action: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error>;
+
+    // held by requests that change the storage, so that writers do not interleave
+    fn lock(&self) -> MutexGuard<'_, ()>
+    {
+        self.tables().requests.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn users(&self) -> MutexGuard<'_, Vec<user::User>>
+    {
+        self.tables().users.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn contests(&self) -> MutexGuard<'_, Vec<contest::Contest>>
+    {
+        self.tables().contests.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, Vec<job::ResponseContent>>
+    {
+        self.tables().jobs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // puts a job into the job list once it is saved, in place of the job with the same id
+    // storages that can read their jobs back only keep the jobs that are not finished, and leave the others out
+    fn keep_job(&self, content: &job::ResponseContent)
+    {
+        let mut lock_job_list = self.jobs();
+        match lock_job_list.iter().position(|other| other.id == content.id)
+        {
+            Some(index) => lock_job_list[index] = content.clone(),
+            None => lock_job_list.push(content.clone()),
+        }
+    }
+
+    // the job with the given id, finished or not
+    fn job(&self, job_id: u32) -> Result<Option<job::ResponseContent>, Error>
+    {
+        Ok(self.jobs().iter().find(|content| content.id == job_id).cloned())
+    }
+
+    // the jobs matching the query, e.g. those listed by GET /jobs
+    // filters the list in memory, storages that can search their jobs themselves do so instead
+    fn query_jobs(&self, query: &JobQuery) -> Result<Vec<job::ResponseContent>, Error>
+    {
+        // the user is looked up before the job list is locked, names differing only in case are the same name
+        let name_id = match &query.user_name
+        {
+            Some(user_name) =>
+            {
+                let user_name = user_name.to_lowercase();
+                self.users().iter().find(|user| user.name.to_lowercase() == user_name).map(|user| user.id)
+            }
+            None => None,
+        };
+
+        let lock_job_list = self.jobs();
+        let mut jobs: Vec<&job::ResponseContent> = lock_job_list.iter().filter(|job| query.matches(job, name_id)).collect();
+        jobs.sort_by_key(|job| job.id);
+        if query.descending
+        {
+            jobs.reverse();
+        }
+        let limit = query.limit.map(|limit| limit as usize).unwrap_or(jobs.len());
+        return Ok(jobs.into_iter().take(limit).cloned().collect());
+    }
+
+    fn counter(&self, counter: Counter) -> MutexGuard<'_, u32>
+    {
+        let tables = self.tables();
+        match counter
+        {
+            Counter::User => tables.user_id_count.lock().unwrap_or_else(PoisonError::into_inner),
+            Counter::Contest => tables.contest_id_count.lock().unwrap_or_else(PoisonError::into_inner),
+            Counter::Job => tables.job_id_count.lock().unwrap_or_else(PoisonError::into_inner),
+        }
+    }
+}
\ No newline at end of file
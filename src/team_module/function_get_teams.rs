@@ -0,0 +1,38 @@
+use actix_web::{get, Responder, HttpResponse, web};
+
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+// gets list of all teams
+#[get("/teams", wrap = "permission::Require(permission::Permission::view)")]
+async fn get_teams(data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    match web::block(move || data_storage.teams().map_err(storage::unreadable)).await
+    {
+        Ok(Ok(teams)) => HttpResponse::Ok().json(teams),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Teams could not be listed.".to_string())),
+    }
+}
+
+// gets a team given its id
+#[get("/teams/{teamId}", wrap = "permission::Require(permission::Permission::view)")]
+#[allow(non_snake_case)]
+async fn get_teams_teamId(teamId: web::Path<u32>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let team_id = teamId.into_inner();
+    match web::block(move || data_storage.teams().map_err(storage::unreadable)).await
+    {
+        Ok(Ok(teams)) => match teams.into_iter().find(|team| team.id == team_id)
+        {
+            Some(team) => HttpResponse::Ok().json(team),
+            None => HttpResponse::NotFound().json(job::Error::
+                new(3, "ERR_NOT_FOUND".to_string(), format!("Team {} not found.", team_id).to_string())),
+        },
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Team could not be found.".to_string())),
+    }
+}
\ No newline at end of file
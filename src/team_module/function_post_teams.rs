@@ -0,0 +1,91 @@
+use actix_web::{post, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
+
+use crate::team;
+use crate::contest;
+use crate::audit;
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+// adds a team, or changes the team with the id of the body
+// contests registering the team follow its members, see contest::sync_team_members
+fn save_team(body: team::PostTeam, caller: &permission::Caller, storage: &dyn storage::Storage) -> Result<team::Team, (StatusCode, job::Error)>
+{
+    let _lock = storage.lock();
+
+    team::check(&body, &storage.users())?;
+    let teams = storage.teams().map_err(storage::unreadable)?;
+    if let Some(id) = body.id
+    {
+        if !teams.iter().any(|team| team.id == id)
+        {
+            return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("Team {} not found.", id).to_string())));
+        }
+    }
+    if team::name_taken(&teams, &body.name, body.id)
+    {
+        return Err((StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(),
+            format!("Team name '{}' already exists.", body.name).to_string())));
+    }
+
+    let team = team::Team
+    {
+        id: body.id.unwrap_or(teams.len() as u32),
+        name: body.name,
+        member_ids: body.member_ids,
+        coach_id: body.coach_id,
+    };
+    let mut all_teams: Vec<team::Team> = teams.into_iter().filter(|other| other.id != team.id).collect();
+    all_teams.push(team.clone());
+
+    // users are locked before contests, like everywhere else
+    let lock_user_list = storage.users();
+    let mut lock_contest_list = storage.contests();
+    let mut contests: Vec<contest::Contest> = vec![];
+    for registered in lock_contest_list.iter().filter(|contest| contest.team_ids.contains(&team.id))
+    {
+        let mut registered = registered.clone();
+        contest::sync_team_members(&mut registered, &all_teams, &lock_user_list)
+            .map_err(|message| (StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message)))?;
+        contests.push(registered);
+    }
+
+    let actor = caller.actor();
+    let change = match body.id
+    {
+        Some(_) => audit::Change::team_updated { team: team.clone() },
+        None => audit::Change::team_created { team: team.clone() },
+    };
+    storage.transaction(&mut || {
+        storage.save_team(&team)?;
+        storage.append_event(&actor, change.clone())?;
+        for contest in contests.iter()
+        {
+            storage.save_contest(contest)?;
+            storage.append_event(&actor, audit::Change::contest_updated { contest: contest.clone() })?;
+        }
+        Ok(())
+    }).map_err(storage::internal)?;
+    for contest in contests.into_iter()
+    {
+        let index = lock_contest_list.iter().position(|existing| existing.id == contest.id).unwrap();
+        lock_contest_list[index] = contest;
+    }
+    return Ok(team);
+}
+
+// posts a new team, or updates one
+#[post("/teams", wrap = "permission::Require(permission::Permission::manage_contests)")]
+async fn post_teams(body: web::Json<team::PostTeam>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let caller = caller.into_inner();
+    match web::block(move || save_team(body.into_inner(), &caller, data_storage.get_ref())).await
+    {
+        Ok(Ok(team)) => HttpResponse::Ok().json(team),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Team could not be saved.".to_string())),
+    }
+}
\ No newline at end of file
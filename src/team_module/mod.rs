@@ -0,0 +1,3 @@
+pub mod team;
+pub mod function_post_teams;
+pub mod function_get_teams;
\ No newline at end of file
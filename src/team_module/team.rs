@@ -0,0 +1,106 @@
+use actix_web::http::StatusCode;
+use serde::{Serialize, Deserialize};
+
+use crate::job;
+use crate::user;
+
+// longest name a team may have
+pub const MAX_NAME_LENGTH: usize = 64;
+
+// users taking part in team contests together, e.g. in ICPC-style contests
+// the coach follows the team but does not submit for it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Team
+{
+    pub id: u32,
+    pub name: String,
+    pub member_ids: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coach_id: Option<u32>,
+}
+
+// body of POST /teams, the team with the id is updated if one is given
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostTeam
+{
+    pub id: Option<u32>,
+    pub name: String,
+    pub member_ids: Vec<u32>,
+    #[serde(default)]
+    pub coach_id: Option<u32>,
+}
+
+// a team as shown in ranklists, with its members and its coach
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Roster
+{
+    pub id: u32,
+    pub name: String,
+    pub members: Vec<user::User>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coach: Option<user::User>,
+}
+
+impl Roster
+{
+    // users that do not exist anymore are left out, users are shown without their profiles like in ranklists
+    pub fn new(team: &Team, users: &[user::User]) -> Roster
+    {
+        let find = |user_id: u32| users.iter().find(|user| user.id == user_id).map(|user| user.summary());
+        Roster
+        {
+            id: team.id,
+            name: team.name.clone(),
+            members: team.member_ids.iter().filter_map(|user_id| find(*user_id)).collect(),
+            coach: team.coach_id.and_then(find),
+        }
+    }
+}
+
+fn invalid_argument(message: String) -> (StatusCode, job::Error)
+{
+    (StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message))
+}
+
+// checks the name, the members and the coach of a team, users is the user list
+pub fn check(body: &PostTeam, users: &[user::User]) -> Result<(), (StatusCode, job::Error)>
+{
+    let length = body.name.trim().chars().count();
+    if length == 0 || body.name.chars().count() > MAX_NAME_LENGTH
+    {
+        return Err(invalid_argument(format!("Team name must have 1 to {} characters.", MAX_NAME_LENGTH)));
+    }
+    if body.member_ids.is_empty()
+    {
+        return Err(invalid_argument("Team must have at least one member.".to_string()));
+    }
+    for (index, user_id) in body.member_ids.iter().enumerate()
+    {
+        if body.member_ids[..index].contains(user_id)
+        {
+            return Err(invalid_argument(format!("User {} is repeated.", user_id)));
+        }
+    }
+    if let Some(coach_id) = body.coach_id
+    {
+        if body.member_ids.contains(&coach_id)
+        {
+            return Err(invalid_argument(format!("User {} cannot be both a member and the coach.", coach_id)));
+        }
+    }
+    for user_id in body.member_ids.iter().chain(body.coach_id.iter())
+    {
+        if !users.iter().any(|user| user.id == *user_id)
+        {
+            return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", user_id))));
+        }
+    }
+    Ok(())
+}
+
+// whether another team has the name, names differing only in case are the same name
+pub fn name_taken(teams: &[Team], name: &str, except: Option<u32>) -> bool
+{
+    let name = name.to_lowercase();
+    return teams.iter().any(|team| Some(team.id) != except && team.name.to_lowercase() == name);
+}
\ No newline at end of file
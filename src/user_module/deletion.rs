@@ -0,0 +1,102 @@
+use serde::{Serialize, Deserialize};
+
+use crate::user;
+use crate::team;
+use crate::job;
+use crate::config;
+use crate::contest;
+use crate::function_post_jobs;
+
+// what happens to the jobs of a deleted user, given with DELETE /users/{id}?jobs=
+// anonymize keeps the jobs, so that they still count for best case times and problem statistics,
+// and keeps the user they belong to as a deactivated placeholder without name, profile or credentials
+// delete removes the jobs and the user
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[allow(non_camel_case_types)]
+pub enum JobPolicy
+{
+    #[default]
+    anonymize,
+    delete,
+}
+
+// the rows a deletion changed, so that they can be saved
+#[derive(Debug, Clone, Default)]
+pub struct Cascade
+{
+    pub contest_ids: Vec<u32>,
+    pub teams: Vec<team::Team>,
+    pub job_ids: Vec<u32>, // jobs of the user, which are removed unless they are anonymized
+    pub placeholder: Option<user::User>, // the user left by anonymize
+}
+
+// the user left in place of a deleted user whose jobs are kept
+// its name has a '#', which user::check_name does not allow, so that no user can take it
+pub fn placeholder(user_id: u32) -> user::User
+{
+    user::User { id: user_id, name: format!("deleted#{}", user_id), profile: user::Profile::default(), deactivated: true }
+}
+
+// removes a user from the lists, with its place in contests and teams
+// placeholders stay in contest 0 like every user, deleted users leave it too
+// contests losing jobs have their best case times computed again from the remaining ones
+// jobs holds the jobs of the user, and with delete every job of the contests it submitted to
+pub fn cascade(user_id: u32, policy: JobPolicy, users: &mut Vec<user::User>, contests: &mut Vec<contest::Contest>, teams: &mut Vec<team::Team>,
+    jobs: &mut Vec<job::ResponseContent>, problems: &Vec<config::Problem>) -> Cascade
+{
+    let mut cascade = Cascade::default();
+    let user_jobs: Vec<&job::ResponseContent> = jobs.iter().filter(|content| content.submission.user_id == user_id).collect();
+    cascade.job_ids = user_jobs.iter().map(|content| content.id).collect();
+    let mut job_contest_ids: Vec<u32> = user_jobs.iter().map(|content| content.submission.contest_id).collect();
+    job_contest_ids.dedup();
+
+    match policy
+    {
+        JobPolicy::anonymize =>
+        {
+            let user = placeholder(user_id);
+            if let Some(existing) = users.iter_mut().find(|existing| existing.id == user_id) {*existing = user.clone();}
+            cascade.placeholder = Some(user);
+        }
+        JobPolicy::delete =>
+        {
+            users.retain(|user| user.id != user_id);
+            jobs.retain(|content| content.submission.user_id != user_id);
+        }
+    }
+
+    for contest in contests.iter_mut()
+    {
+        let mut changed = false;
+        if contest.id == 0 && policy == JobPolicy::anonymize
+        {
+            for rank_info in contest.users.iter_mut().filter(|rank_info| rank_info.user.id == user_id)
+            {
+                rank_info.user = placeholder(user_id).summary();
+                changed = true;
+            }
+        }
+        else if contest.user_ids.contains(&user_id) || contest.users.iter().any(|rank_info| rank_info.user.id == user_id)
+        {
+            contest.user_ids.retain(|id| *id != user_id);
+            contest.users.retain(|rank_info| rank_info.user.id != user_id);
+            changed = true;
+        }
+        if policy == JobPolicy::delete && job_contest_ids.contains(&contest.id)
+        {
+            contest.case_times = contest::empty_case_times(problems);
+            function_post_jobs::rebuild_case_times(contest, jobs, problems);
+            changed = true;
+        }
+        if changed {cascade.contest_ids.push(contest.id);}
+    }
+
+    for team in teams.iter_mut()
+    {
+        if !team.member_ids.contains(&user_id) && team.coach_id != Some(user_id) {continue;}
+        team.member_ids.retain(|id| *id != user_id);
+        if team.coach_id == Some(user_id) {team.coach_id = None;}
+        cascade.teams.push(team.clone());
+    }
+    return cascade;
+}
\ No newline at end of file
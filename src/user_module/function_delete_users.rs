@@ -0,0 +1,125 @@
+use actix_web::{delete, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
+use serde::{Serialize, Deserialize};
+use std::sync::Mutex;
+use std::sync::Arc;
+
+use crate::deletion;
+use crate::audit;
+use crate::job;
+use crate::config;
+use crate::storage;
+use crate::permission;
+
+// query of DELETE /users/{id}
+#[derive(Deserialize, Debug)]
+pub struct DeleteQuery
+{
+    #[serde(default)]
+    jobs: deletion::JobPolicy,
+}
+
+// response of DELETE /users/{id}, job_count is the number of jobs deleted or anonymized
+#[derive(Serialize, Debug, Clone)]
+pub struct Deletion
+{
+    pub user_id: u32,
+    pub jobs: deletion::JobPolicy,
+    pub job_count: u32,
+}
+
+// the jobs deletion::cascade needs, those of the user and, when they are deleted, the others of their contests
+fn cascade_jobs(user_id: u32, policy: deletion::JobPolicy, storage: &dyn storage::Storage) -> Result<Vec<job::ResponseContent>, storage::Error>
+{
+    let user_jobs = storage.query_jobs(&storage::JobQuery { user_id: Some(user_id), ..Default::default() })?;
+    if policy == deletion::JobPolicy::anonymize {return Ok(user_jobs);}
+
+    let mut contest_ids: Vec<u32> = user_jobs.iter().map(|content| content.submission.contest_id).collect();
+    contest_ids.sort();
+    contest_ids.dedup();
+    let mut jobs = vec![];
+    for contest_id in contest_ids
+    {
+        jobs.extend(storage.query_jobs(&storage::JobQuery { contest_id: Some(contest_id), ..Default::default() })?);
+    }
+    return Ok(jobs);
+}
+
+// deletes a user and removes it from contests and teams, see deletion::cascade
+// users with jobs that are still being judged cannot be deleted, since judging would save them again
+fn delete_user(user_id: u32, policy: deletion::JobPolicy, caller: &permission::Caller, config: &config::Config, storage: &dyn storage::Storage)
+    -> Result<Deletion, (StatusCode, job::Error)>
+{
+    if user_id == 0
+    {
+        return Err((StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), "User 0 is the root user and cannot be deleted.".to_string())));
+    }
+
+    let _lock = storage.lock();
+    let mut lock_user_list = storage.users();
+    if !lock_user_list.iter().any(|user| user.id == user_id)
+    {
+        return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", user_id).to_string())));
+    }
+    if storage.jobs().iter().any(|content| content.submission.user_id == user_id && content.state != "Finished")
+    {
+        return Err((StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(),
+            format!("User {} has jobs that are not finished.", user_id).to_string())));
+    }
+    let mut lock_contest_list = storage.contests();
+    let mut teams = storage.teams().map_err(storage::unreadable)?;
+    let mut jobs = cascade_jobs(user_id, policy, storage).map_err(storage::unreadable)?;
+
+    // the lists are changed on copies, which replace them once the deletion is saved
+    let mut users = lock_user_list.clone();
+    let mut contests = lock_contest_list.clone();
+    let cascade = deletion::cascade(user_id, policy, &mut users, &mut contests, &mut teams, &mut jobs, &config.problems);
+    let actor = caller.actor();
+
+    // rows referencing the user are removed before the user itself
+    storage.transaction(&mut || {
+        if policy == deletion::JobPolicy::delete
+        {
+            for job_id in cascade.job_ids.iter() {storage.delete_job(*job_id)?;}
+        }
+        for contest in contests.iter().filter(|contest| cascade.contest_ids.contains(&contest.id))
+        {
+            storage.save_contest(contest)?;
+        }
+        for team in cascade.teams.iter() {storage.save_team(team)?;}
+        storage.delete_credentials(user_id)?;
+        match &cascade.placeholder
+        {
+            Some(user) => storage.save_user(user),
+            None => storage.delete_user(user_id),
+        }?;
+        storage.append_event(&actor, audit::Change::user_deleted { user_id, jobs: policy })
+    }).map_err(storage::internal)?;
+    *lock_user_list = users;
+    *lock_contest_list = contests;
+    if policy == deletion::JobPolicy::delete
+    {
+        storage.jobs().retain(|content| content.submission.user_id != user_id);
+    }
+    log::info!("User {} deleted, {} jobs {:?}", user_id, cascade.job_ids.len(), policy);
+    return Ok(Deletion { user_id, jobs: policy, job_count: cascade.job_ids.len() as u32 });
+}
+
+// deletes a user, its jobs are anonymized unless ?jobs=delete
+#[delete("/users/{userId}", wrap = "permission::Require(permission::Permission::manage_users)")]
+#[allow(non_snake_case)]
+async fn delete_users_userId(userId: web::Path<u32>, query: web::Query<DeleteQuery>, caller: web::ReqData<permission::Caller>,
+    data_config: web::Data<Arc<Mutex<config::Config>>>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let user_id = userId.into_inner();
+    let policy = query.jobs;
+    let caller = caller.into_inner();
+    let config = data_config.lock().unwrap().clone();
+    match web::block(move || delete_user(user_id, policy, &caller, &config, data_storage.get_ref())).await
+    {
+        Ok(Ok(deletion)) => HttpResponse::Ok().json(deletion),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), format!("User {} could not be deleted.", user_id).to_string())),
+    }
+}
\ No newline at end of file
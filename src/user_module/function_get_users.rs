@@ -1,19 +1,67 @@
-use actix_web::{get, Responder, HttpResponse};
+use actix_web::{get, Responder, HttpResponse, web};
+use std::sync::Mutex;
+use std::sync::Arc;
 
 use crate::user;
-use crate::USER_LIST;
-use crate::sql;
-use crate::CONNECTION;
+use crate::stats;
+use crate::config;
+use crate::job;
+use crate::storage;
+use crate::permission;
 
 // gets list of all users
-#[get("/users")]
-async fn get_users() -> impl Responder 
+// emails are only shown to the users themselves and to those managing users
+#[get("/users", wrap = "permission::Require(permission::Permission::view)")]
+async fn get_users(caller: web::ReqData<permission::Caller>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
 {
-    let connection = CONNECTION.lock().unwrap();
-    let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = USER_LIST.lock().unwrap();
-    
-    // if SQL storage activated, then load from database
-    sql::get_user(&connection, &mut lock_user_list);
+    let lock_user_list: std::sync::MutexGuard<Vec<user::User>> = data_storage.users();
 
-    return HttpResponse::Ok().json(lock_user_list.clone());
+    let list: Vec<user::User> = lock_user_list.iter()
+        .map(|user| if caller.acts_for(user.id) {user.clone()} else {user.public()})
+        .collect();
+    return HttpResponse::Ok().json(list);
+}
+
+// gets a user and its profile given its id
+#[get("/users/{userId}", wrap = "permission::Require(permission::Permission::view)")]
+#[allow(non_snake_case)]
+async fn get_users_userId(userId: web::Path<u32>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
+{
+    let lock_user_list: std::sync::MutexGuard<Vec<user::User>> = data_storage.users();
+
+    match lock_user_list.iter().find(|user| user.id == *userId)
+    {
+        Some(user) if caller.acts_for(user.id) => HttpResponse::Ok().json(user.clone()),
+        Some(user) => HttpResponse::Ok().json(user.public()),
+        None => HttpResponse::NotFound().json(job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", userId.to_owned()).to_string())),
+    }
+}
+
+// gets the statistics of a user computed from its jobs, see stats::compute
+#[get("/users/{userId}/stats", wrap = "permission::Require(permission::Permission::view)")]
+#[allow(non_snake_case)]
+async fn get_users_userId_stats(userId: web::Path<u32>, caller: web::ReqData<permission::Caller>,
+    data_config: web::Data<Arc<Mutex<config::Config>>>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let user_id = userId.into_inner();
+    let user = data_storage.users().iter().find(|user| user.id == user_id).cloned();
+    let user = match user
+    {
+        Some(user) if caller.acts_for(user.id) => user,
+        Some(user) => user.public(),
+        None => return HttpResponse::NotFound().json(job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", user_id).to_string())),
+    };
+
+    // the config is copied so that requests changing the contests are not blocked behind it
+    let config = data_config.lock().unwrap().clone();
+    match web::block(move || stats::compute(user, &config, data_storage.get_ref()).map_err(storage::unreadable)).await
+    {
+        Ok(Ok(stats)) => HttpResponse::Ok().json(stats),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Statistics could not be computed.".to_string())),
+    }
 }
\ No newline at end of file
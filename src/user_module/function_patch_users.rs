@@ -0,0 +1,78 @@
+use actix_web::{patch, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
+
+use crate::user;
+use crate::audit;
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+fn invalid_argument(message: String) -> (StatusCode, job::Error)
+{
+    (StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message))
+}
+
+// changes the fields of a user that are set in the body
+// users may change their own profile, renaming them and changing other users needs Permission::manage_users
+fn patch_user(user_id: u32, body: user::PatchUser, caller: &permission::Caller, storage: &dyn storage::Storage)
+    -> Result<user::User, (StatusCode, job::Error)>
+{
+    if !caller.acts_for(user_id)
+    {
+        return Err((StatusCode::FORBIDDEN, permission::forbidden("Only the user itself or those managing users may change a user.")));
+    }
+    if let Some(name) = &body.name
+    {
+        user::check_name(name).map_err(invalid_argument)?;
+    }
+
+    let _lock = storage.lock();
+    let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = storage.users();
+
+    let index = match lock_user_list.iter().position(|user| user.id == user_id)
+    {
+        Some(index) => index,
+        None => return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", user_id).to_string()))),
+    };
+    let mut user = lock_user_list[index].clone();
+
+    if let Some(name) = body.name
+    {
+        if name != user.name && !caller.has(permission::Permission::manage_users)
+        {
+            return Err((StatusCode::FORBIDDEN, permission::forbidden("Only those managing users may rename a user.")));
+        }
+        if user::name_taken(&lock_user_list, &name, Some(user_id))
+        {
+            return Err(invalid_argument(format!("User name '{}' already exists.", name)));
+        }
+        user.name = name;
+    }
+    user.profile.apply(body.profile);
+    user.profile.check().map_err(invalid_argument)?;
+
+    let actor = caller.actor();
+    storage.transaction(&mut || {
+        storage.save_user(&user)?;
+        storage.append_event(&actor, audit::Change::user_updated { user: user.clone() })
+    }).map_err(storage::internal)?;
+    lock_user_list[index] = user.clone();
+    return Ok(user);
+}
+
+// changes the name or the profile of a user, fields set to "" are cleared
+#[patch("/users/{userId}", wrap = "permission::Require(permission::Permission::edit_profile)")]
+#[allow(non_snake_case)]
+async fn patch_users_userId(userId: web::Path<u32>, body: web::Json<user::PatchUser>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let user_id = userId.into_inner();
+    let caller = caller.into_inner();
+    match web::block(move || patch_user(user_id, body.into_inner(), &caller, data_storage.get_ref())).await
+    {
+        Ok(Ok(user)) => HttpResponse::Ok().json(user),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "User could not be changed.".to_string())),
+    }
+}
\ No newline at end of file
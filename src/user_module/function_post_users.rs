@@ -1,51 +1,100 @@
-use std::i64::MAX;
 
-use actix_web::{post, Responder, HttpResponse, web};
-use chrono::{DateTime, Utc};
+use actix_web::{post, Responder, HttpResponse, HttpRequest, HttpMessage, web};
+use actix_web::http::StatusCode;
+use serde::Deserialize;
 
 use crate::user;
+use crate::provision;
+use crate::audit;
 use crate::job;
 use crate::contest;
-use crate::sql;
+use crate::storage;
+use crate::storage::Counter;
+use crate::permission;
 
+// adds a new user with a new id, who takes part in contest 0
+// the caller holds Storage::lock and has checked the name with user::check_name and user::name_taken, and the profile with Profile::check
+pub fn create_user(name: String, profile: user::Profile, actor: &str, storage: &dyn storage::Storage) -> Result<user::User, storage::Error>
+{
+    let mut lock_user_list = storage.users();
+    let mut lock_user_id_count = storage.counter(Counter::User);
 
-use crate::USER_LIST;
-use crate::USER_ID_COUNT;
+    // push new user into user list, with a new id
+    lock_user_list.push(user::User { id: *lock_user_id_count, name, profile, deactivated: false });
+    
+    // START setup for contest 0
 
-use crate::CONTEST_LIST;
+    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
+    
+    // adds id of the new user into user_ids
+    lock_contest_list[0].user_ids.push(*lock_user_id_count as u32);
+    let number_of_problems = lock_contest_list[0].problem_ids.len();
+    
+    // adds a new user into user lists
+    // use to store user progress 
+    lock_contest_list[0].users.push(contest::RankInfo::new(lock_user_list[lock_user_list.len() -1].clone(), number_of_problems));
+
+    // END setup for contest 0
+
+    *lock_user_id_count += 1;
+
+    // the user, its place in contest 0 and the user count are stored together
+    // ids of deleted users are not given again, so the new user is the last one rather than the one at its id
+    let output = lock_user_list[lock_user_list.len() - 1].clone();
+    let saved = storage.transaction(&mut || {
+        storage.save_counter(Counter::User, *lock_user_id_count)?;
+        storage.save_user(&output)?;
+        storage.save_participant(&lock_contest_list[0], output.id)?;
+        storage.append_event(actor, audit::Change::user_created { user: output.clone() })
+    });
+    if let Err(error) = saved
+    {
+        drop(lock_contest_list);
+        drop(lock_user_id_count);
+        drop(lock_user_list);
+        forget_user(&output, storage);
+        return Err(error);
+    }
+    return Ok(output);
+}
 
-use crate::CONNECTION;
+// takes back a user added by create_user whose transaction failed, its rows were rolled back with it
+// the user was the last one created, so its id is given again
+pub fn forget_user(user: &user::User, storage: &dyn storage::Storage)
+{
+    let mut lock_user_list = storage.users();
+    let mut lock_user_id_count = storage.counter(Counter::User);
+    let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = storage.contests();
+
+    lock_user_list.retain(|other| other.id != user.id);
+    lock_contest_list[0].user_ids.retain(|id| *id != user.id);
+    lock_contest_list[0].users.retain(|rank_info| rank_info.user.id != user.id);
+    *lock_user_id_count = user.id;
+}
 
-// checks if a user exists given its name
-fn check_exists(list: &std::sync::MutexGuard<Vec<user::User>>, name: String) -> bool
+// adds a new user, or renames the user with the id of the body
+fn save_user(body: user::GetUser, caller: &permission::Caller, storage: &dyn storage::Storage) -> Result<user::User, (StatusCode, job::Error)>
 {
-    for element in list.iter()
+    if let Err(message) = user::check_name(&body.name)
     {
-        if element.name == name {return true;}
+        return Err((StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message)));
     }
-    return false;
-}
 
-// posts a new user
-#[post("/users")]
-async fn post_users(body: web::Json<user::GetUser>) -> impl Responder 
-{
-    let connection = CONNECTION.lock().unwrap();
+    let actor = caller.actor();
+    let _lock = storage.lock();
 
-    let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = USER_LIST.lock().unwrap();
-    
-    // if SQL storage is activated, then load from database
-    sql::get_user(&connection, &mut lock_user_list);
+    let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = storage.users();
 
     // if a user with this name already exists, then return error
-    if check_exists(&lock_user_list, body.name.clone())
+    // names differing only in case are the same name, a user may be renamed to another case of its own name
+    if user::name_taken(&lock_user_list, &body.name, body.id)
     {
-        return HttpResponse::BadRequest().json(job::Error::
+        return Err((StatusCode::BAD_REQUEST, job::Error::
             new(
                 1, 
                 "ERR_INVALID_ARGUMENT".to_string(), 
                 format!("User name '{}' already exists.", body.name.clone()).to_string()
-            ));
+            )));
     }
 
     // if id is provided, then update user information
@@ -56,72 +105,93 @@ async fn post_users(body: web::Json<user::GetUser>) -> impl Responder
         {
             if id == user.id
             {
-                user.name = body.name.clone();
-
-                let output = user.clone();
+                let mut output = user.clone();
+                output.name = body.name.clone();
 
-                // pushes information into SQL database if persistent storage is activated
-                sql::push_user(&connection, &mut lock_user_list);
+                storage.transaction(&mut || {
+                    storage.save_user(&output)?;
+                    storage.append_event(&actor, audit::Change::user_renamed { user: output.clone() })
+                }).map_err(storage::internal)?;
+                *user = output.clone();
 
-                return HttpResponse::Ok().json(output);
+                return Ok(output);
             }
         }
         // if not found then return error
-        return HttpResponse::NotFound().json(job::Error::
+        return Err((StatusCode::NOT_FOUND, job::Error::
             new(
                 3, 
                 "ERR_NOT_FOUND".to_string(), 
                 format!("User {} not found.", id.to_owned()).to_string()
-            ));
+            )));
     }
-    // if id not provided, then new user added
-    else 
-    {
-        let mut lock_user_id_count = USER_ID_COUNT.lock().unwrap();
-
-        // if SQL storage is activated, then load from database
-        sql::get_user_count(&connection, &mut lock_user_id_count);
-
-        // push new user into user list, with a new id
-        lock_user_list.push(user::User
-            {   
-                id: *lock_user_id_count, 
-                name: body.name.clone(),
-            });
-        
-        // START setup for contest 0
-
-        let mut lock_contest_list: std::sync::MutexGuard<Vec<contest::Contest>> = CONTEST_LIST.lock().unwrap();
-        
-        // adds id of the new user into user_ids
-        lock_contest_list[0].user_ids.push(*lock_user_id_count as u32);
-        let number_of_problems = lock_contest_list[0].problem_ids.len();
-        
-        // adds a new user into user lists
-        // use to store user progress 
-        lock_contest_list[0].users.push(contest::RankInfo
-        {
-            user: lock_user_list[lock_user_list.len() -1].clone(),
-            scores: vec![0.0; number_of_problems],
-            rank: 0,
-
-            highest_scores: vec![0.0 ; number_of_problems],
-            latest_scores: vec![0.0 ; number_of_problems],
 
-            competitive_score_sum: 0.0,
-            shortest_times: vec![vec![MAX; 20]; number_of_problems], // possible error
+    // if id not provided, then new user added
+    drop(lock_user_list);
+    return create_user(body.name.clone(), user::Profile::default(), &actor, storage).map_err(storage::internal);
+}
 
-            latest_submission: DateTime::<Utc>::MAX_UTC,
-            score: 0,
-            submission_count: 0,
-        });
+// posts a new user
+// saving waits for other writers, so it runs on the blocking thread pool
+#[post("/users", wrap = "permission::Require(permission::Permission::manage_users)")]
+async fn post_users(body: web::Json<user::GetUser>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder 
+{
+    let caller = caller.into_inner();
+    match web::block(move || save_user(body.into_inner(), &caller, data_storage.get_ref())).await
+    {
+        Ok(Ok(user)) => HttpResponse::Ok().json(user),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "User could not be saved.".to_string())),
+    }
+}
 
-        // END setup for contest 0
+// query of POST /users/import
+#[derive(Deserialize, Debug)]
+pub struct ImportQuery
+{
+    contest_id: Option<u32>,
+}
 
-        *lock_user_id_count += 1;
-        sql::push_user_count(&connection, *lock_user_id_count);
+// creates many users at once from a CSV file ("Content-Type: text/csv") or a JSON array, see provision
+// giving users a team or enrolling them into a contest also needs Permission::manage_contests
+#[post("/users/import", wrap = "permission::Require(permission::Permission::manage_users)")]
+async fn post_users_import(req: HttpRequest, body: web::Bytes, query: web::Query<ImportQuery>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let rows = if req.content_type() == "text/csv"
+    {
+        std::str::from_utf8(&body).map_err(|_| "CSV is not UTF-8.".to_string()).and_then(provision::parse_csv)
+    }
+    else
+    {
+        provision::parse_json(&body)
+    };
+    let rows = match rows
+    {
+        Ok(rows) => rows,
+        Err(message) => return HttpResponse::BadRequest().json(job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message)),
+    };
+    if (query.contest_id.is_some() || rows.iter().any(|row| row.team.is_some())) && !caller.has(permission::Permission::manage_contests)
+    {
+        return HttpResponse::Forbidden().json(permission::forbidden("Only those managing contests may put users in teams or contests."));
+    }
 
-        sql::push_user(&connection, &mut lock_user_list);
-        return HttpResponse::Ok().json(lock_user_list[(*lock_user_id_count-1) as usize].clone());
+    let contest_id = query.contest_id;
+    let caller = caller.into_inner();
+    let result = web::block(move || {
+        let accounts = provision::prepare(rows)?;
+        let actor = caller.actor();
+        let storage = data_storage.get_ref();
+        let _lock = storage.lock();
+        provision::provision(&accounts, contest_id, &actor, storage)
+    }).await;
+    match result
+    {
+        Ok(Ok(provisioned)) => HttpResponse::Ok().json(provisioned),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Users could not be imported.".to_string())),
     }
 }
\ No newline at end of file
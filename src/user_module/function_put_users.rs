@@ -0,0 +1,93 @@
+use actix_web::{put, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
+
+use crate::user;
+use crate::audit;
+use crate::job;
+use crate::storage;
+use crate::permission;
+
+// gives a user a new role, which applies to its sessions from their next request on
+fn set_role(user_id: u32, role: user::PutRole, caller: &permission::Caller, storage: &dyn storage::Storage) -> Result<user::Account, (StatusCode, job::Error)>
+{
+    let _lock = storage.lock();
+
+    let user = storage.users().iter().find(|user| user.id == user_id).cloned();
+    let user = match user
+    {
+        Some(user) => user,
+        None => return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", user_id).to_string()))),
+    };
+
+    let actor = caller.actor();
+    storage.transaction(&mut || {
+        storage.save_role(user.id, role.role)?;
+        storage.append_event(&actor, audit::Change::user_role_changed { user: user.clone(), role: role.role })
+    }).map_err(storage::internal)?;
+    log::info!("User {} is now {:?}", user.id, role.role);
+    return Ok(user::Account { user, role: role.role });
+}
+
+// changes the role of a user
+#[put("/users/{userId}/role", wrap = "permission::Require(permission::Permission::manage_users)")]
+#[allow(non_snake_case)]
+async fn put_users_userId_role(userId: web::Path<u32>, body: web::Json<user::PutRole>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let user_id = userId.into_inner();
+    let caller = caller.into_inner();
+    match web::block(move || set_role(user_id, body.into_inner(), &caller, data_storage.get_ref())).await
+    {
+        Ok(Ok(account)) => HttpResponse::Ok().json(account),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "Role could not be changed.".to_string())),
+    }
+}
+
+// deactivates a user, or activates it again
+// sessions and API keys of deactivated users are rejected but kept, so that they work again once the user is active
+fn set_deactivated(user_id: u32, body: user::PutDeactivated, caller: &permission::Caller, storage: &dyn storage::Storage)
+    -> Result<user::User, (StatusCode, job::Error)>
+{
+    if user_id == 0
+    {
+        return Err((StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), "User 0 is the root user and cannot be deactivated.".to_string())));
+    }
+
+    let _lock = storage.lock();
+    let mut lock_user_list: std::sync::MutexGuard<Vec<user::User>> = storage.users();
+    let user = match lock_user_list.iter_mut().find(|user| user.id == user_id)
+    {
+        Some(user) => user,
+        None => return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("User {} not found.", user_id).to_string()))),
+    };
+    let mut output = user.clone();
+    output.deactivated = body.deactivated;
+
+    let actor = caller.actor();
+    storage.transaction(&mut || {
+        storage.save_user(&output)?;
+        storage.append_event(&actor, audit::Change::user_updated { user: output.clone() })
+    }).map_err(storage::internal)?;
+    *user = output.clone();
+    log::info!("User {} is now {}", output.id, if output.deactivated {"deactivated"} else {"active"});
+    return Ok(output);
+}
+
+// deactivates or activates a user
+#[put("/users/{userId}/deactivated", wrap = "permission::Require(permission::Permission::manage_users)")]
+#[allow(non_snake_case)]
+async fn put_users_userId_deactivated(userId: web::Path<u32>, body: web::Json<user::PutDeactivated>, caller: web::ReqData<permission::Caller>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let user_id = userId.into_inner();
+    let caller = caller.into_inner();
+    match web::block(move || set_deactivated(user_id, body.into_inner(), &caller, data_storage.get_ref())).await
+    {
+        Ok(Ok(user)) => HttpResponse::Ok().json(user),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), "User could not be changed.".to_string())),
+    }
+}
\ No newline at end of file
@@ -1,3 +1,9 @@
 pub mod user;
+pub mod stats;
+pub mod provision;
+pub mod deletion;
 pub mod function_get_users;
-pub mod function_post_users;
\ No newline at end of file
+pub mod function_post_users;
+pub mod function_put_users;
+pub mod function_patch_users;
+pub mod function_delete_users;
\ No newline at end of file
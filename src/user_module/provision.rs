@@ -0,0 +1,383 @@
+use actix_web::http::StatusCode;
+use serde::{Serialize, Deserialize};
+
+use crate::user;
+use crate::config;
+use crate::team;
+use crate::auth;
+use crate::audit;
+use crate::job;
+use crate::contest;
+use crate::storage;
+use crate::function_post_users;
+
+// a user to be created by POST /users/import or by the import-users command
+// CSV files have a header naming the columns, only the name column is required
+// an empty or missing password is generated, users with the same team are put in that team
+// role is given to the user, e.g. to create the first admin of a server whose anonymous_role cannot manage users
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ImportRow
+{
+    pub name: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub team: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub role: Option<config::Role>,
+}
+
+// columns a CSV file may have
+const COLUMNS: [&str; 5] = ["name", "display_name", "team", "password", "role"];
+
+// an account ready to be created, its password is already hashed
+#[derive(Debug, Clone)]
+pub struct Account
+{
+    row: ImportRow,
+    password_hash: String,
+    generated_password: Option<String>,
+}
+
+// a created user, only generated passwords are sent back so that they can be handed out
+#[derive(Serialize, Debug, Clone)]
+pub struct Credential
+{
+    pub id: u32,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+// response of POST /users/import, teams are those created or joined by the imported users
+#[derive(Serialize, Debug, Clone)]
+pub struct Provisioned
+{
+    pub users: Vec<Credential>,
+    pub teams: Vec<team::Team>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contest_id: Option<u32>,
+}
+
+fn invalid_argument(message: String) -> (StatusCode, job::Error)
+{
+    (StatusCode::BAD_REQUEST, job::Error::new(1, "ERR_INVALID_ARGUMENT".to_string(), message))
+}
+
+// splits a CSV text into records of fields
+// fields may be quoted, with "" standing for a quote inside them, and blank lines are skipped
+fn csv_records(text: &str) -> Result<Vec<Vec<String>>, String>
+{
+    let mut records: Vec<Vec<String>> = vec![];
+    let mut record: Vec<String> = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next()
+    {
+        if quoted
+        {
+            match c
+            {
+                '"' if chars.peek() == Some(&'"') => {chars.next(); field.push('"');}
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c
+        {
+            '"' if field.is_empty() => quoted = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' =>
+            {
+                record.push(std::mem::take(&mut field));
+                if !(record.len() == 1 && record[0].trim().is_empty()) {records.push(std::mem::take(&mut record));}
+                record.clear();
+            }
+            _ => field.push(c),
+        }
+    }
+    if quoted {return Err("CSV has a quote that is not closed.".to_string());}
+    record.push(field);
+    if !(record.len() == 1 && record[0].trim().is_empty()) {records.push(record);}
+    return Ok(records);
+}
+
+// reads the rows of a CSV file, whose first record names the columns
+pub fn parse_csv(text: &str) -> Result<Vec<ImportRow>, String>
+{
+    let mut records = csv_records(text)?.into_iter();
+    let header: Vec<String> = match records.next()
+    {
+        Some(header) => header.iter().map(|column| column.trim().to_lowercase()).collect(),
+        None => return Ok(vec![]),
+    };
+    for (index, column) in header.iter().enumerate()
+    {
+        if !COLUMNS.contains(&column.as_str())
+        {
+            return Err(format!("Unknown column '{}', columns may be {}.", column, COLUMNS.join(", ")));
+        }
+        if header[..index].contains(column) {return Err(format!("Column '{}' is repeated.", column));}
+    }
+    if !header.iter().any(|column| column == "name") {return Err("CSV must have a name column.".to_string());}
+
+    let mut rows: Vec<ImportRow> = vec![];
+    for (index, record) in records.enumerate()
+    {
+        if record.len() != header.len()
+        {
+            return Err(format!("Row {} has {} fields, the header has {}.", index + 1, record.len(), header.len()));
+        }
+        let mut row = ImportRow::default();
+        for (column, value) in header.iter().zip(record.into_iter())
+        {
+            let value = value.trim().to_string();
+            let optional = if value.is_empty() {None} else {Some(value.clone())};
+            match column.as_str()
+            {
+                "name" => row.name = value,
+                "display_name" => row.display_name = optional,
+                "team" => row.team = optional,
+                "role" if !value.is_empty() => row.role = Some(serde_json::from_value(serde_json::Value::String(value.clone()))
+                    .map_err(|_| format!("Row {} has an unknown role '{}'.", index + 1, value))?),
+                "role" => {}
+                _ => row.password = optional,
+            }
+        }
+        rows.push(row);
+    }
+    return Ok(rows);
+}
+
+// reads the rows of a JSON array
+pub fn parse_json(bytes: &[u8]) -> Result<Vec<ImportRow>, String>
+{
+    serde_json::from_slice(bytes).map_err(|err| format!("Users could not be read: {}", err))
+}
+
+// checks the rows and hashes their passwords, generating those that are not given
+// this is slow on purpose and done before the storage is locked
+pub fn prepare(rows: Vec<ImportRow>) -> Result<Vec<Account>, (StatusCode, job::Error)>
+{
+    if rows.is_empty()
+    {
+        return Err(invalid_argument("No users to import.".to_string()));
+    }
+    for (index, row) in rows.iter().enumerate()
+    {
+        let at = |message: String| invalid_argument(format!("Row {}: {}", index + 1, message));
+        user::check_name(&row.name).map_err(at)?;
+        if rows[..index].iter().any(|other| other.name.to_lowercase() == row.name.to_lowercase())
+        {
+            return Err(at(format!("User name '{}' is repeated.", row.name)));
+        }
+        user::Profile { display_name: row.display_name.clone(), ..Default::default() }.check().map_err(at)?;
+        if let Some(team) = &row.team
+        {
+            if team.trim().is_empty() || team.chars().count() > team::MAX_NAME_LENGTH
+            {
+                return Err(at(format!("Team name must have 1 to {} characters.", team::MAX_NAME_LENGTH)));
+            }
+        }
+        if let Some(password) = &row.password
+        {
+            if !password.is_empty() && password.chars().count() < auth::MIN_PASSWORD_LENGTH
+            {
+                return Err(at(format!("Password must have at least {} characters.", auth::MIN_PASSWORD_LENGTH)));
+            }
+        }
+    }
+
+    let accounts = rows.into_iter().map(|row|
+    {
+        let generated_password = match &row.password
+        {
+            Some(password) if !password.is_empty() => None,
+            _ => Some(auth::generate_password()),
+        };
+        let password_hash = auth::hash_password(generated_password.as_ref().or(row.password.as_ref()).unwrap());
+        Account { row, password_hash, generated_password }
+    }).collect();
+    return Ok(accounts);
+}
+
+// creates the accounts, their teams and their place in the contest in one transaction, nothing is created if a check fails
+// teams are matched by name, so that users may join existing teams
+// the caller holds Storage::lock
+pub fn provision(accounts: &[Account], contest_id: Option<u32>, actor: &str, storage: &dyn storage::Storage)
+    -> Result<Provisioned, (StatusCode, job::Error)>
+{
+    for account in accounts.iter()
+    {
+        if user::name_taken(&storage.users(), &account.row.name, None)
+        {
+            return Err(invalid_argument(format!("User name '{}' already exists.", account.row.name)));
+        }
+    }
+    if let Some(contest_id) = contest_id
+    {
+        // every user takes part in contest 0
+        if contest_id == 0
+        {
+            return Err(invalid_argument("Users are enrolled in contest 0 when they are created.".to_string()));
+        }
+        let lock_contest_list = storage.contests();
+        let contest = match lock_contest_list.iter().find(|contest| contest.id == contest_id)
+        {
+            Some(contest) => contest,
+            None => return Err((StatusCode::NOT_FOUND, job::Error::new(3, "ERR_NOT_FOUND".to_string(), format!("Contest {} not found.", contest_id)))),
+        };
+        if !contest.team_ids.is_empty() && accounts.iter().any(|account| account.row.team.is_none())
+        {
+            return Err(invalid_argument(format!("Contest {} has teams, so every user must have a team.", contest_id)));
+        }
+    }
+
+    // contests are changed on copies, which replace them once everything is saved
+    // users are added to the lists as they are created, and taken back if something cannot be saved
+    let mut output: Option<Provisioned> = None;
+    let mut users: Vec<user::User> = vec![];
+    let mut contests: Vec<contest::Contest> = vec![];
+    let saved = storage.transaction(&mut || {
+        let mut credentials: Vec<Credential> = vec![];
+        for account in accounts.iter()
+        {
+            let profile = user::Profile { display_name: account.row.display_name.clone(), ..Default::default() };
+            let user = function_post_users::create_user(account.row.name.clone(), profile, actor, storage)?;
+            users.push(user.clone());
+            storage.save_password(user.id, &account.password_hash)?;
+            if let Some(role) = account.row.role
+            {
+                storage.save_role(user.id, role)?;
+                storage.append_event(actor, audit::Change::user_role_changed { user: user.clone(), role })?;
+            }
+            credentials.push(Credential { id: user.id, name: user.name.clone(), password: account.generated_password.clone() });
+        }
+
+        // groups the users by team, teams that do not exist yet are created
+        let mut all_teams = storage.teams()?;
+        let mut teams: Vec<team::Team> = vec![];
+        let mut created: Vec<u32> = vec![];
+        for (account, user) in accounts.iter().zip(users.iter())
+        {
+            let name = match &account.row.team
+            {
+                Some(name) => name.trim(),
+                None => continue,
+            };
+            let index = match teams.iter().position(|team| team.name.to_lowercase() == name.to_lowercase())
+            {
+                Some(index) => index,
+                None =>
+                {
+                    let team = match all_teams.iter().find(|team| team.name.to_lowercase() == name.to_lowercase())
+                    {
+                        Some(team) => team.clone(),
+                        None =>
+                        {
+                            let team = team::Team { id: all_teams.len() as u32, name: name.to_string(), member_ids: vec![], coach_id: None };
+                            created.push(team.id);
+                            all_teams.push(team.clone());
+                            team
+                        }
+                    };
+                    teams.push(team);
+                    teams.len() - 1
+                }
+            };
+            teams[index].member_ids.push(user.id);
+        }
+        for team in teams.iter()
+        {
+            storage.save_team(team)?;
+            let change = match created.contains(&team.id)
+            {
+                true => audit::Change::team_created { team: team.clone() },
+                false => audit::Change::team_updated { team: team.clone() },
+            };
+            storage.append_event(actor, change)?;
+        }
+
+        // the users join the contest, contests with teams also register the teams of the users
+        if let Some(contest_id) = contest_id
+        {
+            let mut contest = storage.contests().iter().find(|contest| contest.id == contest_id).unwrap().clone();
+            for user in users.iter()
+            {
+                contest.user_ids.push(user.id);
+                contest.users.push(contest::RankInfo::new(user.clone(), contest.problem_ids.len()));
+            }
+            if !contest.team_ids.is_empty()
+            {
+                for team in teams.iter()
+                {
+                    if !contest.team_ids.contains(&team.id) {contest.team_ids.push(team.id);}
+                }
+            }
+            storage.save_contest(&contest)?;
+            storage.append_event(actor, audit::Change::contest_updated { contest: contest.clone() })?;
+            contests.push(contest);
+        }
+
+        // the other contests registering the teams of the users take the users in as well
+        let all_teams: Vec<team::Team> = all_teams.iter().map(|team| teams.iter().find(|joined| joined.id == team.id).unwrap_or(team).clone()).collect();
+        let lock_user_list = storage.users();
+        let lock_contest_list = storage.contests();
+        for contest in lock_contest_list.iter()
+        {
+            if Some(contest.id) == contest_id || !teams.iter().any(|team| contest.team_ids.contains(&team.id)) {continue;}
+            let mut contest = contest.clone();
+            contest::sync_team_members(&mut contest, &all_teams, &lock_user_list).map_err(storage::Error)?;
+            storage.save_contest(&contest)?;
+            storage.append_event(actor, audit::Change::contest_updated { contest: contest.clone() })?;
+            contests.push(contest);
+        }
+
+        output = Some(Provisioned { users: credentials, teams, contest_id });
+        Ok(())
+    });
+    if let Err(error) = saved
+    {
+        for user in users.iter().rev() {function_post_users::forget_user(user, storage);}
+        return Err(storage::internal(error));
+    }
+
+    let mut lock_contest_list = storage.contests();
+    for contest in contests.into_iter()
+    {
+        let index = lock_contest_list.iter().position(|existing| existing.id == contest.id).unwrap();
+        lock_contest_list[index] = contest;
+    }
+    return Ok(output.unwrap());
+}
+
+// creates the users of a CSV or JSON file for the import-users command, which are told apart by the extension
+// returns the credentials as CSV, to be printed
+pub fn import_file(path: &str, contest_id: Option<u32>, storage: &dyn storage::Storage) -> Result<String, String>
+{
+    let bytes = std::fs::read(path).map_err(|err| format!("cannot open {}: {}", path, err))?;
+    let rows = if path.to_lowercase().ends_with(".csv")
+    {
+        let text = String::from_utf8(bytes).map_err(|_| format!("{} is not UTF-8", path))?;
+        parse_csv(&text)?
+    }
+    else
+    {
+        parse_json(&bytes)?
+    };
+    let accounts = prepare(rows).map_err(|(_, error)| error.message)?;
+    let provisioned = provision(&accounts, contest_id, audit::ACTOR_ADMIN, storage).map_err(|(_, error)| error.message)?;
+
+    let mut output = String::from("id,name,password");
+    for credential in provisioned.users.iter()
+    {
+        output.push_str(&format!("\n{},{},{}", credential.id, credential.name, credential.password.clone().unwrap_or_default()));
+    }
+    return Ok(output);
+}
\ No newline at end of file
@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+
+use crate::user;
+use crate::job;
+use crate::config;
+use crate::contest;
+use crate::storage;
+use crate::function_get_contests;
+
+// a contest a user took part in, with its place in the ranklist
+#[derive(Serialize, Debug, Clone)]
+pub struct ContestHistory
+{
+    pub contest_id: u32,
+    pub name: String,
+    pub rank: u32,
+    pub score: u32,
+    pub submission_count: u32, // jobs of the user itself, also in contests with teams
+}
+
+// response of GET /users/{id}/stats
+// verdicts and days only appear once the user has a job with them, days are UTC dates such as "2022-08-27"
+#[derive(Serialize, Debug, Clone)]
+pub struct Stats
+{
+    pub user: user::User,
+    pub submission_count: u32,
+    pub solved_problem_ids: Vec<u32>,
+    pub attempted_problem_ids: Vec<u32>, // problems with jobs but without an accepted one
+    pub acceptance_rate: f64, // accepted jobs among the finished ones, 0 without finished jobs
+    pub results: BTreeMap<String, u32>,
+    pub languages: BTreeMap<String, u32>,
+    pub activity: BTreeMap<String, u32>,
+    pub contests: Vec<ContestHistory>,
+}
+
+// the name a result is serialized with, e.g. "Wrong Answer"
+fn result_name(result: &job::PossibleResult) -> String
+{
+    serde_json::to_value(result).ok().and_then(|value| value.as_str().map(|name| name.to_string())).unwrap_or_default()
+}
+
+// computes the statistics of a user from its jobs
+// contest 0 has every user, so it is not part of the contest history
+pub fn compute(user: user::User, config: &config::Config, storage: &dyn storage::Storage) -> Result<Stats, storage::Error>
+{
+    let query = storage::JobQuery
+    {
+        user_id: Some(user.id),
+        user_name: None,
+        contest_id: None,
+        problem_id: None,
+        language: None,
+        from: None,
+        to: None,
+        state: None,
+        result: None,
+
+        descending: false,
+        after_id: None,
+        limit: None,
+        with_source: false,
+    };
+    let jobs = storage.query_jobs(&query)?;
+
+    let mut solved_problem_ids: Vec<u32> = vec![];
+    let mut attempted_problem_ids: Vec<u32> = vec![];
+    let mut results: BTreeMap<String, u32> = BTreeMap::new();
+    let mut languages: BTreeMap<String, u32> = BTreeMap::new();
+    let mut activity: BTreeMap<String, u32> = BTreeMap::new();
+    let mut finished_count = 0;
+    let mut accepted_count = 0;
+    for content in jobs.iter()
+    {
+        let problem_id = content.submission.problem_id;
+        if content.result == job::PossibleResult::Accepted
+        {
+            accepted_count += 1;
+            if !solved_problem_ids.contains(&problem_id) {solved_problem_ids.push(problem_id);}
+        }
+        else if !attempted_problem_ids.contains(&problem_id)
+        {
+            attempted_problem_ids.push(problem_id);
+        }
+        if content.state == "Finished" {finished_count += 1;}
+
+        *results.entry(result_name(&content.result)).or_insert(0) += 1;
+        *languages.entry(content.submission.language.clone()).or_insert(0) += 1;
+        *activity.entry(content.created_time.format("%Y-%m-%d").to_string()).or_insert(0) += 1;
+    }
+    attempted_problem_ids.retain(|problem_id| !solved_problem_ids.contains(problem_id));
+    solved_problem_ids.sort();
+    attempted_problem_ids.sort();
+
+    // the contests are copied, since the ranks of contests with teams need the job list
+    let contests: Vec<contest::Contest> = storage.contests().iter()
+        .filter(|contest| contest.id != 0 && contest.user_ids.contains(&user.id))
+        .cloned()
+        .collect();
+    let mut history: Vec<ContestHistory> = vec![];
+    for contest in contests.iter()
+    {
+        if let Some((rank, score)) = function_get_contests::standing(contest, user.id, config, storage)?
+        {
+            history.push(ContestHistory
+            {
+                contest_id: contest.id,
+                name: contest.name.clone(),
+                rank,
+                score,
+                submission_count: jobs.iter().filter(|content| content.submission.contest_id == contest.id).count() as u32,
+            });
+        }
+    }
+
+    Ok(Stats
+    {
+        user,
+        submission_count: jobs.len() as u32,
+        solved_problem_ids,
+        attempted_problem_ids,
+        acceptance_rate: if finished_count == 0 {0.0} else {accepted_count as f64 / finished_count as f64},
+        results,
+        languages,
+        activity,
+        contests: history,
+    })
+}
\ No newline at end of file
@@ -1,11 +1,149 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::config;
+
+// longest name a user may have
+pub const MAX_NAME_LENGTH: usize = 32;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User
 {
     pub id: u32,
     pub name: String,
+    #[serde(flatten)]
+    pub profile: Profile,
+    // deactivated users cannot log in or submit, and are left out of ranklists
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deactivated: bool,
+}
+
+impl User
+{
+    pub fn new(id: u32, name: String) -> User
+    {
+        User { id, name, profile: Profile::default(), deactivated: false }
+    }
+
+    // the user as it appears in ranklists, without its profile
+    pub fn summary(&self) -> User
+    {
+        User::new(self.id, self.name.clone())
+    }
+
+    // the user as it is shown to others, the email is only shown to the user itself and to those managing users
+    pub fn public(&self) -> User
+    {
+        let mut user = self.clone();
+        user.profile.email = None;
+        return user;
+    }
+}
+
+// optional details of a user, fields that are not set are left out of the JSON
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Profile
+{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>, // ISO 3166-1 alpha-2 code, e.g. "CN"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+}
+
+// checks that a field has at most max characters and no control characters
+fn check_text(field: &str, value: &Option<String>, max: usize) -> Result<(), String>
+{
+    match value
+    {
+        Some(value) if value.chars().count() > max => Err(format!("{} must have at most {} characters.", field, max)),
+        Some(value) if value.chars().any(|c| c.is_control() && c != '\n') => Err(format!("{} must not contain control characters.", field)),
+        _ => Ok(()),
+    }
+}
+
+impl Profile
+{
+    // the fields set in the patch replace those of the profile, fields set to "" are cleared
+    pub fn apply(&mut self, patch: Profile)
+    {
+        fn replace(field: &mut Option<String>, value: Option<String>)
+        {
+            if let Some(value) = value
+            {
+                *field = if value.is_empty() {None} else {Some(value)};
+            }
+        }
+        replace(&mut self.display_name, patch.display_name);
+        replace(&mut self.email, patch.email);
+        replace(&mut self.organization, patch.organization);
+        replace(&mut self.country, patch.country);
+        replace(&mut self.avatar_url, patch.avatar_url);
+        replace(&mut self.bio, patch.bio);
+    }
+
+    pub fn check(&self) -> Result<(), String>
+    {
+        check_text("Display name", &self.display_name, 64)?;
+        check_text("Email", &self.email, 254)?;
+        check_text("Organization", &self.organization, 128)?;
+        check_text("Avatar URL", &self.avatar_url, 512)?;
+        check_text("Bio", &self.bio, 1000)?;
+        if let Some(email) = &self.email
+        {
+            let valid = match email.split_once('@')
+            {
+                Some((local, domain)) => !local.is_empty() && domain.contains('.') && !domain.contains('@')
+                    && !email.chars().any(char::is_whitespace),
+                None => false,
+            };
+            if !valid {return Err(format!("Email '{}' is not a valid address.", email));}
+        }
+        if let Some(country) = &self.country
+        {
+            if country.len() != 2 || !country.chars().all(|c| c.is_ascii_uppercase())
+            {
+                return Err(format!("Country '{}' is not an ISO 3166-1 alpha-2 code such as 'CN'.", country));
+            }
+        }
+        if let Some(avatar_url) = &self.avatar_url
+        {
+            if !avatar_url.starts_with("https://") && !avatar_url.starts_with("http://")
+            {
+                return Err(format!("Avatar URL '{}' must be an http or https URL.", avatar_url));
+            }
+        }
+        Ok(())
+    }
+}
+
+// names have 1 to MAX_NAME_LENGTH letters, digits, '_', '-' or '.'
+pub fn check_name(name: &str) -> Result<(), String>
+{
+    let length = name.chars().count();
+    if length == 0 || length > MAX_NAME_LENGTH
+    {
+        return Err(format!("User name must have 1 to {} characters.", MAX_NAME_LENGTH));
+    }
+    if !name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+    {
+        return Err(format!("User name '{}' may only contain letters, digits, '_', '-' and '.'.", name));
+    }
+    Ok(())
+}
+
+// whether another user has the name, names differing only in case are the same name
+pub fn name_taken(users: &[User], name: &str, except: Option<u32>) -> bool
+{
+    let name = name.to_lowercase();
+    return users.iter().any(|user| Some(user.id) != except && user.name.to_lowercase() == name);
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -13,4 +151,37 @@ pub struct GetUser
 {
     pub id: Option<u32>,
     pub name: String,
+}
+
+// body of PATCH /users/{id}, only the fields that are set are changed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PatchUser
+{
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub profile: Profile,
+}
+
+// a user with its role, as returned by GET /me and PUT /users/{id}/role
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Account
+{
+    #[serde(flatten)]
+    pub user: User,
+    pub role: config::Role,
+}
+
+// body of PUT /users/{id}/role
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PutRole
+{
+    pub role: config::Role,
+}
+
+// body of PUT /users/{id}/deactivated
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PutDeactivated
+{
+    pub deactivated: bool,
 }
\ No newline at end of file
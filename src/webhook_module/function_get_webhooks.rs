@@ -0,0 +1,14 @@
+use actix_web::{get, Responder, HttpResponse};
+
+use crate::webhook;
+
+use crate::WEBHOOK_DELIVERY_LIST;
+use crate::permission;
+
+// gets the log of the latest webhook deliveries, see webhook::MAX_LOGGED_DELIVERIES
+#[get("/webhooks/deliveries", wrap = "permission::Require(permission::Permission::administer)")]
+async fn get_webhooks_deliveries() -> impl Responder
+{
+    let lock_delivery_list: std::sync::MutexGuard<Vec<webhook::Delivery>> = WEBHOOK_DELIVERY_LIST.lock().unwrap();
+    return HttpResponse::Ok().json(lock_delivery_list.clone());
+}
\ No newline at end of file
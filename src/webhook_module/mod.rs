@@ -0,0 +1,2 @@
+pub mod webhook;
+pub mod function_get_webhooks;
\ No newline at end of file
@@ -0,0 +1,256 @@
+use std::sync::mpsc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::config;
+use crate::contest;
+use crate::job;
+
+use crate::WEBHOOK_SENDER;
+use crate::WEBHOOK_DELIVERY_LIST;
+use crate::WEBHOOK_DELIVERY_ID_COUNT;
+
+// number of attempts if the webhook does not say otherwise
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+// number of deliveries kept in the log, the oldest finished ones are dropped first
+const MAX_LOGGED_DELIVERIES: usize = 1000;
+
+// body of every webhook request
+// data is a job::ResponseContent for job events and a contest::Contest for contest events
+#[derive(Serialize, Debug, Clone)]
+pub struct Payload<'a, T: Serialize>
+{
+    pub event: &'a str,
+    pub time: DateTime<Utc>,
+    pub data: &'a T,
+}
+
+// one event sent to one webhook, kept in the delivery log
+#[derive(Serialize, Debug, Clone)]
+pub struct Delivery
+{
+    pub id: u32,
+    pub url: String,
+    pub event: String,
+    pub status: String, // "Pending", "Delivered" or "Failed"
+    pub attempts: u32,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    pub created_time: DateTime<Utc>,
+    pub updated_time: DateTime<Utc>,
+
+    #[serde(skip)]
+    body: String,
+    #[serde(skip)]
+    secret: String,
+    #[serde(skip)]
+    max_attempts: u32,
+    #[serde(skip)]
+    next_attempt: DateTime<Utc>,
+}
+
+// returns the value of the signature header for a body
+pub fn sign(secret: &str, body: &str) -> String
+{
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(body.as_bytes());
+    return format!("sha256={:x}", mac.finalize().into_bytes());
+}
+
+// stores the latest state of a delivery in the log
+fn log_delivery(delivery: &Delivery)
+{
+    let mut lock_delivery_list = WEBHOOK_DELIVERY_LIST.lock().unwrap();
+    for logged in lock_delivery_list.iter_mut()
+    {
+        if logged.id == delivery.id
+        {
+            *logged = delivery.clone();
+            return;
+        }
+    }
+    lock_delivery_list.push(delivery.clone());
+
+    if lock_delivery_list.len() > MAX_LOGGED_DELIVERIES
+    {
+        let index = lock_delivery_list.iter().position(|logged| logged.status != "Pending").unwrap_or(0);
+        lock_delivery_list.remove(index);
+    }
+}
+
+// sends a delivery once and updates its state
+fn attempt(client: &reqwest::blocking::Client, delivery: &mut Delivery)
+{
+    delivery.attempts += 1;
+    delivery.updated_time = Utc::now();
+
+    let response = client.post(&delivery.url)
+        .header("Content-Type", "application/json")
+        .header("X-OJ-Event", delivery.event.clone())
+        .header("X-OJ-Delivery", delivery.id.to_string())
+        .header("X-OJ-Signature", sign(&delivery.secret, &delivery.body))
+        .body(delivery.body.clone())
+        .timeout(Duration::from_secs(10))
+        .send();
+
+    match response
+    {
+        Ok(response) if response.status().is_success() =>
+        {
+            delivery.status = "Delivered".to_string();
+            delivery.response_status = Some(response.status().as_u16());
+            delivery.error = None;
+        }
+        Ok(response) =>
+        {
+            delivery.response_status = Some(response.status().as_u16());
+            delivery.error = Some(format!("HTTP {}", response.status()));
+        }
+        Err(err) =>
+        {
+            delivery.response_status = None;
+            delivery.error = Some(err.to_string());
+        }
+    }
+
+    if delivery.status != "Delivered"
+    {
+        if delivery.attempts >= delivery.max_attempts
+        {
+            delivery.status = "Failed".to_string();
+            log::warn!("Webhook delivery {} to {} failed", delivery.id, delivery.url);
+        }
+        else
+        {
+            // waits 1s, 2s, 4s, ... before trying again
+            let backoff = 1i64 << (delivery.attempts - 1).min(10);
+            delivery.next_attempt = Utc::now() + chrono::Duration::seconds(backoff);
+        }
+    }
+    log_delivery(delivery);
+}
+
+// runs in its own thread, sends each event to the matching webhooks and retries failed deliveries
+fn dispatch(webhooks: Vec<config::Webhook>, receiver: mpsc::Receiver<(String, String)>)
+{
+    let client = reqwest::blocking::Client::new();
+    let mut pending: Vec<Delivery> = vec![];
+
+    loop
+    {
+        // waits for new events until the next retry is due
+        let mut timeout = Duration::from_secs(1);
+        for delivery in &pending
+        {
+            let until = (delivery.next_attempt - Utc::now()).to_std().unwrap_or(Duration::ZERO);
+            if until < timeout {timeout = until;}
+        }
+
+        match receiver.recv_timeout(timeout)
+        {
+            Ok((event, body)) =>
+            {
+                for webhook in &webhooks
+                {
+                    if !webhook.events.is_empty() && !webhook.events.contains(&event) {continue;}
+
+                    let mut lock_delivery_id_count = WEBHOOK_DELIVERY_ID_COUNT.lock().unwrap();
+                    let delivery = Delivery
+                    {
+                        id: *lock_delivery_id_count,
+                        url: webhook.url.clone(),
+                        event: event.clone(),
+                        status: "Pending".to_string(),
+                        attempts: 0,
+                        response_status: None,
+                        error: None,
+                        created_time: Utc::now(),
+                        updated_time: Utc::now(),
+                        body: body.clone(),
+                        secret: webhook.secret.clone(),
+                        max_attempts: webhook.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+                        next_attempt: Utc::now(),
+                    };
+                    *lock_delivery_id_count += 1;
+                    log_delivery(&delivery);
+                    pending.push(delivery);
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+
+        // sends every delivery that is due
+        let now = Utc::now();
+        for delivery in pending.iter_mut()
+        {
+            if delivery.next_attempt <= now
+            {
+                attempt(&client, delivery);
+            }
+        }
+        pending.retain(|delivery| delivery.status == "Pending");
+    }
+}
+
+// starts the thread that delivers webhooks
+// nothing is sent if no webhooks are configured
+pub fn start(webhooks: Vec<config::Webhook>)
+{
+    if webhooks.is_empty() {return;}
+
+    let (sender, receiver) = mpsc::channel::<(String, String)>();
+    *WEBHOOK_SENDER.lock().unwrap() = Some(sender);
+    std::thread::spawn(move || dispatch(webhooks, receiver));
+}
+
+// queues an event for every webhook listening to it
+pub fn fire<T: Serialize>(event: &str, data: &T)
+{
+    let lock_sender = WEBHOOK_SENDER.lock().unwrap();
+    if let Some(sender) = lock_sender.as_ref()
+    {
+        let body = serde_json::to_string(&Payload { event, time: Utc::now(), data }).expect("error serializing webhook");
+        sender.send((event.to_string(), body)).ok();
+    }
+}
+
+// fires the events of a new job that just finished (rejudges fire "rejudge_finished" instead)
+// job_list holds the accepted jobs of the problem in the contest, or more, to check whether this is the first accepted one
+pub fn fire_job_finished(content: &job::ResponseContent, job_list: &Vec<job::ResponseContent>)
+{
+    fire("job_finished", content);
+
+    if content.result != job::PossibleResult::Accepted {return;}
+    for other in job_list.iter()
+    {
+        if other.id != content.id && other.result == job::PossibleResult::Accepted &&
+        other.submission.contest_id == content.submission.contest_id &&
+        other.submission.problem_id == content.submission.problem_id
+        {
+            return;
+        }
+    }
+    fire("first_accept", content);
+}
+
+// fires the events of contests that started or ended between two checks
+pub fn fire_contest_times(contest_list: &Vec<contest::Contest>, last_check: DateTime<Utc>, now: DateTime<Utc>)
+{
+    for contest in contest_list.iter()
+    {
+        if contest.id == 0 {continue;}
+        if last_check < contest.from && contest.from <= now
+        {
+            fire("contest_started", contest);
+        }
+        if last_check < contest.to && contest.to <= now
+        {
+            fire("contest_ended", contest);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,42 @@
+use actix_web::{get, Responder, HttpResponse, web};
+
+use crate::job;
+use crate::worker;
+use crate::permission;
+
+use crate::WORKER_LIST;
+use crate::DATA_FILES;
+
+// gets list of all registered workers
+#[get("/workers", wrap = "permission::Require(permission::Permission::administer)")]
+async fn get_workers() -> impl Responder
+{
+    let lock_worker_list: std::sync::MutexGuard<Vec<worker::Worker>> = WORKER_LIST.lock().unwrap();
+    return HttpResponse::Ok().json(lock_worker_list.clone());
+}
+
+// downloads a test data file given its checksum
+// only files that were sent to a worker in an assignment can be downloaded
+#[get("/workers/data/{checksum}", wrap = "permission::Require(permission::Permission::judge)")]
+async fn get_workers_data(checksum: web::Path<String>) -> impl Responder
+{
+    let path = match DATA_FILES.lock().unwrap().get(&checksum.to_string())
+    {
+        Some(path) => path.clone(),
+        None =>
+        {
+            return HttpResponse::NotFound().json(job::Error::
+                new(3, "ERR_NOT_FOUND".to_string(), format!("Data {} not found.", checksum.to_owned()).to_string()));
+        }
+    };
+
+    match std::fs::read(&path)
+    {
+        Ok(bytes) => return HttpResponse::Ok().content_type("application/octet-stream").body(bytes),
+        Err(_) =>
+        {
+            return HttpResponse::InternalServerError().json(job::Error::
+                new(6, "ERR_INTERNAL".to_string(), format!("Data {} could not be read.", checksum.to_owned()).to_string()));
+        }
+    }
+}
\ No newline at end of file
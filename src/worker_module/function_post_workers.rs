@@ -0,0 +1,374 @@
+use actix_web::{post, Responder, HttpResponse, web};
+use actix_web::http::StatusCode;
+use chrono::Utc;
+use std::sync::Mutex;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::job;
+use crate::config;
+use crate::worker;
+use crate::events;
+use crate::storage;
+use crate::function_post_jobs;
+use crate::audit;
+use crate::permission;
+
+use crate::JOB_QUEUE;
+
+use crate::WORKER_LIST;
+use crate::WORKER_ID_COUNT;
+use crate::DATA_FILES;
+
+// registers a new judge worker
+#[post("/workers", wrap = "permission::Require(permission::Permission::judge)")]
+async fn post_workers(body: web::Json<worker::RegisterWorker>) -> impl Responder
+{
+    let mut lock_worker_list: std::sync::MutexGuard<Vec<worker::Worker>> = WORKER_LIST.lock().unwrap();
+    let mut lock_worker_id_count = WORKER_ID_COUNT.lock().unwrap();
+
+    let worker = worker::Worker
+    {
+        id: *lock_worker_id_count,
+        name: body.name.clone(),
+        registered_time: Utc::now(),
+        last_heartbeat: Utc::now(),
+        alive: true,
+        job: None,
+    };
+    lock_worker_list.push(worker.clone());
+    *lock_worker_id_count += 1;
+
+    log::info!("Worker {} ({}) registered", worker.id, worker.name);
+    return HttpResponse::Ok().json(worker);
+}
+
+// keeps a worker alive
+// dead or unknown workers get an error and have to register again
+#[post("/workers/{workerId}/heartbeat", wrap = "permission::Require(permission::Permission::judge)")]
+#[allow(non_snake_case)]
+async fn post_workers_workerId_heartbeat(workerId: web::Path<u32>) -> impl Responder
+{
+    let mut lock_worker_list: std::sync::MutexGuard<Vec<worker::Worker>> = WORKER_LIST.lock().unwrap();
+    for worker in lock_worker_list.iter_mut()
+    {
+        if worker.id == workerId.to_owned() && worker.alive
+        {
+            worker.last_heartbeat = Utc::now();
+            return HttpResponse::Ok().json(worker.clone());
+        }
+    }
+    return HttpResponse::NotFound().json(job::Error::
+        new(3, "ERR_NOT_FOUND".to_string(), format!("Worker {} not found.", workerId.to_owned()).to_string()));
+}
+
+// hashes the test data of every case of a problem
+// the files are remembered so that workers can download them by checksum
+// returns None if any of the files cannot be read
+fn register_data_files(problem: &config::Problem) -> Option<Vec<worker::DataFile>>
+{
+    let mut lock_data_files = DATA_FILES.lock().unwrap();
+    let mut data: Vec<worker::DataFile> = vec![];
+    for case in &problem.cases
+    {
+        let input_checksum = worker::checksum(&std::fs::read(&case.input_file).ok()?);
+        let answer_checksum = worker::checksum(&std::fs::read(&case.answer_file).ok()?);
+
+        lock_data_files.insert(input_checksum.clone(), case.input_file.clone());
+        lock_data_files.insert(answer_checksum.clone(), case.answer_file.clone());
+
+        data.push(worker::DataFile { input_checksum, answer_checksum });
+    }
+    return Some(data);
+}
+
+// hashes the arguments of the special judge of a problem that are files, so that workers can download the checker too
+// other arguments, such as the interpreter, are expected to be installed on the worker like the compilers
+fn register_checker_files(problem: &config::Problem) -> Vec<Option<String>>
+{
+    let mut lock_data_files = DATA_FILES.lock().unwrap();
+    let arguments = problem.misc.special_judge.clone().unwrap_or_default();
+    let mut checker_data: Vec<Option<String>> = vec![];
+    for argument in arguments.iter()
+    {
+        let bytes = match std::path::Path::new(argument).is_file()
+        {
+            true => std::fs::read(argument).ok(),
+            false => None,
+        };
+        checker_data.push(bytes.map(|bytes| {
+            let checksum = worker::checksum(&bytes);
+            lock_data_files.insert(checksum.clone(), argument.clone());
+            checksum
+        }));
+    }
+    return checker_data;
+}
+
+// marks a queued job as running and collects everything a worker needs to judge it
+// returns None if the job does not exist anymore or its data is missing
+fn build_assignment(queued: &worker::QueuedJob, config: &config::Config, storage: &dyn storage::Storage)
+    -> Result<Option<worker::Assignment>, storage::Error>
+{
+    let found = storage.jobs().iter().find(|content| content.id == queued.job_id).cloned();
+    let mut content = match found
+    {
+        Some(content) => content,
+        None => return Ok(None),
+    };
+
+    let problem_index = function_post_jobs::get_problem_index(&config.problems, content.submission.problem_id);
+    let language_index = function_post_jobs::get_language_index(&config.languages, content.submission.language.clone());
+    let problem = config.problems[problem_index].clone();
+
+    let mut assignment: Option<worker::Assignment> = None;
+    content.updated_time = Utc::now();
+    match register_data_files(&problem)
+    {
+        Some(data) =>
+        {
+            let checker_data = register_checker_files(&problem);
+            content.state = "Running".to_string();
+            content.result = job::PossibleResult::Running;
+            assignment = Some(worker::Assignment
+            {
+                job_id: content.id,
+                submission: content.submission.clone(),
+                problem,
+                language: config.languages[language_index].clone(),
+                data,
+                checker_data,
+            });
+        }
+        // the job cannot be judged anywhere without its data
+        None =>
+        {
+            log::warn!("Missing data for job {}", content.id);
+            content.state = "Finished".to_string();
+            content.result = job::PossibleResult::SystemError;
+        }
+    }
+    storage.save_job(&content)?;
+    storage.keep_job(&content);
+    events::publish_state(&content);
+    return Ok(assignment);
+}
+
+// gives the worker the next queued job, if it is alive and not judging one already
+// returns None if there is nothing to judge yet
+fn take_assignment(worker_id: u32, config: &config::Config, storage: &dyn storage::Storage)
+    -> Result<Option<worker::Assignment>, (StatusCode, job::Error)>
+{
+    let _lock = storage.lock();
+    let mut lock_worker_list: std::sync::MutexGuard<Vec<worker::Worker>> = WORKER_LIST.lock().unwrap();
+
+    let worker = match lock_worker_list.iter_mut().find(|worker| worker.id == worker_id && worker.alive)
+    {
+        Some(worker) => worker,
+        None => return Err((StatusCode::NOT_FOUND, job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), format!("Worker {} not found.", worker_id).to_string()))),
+    };
+    worker.last_heartbeat = Utc::now();
+
+    // a worker only judges one job at a time
+    if worker.job.is_some() {return Ok(None);}
+
+    let mut lock_job_queue = JOB_QUEUE.lock().unwrap();
+    while let Some(queued) = lock_job_queue.pop_front()
+    {
+        match build_assignment(&queued, config, storage)
+        {
+            Ok(Some(assignment)) =>
+            {
+                log::info!("Job {} assigned to worker {}", queued.job_id, worker.id);
+                worker.job = Some(queued);
+                return Ok(Some(assignment));
+            }
+            Ok(None) => {}
+            // the job stays queued for the next poll
+            Err(error) =>
+            {
+                lock_job_queue.push_front(queued);
+                return Err(storage::internal(error));
+            }
+        }
+    }
+    return Ok(None);
+}
+
+// long poll used by workers to get a job
+// waits up to poll_timeout seconds for a job to be queued, otherwise responds with no content
+// taking a job waits for other writers, so each attempt runs on the blocking thread pool
+#[post("/workers/{workerId}/poll", wrap = "permission::Require(permission::Permission::judge)")]
+#[allow(non_snake_case)]
+async fn post_workers_workerId_poll(workerId: web::Path<u32>, data_config: web::Data<Arc<Mutex<config::Config>>>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let worker_id = workerId.into_inner();
+    let config = data_config.lock().unwrap().clone();
+    let deadline = Utc::now() + chrono::Duration::seconds(config.workers.poll_timeout as i64);
+
+    loop
+    {
+        let attempt_config = config.clone();
+        let attempt_storage = data_storage.clone();
+        match web::block(move || take_assignment(worker_id, &attempt_config, attempt_storage.get_ref())).await
+        {
+            Ok(Ok(Some(assignment))) => return HttpResponse::Ok().json(assignment),
+            Ok(Ok(None)) => {}
+            Ok(Err((status, error))) => return HttpResponse::build(status).json(error),
+            Err(_) => return HttpResponse::InternalServerError().json(job::Error::
+                new(6, "ERR_INTERNAL".to_string(), "Job could not be assigned.".to_string())),
+        }
+
+        if Utc::now() >= deadline
+        {
+            return HttpResponse::NoContent().finish();
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+// returns the job a worker is currently judging, if it matches the given job id
+fn get_assigned_job(worker_id: u32, job_id: u32) -> Option<worker::QueuedJob>
+{
+    let mut lock_worker_list: std::sync::MutexGuard<Vec<worker::Worker>> = WORKER_LIST.lock().unwrap();
+    for worker in lock_worker_list.iter_mut()
+    {
+        if worker.id == worker_id && worker.alive
+        {
+            if let Some(queued) = worker.job.clone()
+            {
+                if queued.job_id == job_id
+                {
+                    worker.last_heartbeat = Utc::now();
+                    return Some(queued);
+                }
+            }
+        }
+    }
+    return None;
+}
+
+// stores the result of a single case of a job judged by a worker
+fn save_progress(worker_id: u32, job_id: u32, case: job::Case, storage: &dyn storage::Storage)
+    -> Result<job::ResponseContent, (StatusCode, job::Error)>
+{
+    let _lock = storage.lock();
+
+    if get_assigned_job(worker_id, job_id).is_none()
+    {
+        return Err((StatusCode::NOT_FOUND, job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} is not assigned to worker {}.", job_id, worker_id).to_string())));
+    }
+
+    let found = storage.jobs().iter().find(|content| content.id == job_id).cloned();
+    let mut content = match found
+    {
+        Some(content) => content,
+        None => return Err((StatusCode::NOT_FOUND, job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} not found.", job_id).to_string()))),
+    };
+    for running_case in content.cases.iter_mut()
+    {
+        if running_case.id == case.id
+        {
+            *running_case = case.clone();
+        }
+    }
+    content.updated_time = Utc::now();
+    storage.save_job(&content).map_err(storage::internal)?;
+    storage.keep_job(&content);
+    events::publish(events::JobEvent::from_job(&content, Some(case)));
+    return Ok(content);
+}
+
+// reports the result of a single case while the job is still running
+#[post("/workers/{workerId}/jobs/{jobId}/progress", wrap = "permission::Require(permission::Permission::judge)")]
+#[allow(non_snake_case)]
+async fn post_workers_workerId_jobs_jobId_progress(path: web::Path<(u32, u32)>, body: web::Json<job::Case>,
+    data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let (worker_id, job_id) = path.into_inner();
+    match web::block(move || save_progress(worker_id, job_id, body.into_inner(), data_storage.get_ref())).await
+    {
+        Ok(Ok(content)) => HttpResponse::Ok().json(content),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), format!("Progress of job {} could not be saved.", job_id).to_string())),
+    }
+}
+
+// stores the final result of a job judged by a worker, which is then free to take another job
+fn save_result(worker_id: u32, job_id: u32, report: worker::JobReport, config: &config::Config, storage: &dyn storage::Storage)
+    -> Result<job::ResponseContent, (StatusCode, job::Error)>
+{
+    let _lock = storage.lock();
+
+    let queued = match get_assigned_job(worker_id, job_id)
+    {
+        Some(queued) => queued,
+        None =>
+        {
+            return Err((StatusCode::NOT_FOUND, job::Error::
+                new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} is not assigned to worker {}.", job_id, worker_id).to_string())));
+        }
+    };
+
+    let output = function_post_jobs::finish_queued_job(&queued, report, config, &audit::worker_actor(worker_id), storage);
+
+    // the worker is free to take another job
+    for worker in WORKER_LIST.lock().unwrap().iter_mut()
+    {
+        if worker.id == worker_id
+        {
+            worker.job = None;
+        }
+    }
+
+    match output
+    {
+        Ok(Some(content)) => return Ok(content),
+        Ok(None) => return Err((StatusCode::NOT_FOUND, job::Error::
+            new(3, "ERR_NOT_FOUND".to_string(), format!("Job {} not found.", job_id).to_string()))),
+        Err(error) => return Err(storage::internal(error)),
+    }
+}
+
+// reports the final result of a job
+#[post("/workers/{workerId}/jobs/{jobId}/result", wrap = "permission::Require(permission::Permission::judge)")]
+#[allow(non_snake_case)]
+async fn post_workers_workerId_jobs_jobId_result(path: web::Path<(u32, u32)>, body: web::Json<worker::JobReport>,
+    data_config: web::Data<Arc<Mutex<config::Config>>>, data_storage: web::Data<dyn storage::Storage>) -> impl Responder
+{
+    let (worker_id, job_id) = path.into_inner();
+    let config = data_config.lock().unwrap().clone();
+    match web::block(move || save_result(worker_id, job_id, body.into_inner(), &config, data_storage.get_ref())).await
+    {
+        Ok(Ok(content)) => HttpResponse::Ok().json(content),
+        Ok(Err((status, error))) => HttpResponse::build(status).json(error),
+        Err(_) => HttpResponse::InternalServerError().json(job::Error::
+            new(6, "ERR_INTERNAL".to_string(), format!("Result of job {} could not be saved.", job_id).to_string())),
+    }
+}
+
+// marks workers that stopped sending heartbeats as dead
+// and puts the jobs they were judging back into the queue
+pub fn reap_workers(config: &config::Config, storage: &dyn storage::Storage)
+{
+    let timeout = chrono::Duration::seconds(config.workers.heartbeat_timeout as i64);
+
+    let mut lock_worker_list: std::sync::MutexGuard<Vec<worker::Worker>> = WORKER_LIST.lock().unwrap();
+    for worker in lock_worker_list.iter_mut()
+    {
+        if worker.alive && Utc::now() - worker.last_heartbeat > timeout
+        {
+            log::warn!("Worker {} ({}) stopped sending heartbeats", worker.id, worker.name);
+            worker.alive = false;
+            if let Some(queued) = worker.job.take()
+            {
+                function_post_jobs::requeue_job(queued, config, storage);
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,3 @@
+pub mod worker;
+pub mod function_get_workers;
+pub mod function_post_workers;
\ No newline at end of file
@@ -0,0 +1,72 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use sha2::{Sha256, Digest};
+
+use crate::config;
+use crate::job;
+use crate::post_job;
+
+// a job waiting to be judged (or being judged) by a worker
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueuedJob
+{
+    pub job_id: u32,
+    pub is_rejudge: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Worker
+{
+    pub id: u32,
+    pub name: String,
+    pub registered_time: DateTime<Utc>,
+    pub last_heartbeat: DateTime<Utc>,
+    pub alive: bool,
+    pub job: Option<QueuedJob>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RegisterWorker
+{
+    pub name: String,
+}
+
+// checksums of the test data of a case
+// the worker downloads each file from /workers/data/{checksum}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DataFile
+{
+    pub input_checksum: String,
+    pub answer_checksum: String,
+}
+
+// everything a worker needs to judge a job
+// data[i] belongs to problem.cases[i]
+// checker_data[i] is the checksum of problem.misc.special_judge[i] when that argument is a file on the server,
+// such as the checker script, the worker downloads it like the test data and uses its copy instead
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Assignment
+{
+    pub job_id: u32,
+    pub submission: post_job::PostJob,
+    pub problem: config::Problem,
+    pub language: config::Language,
+    pub data: Vec<DataFile>,
+    #[serde(default)]
+    pub checker_data: Vec<Option<String>>,
+}
+
+// final result of a job sent back by the worker
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobReport
+{
+    pub result: job::PossibleResult,
+    pub score: f32,
+    pub cases: Vec<job::Case>,
+}
+
+// returns the sha256 checksum of the contents of a file, in hex
+pub fn checksum(bytes: &[u8]) -> String
+{
+    return format!("{:x}", Sha256::digest(bytes));
+}
\ No newline at end of file
@@ -4,6 +4,7 @@ use reqwest::blocking::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
 use serde_json;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::env::consts::EXE_EXTENSION;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
@@ -79,6 +80,8 @@ struct TestRequest {
 struct TestResponse {
     status: u16,
     content: Value,
+    #[serde(default)]
+    headers: BTreeMap<String, String>, // headers the response must have, "*" accepts any value
 }
 
 fn _default_true() -> bool {
@@ -108,6 +111,12 @@ struct HttpComm {
     restart_server: bool, // restart server before sending request
     #[serde(default = "_default_false")]
     skip_body: bool, // do not check body
+    #[serde(default)]
+    delay: u64, // milliseconds to wait before sending the request, e.g. for background work
+    #[serde(default)]
+    headers: BTreeMap<String, String>, // headers of the request
+    #[serde(default)]
+    capture: BTreeMap<String, String>, // values of the response body to remember, by JSON pointer
 }
 
 pub struct TestCase {
@@ -119,6 +128,7 @@ pub struct TestCase {
     stdout_file: PathBuf,
     stderr_file: PathBuf,
     http_file: PathBuf,
+    captured: BTreeMap<String, Value>, // "{name}" in later requests and responses is replaced by the value
 }
 
 impl TestCase {
@@ -151,6 +161,50 @@ impl TestCase {
             stdout_file,
             stderr_file,
             http_file,
+            captured: BTreeMap::new(),
+        }
+    }
+
+    // passes more arguments to the server, e.g. to use SQL storage
+    pub fn with_arguments(mut self, arguments: &[&str]) -> Self {
+        self.arguments
+            .extend(arguments.iter().map(|argument| argument.to_string()));
+        self
+    }
+
+    fn substitute_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (name, value) in self.captured.iter() {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            text = text.replace(&format!("{{{}}}", name), &value);
+        }
+        text
+    }
+
+    // a string that is only "{name}" becomes the captured value itself
+    fn substitute(&self, value: &Value) -> Value {
+        match value {
+            Value::String(text) => match self
+                .captured
+                .iter()
+                .find(|(name, _)| *text == format!("{{{}}}", name))
+            {
+                Some((_, captured)) => captured.clone(),
+                None => Value::String(self.substitute_text(text)),
+            },
+            Value::Array(values) => {
+                Value::Array(values.iter().map(|value| self.substitute(value)).collect())
+            }
+            Value::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.substitute(value)))
+                    .collect(),
+            ),
+            value => value.clone(),
         }
     }
 
@@ -271,6 +325,15 @@ impl TestCase {
     }
 
     fn send_request_and_compare_response(&mut self, c: &HttpComm) -> Value {
+        let mut c = c.clone();
+        c.request.path = self.substitute_text(&c.request.path);
+        c.request.content = self.substitute(&c.request.content);
+        c.response.content = self.substitute(&c.response.content);
+        for value in c.headers.values_mut() {
+            *value = self.substitute_text(value);
+        }
+        let c = &c;
+
         if c.restart_server {
             self.kill_server();
             // remove --flush-data before restarting server, then add it back
@@ -285,6 +348,8 @@ impl TestCase {
             self.arguments = old_arguments;
         }
 
+        std::thread::sleep(Duration::from_millis(c.delay));
+
         let url = format!("{}/{}", &self.prefix, &c.request.path);
         let method =
             reqwest::Method::from_bytes(&c.request.method.to_uppercase().as_bytes()).unwrap();
@@ -298,12 +363,16 @@ impl TestCase {
             } else {
                 request = request.json(&c.request.content);
             }
+            for (name, value) in c.headers.iter() {
+                request = request.header(name.as_str(), value.as_str());
+            }
 
             let (mut resp, mut http_file) = self
                 .log_and_send(request)
                 .expect(format!("case {} incorrect: HTTP request failed", self.name).as_str());
 
             let code = resp.status().as_u16();
+            let headers = resp.headers().clone();
 
             let value = if c.skip_body {
                 resp.copy_to(&mut http_file).ok();
@@ -328,6 +397,17 @@ impl TestCase {
                 "case {} incorrect: wrong status code",
                 self.name
             );
+            for (name, expected) in c.response.headers.iter() {
+                let value = headers
+                    .get(name.as_str())
+                    .map(|value| value.to_str().unwrap());
+                assert!(
+                    value.is_some() && (expected == "*" || value == Some(expected.as_str())),
+                    "case {} incorrect: wrong header {}",
+                    self.name,
+                    name
+                );
+            }
 
             value
         };
@@ -378,6 +458,12 @@ impl TestCase {
                 self.name, error
             );
         }
+        for (name, pointer) in c.capture.iter() {
+            let value = body.pointer(pointer).cloned().expect(
+                format!("case {} incorrect: response has no {}", self.name, pointer).as_str(),
+            );
+            self.captured.insert(name.clone(), value);
+        }
         body
     }
 
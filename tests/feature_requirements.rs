@@ -0,0 +1,344 @@
+mod common;
+use common::TestCase;
+
+// a path for the SQL database of a case, removing what an earlier run left there
+fn fresh_database(name: &str) -> String {
+    let path = format!("target/{}.db", name);
+    for suffix in ["", "-wal", "-shm"] {
+        std::fs::remove_file(format!("{}{}", path, suffix)).ok();
+    }
+    path
+}
+
+#[test]
+fn test_feat_026_distributed_judging() {
+    // check the protocol of judge workers in distributed mode
+    // 1. register a worker, which has nothing to judge at first
+    // 2. submit a job of a special judge problem, the worker gets it with the checksums of its data and of its checker
+    // 3. download the files, report the progress and the result of the job
+    let results = TestCase::read("feat_026_distributed_judging")
+        .with_arguments(&["--distributed"])
+        .run();
+    let checker_data = results[3]["checker_data"].as_array().unwrap();
+    assert!(
+        checker_data[0].is_null() && checker_data[1].is_string(),
+        "case feat_026_distributed_judging incorrect: the checker script is not sent to the worker"
+    );
+}
+
+#[test]
+fn test_feat_026_queued_submission_limit() {
+    // check the submission limit of contests in distributed mode
+    // 1. submit jobs in a contest with no worker, so that they stay queued
+    // 2. the queued jobs count for the limit, the job after it is refused
+    TestCase::read("feat_026_queued_submission_limit")
+        .with_arguments(&["--distributed"])
+        .run();
+}
+
+#[test]
+fn test_feat_027_job_events() {
+    // check the server-sent events of jobs
+    // 1. the events of a finished job are streamed as text/event-stream, and the stream ends after its state
+    // 2. the events of jobs, users and contests that do not exist are not found
+    TestCase::read("feat_027_job_events").run();
+}
+
+#[test]
+fn test_feat_028_webhooks() {
+    // check the delivery log of webhooks
+    // 1. submit two accepted jobs, only the first one fires first_accept
+    // 2. the webhook cannot be reached, so its only attempt fails
+    let results = TestCase::read("feat_028_webhooks").run();
+    assert_eq!(
+        results[2].as_array().unwrap().len(),
+        1,
+        "case feat_028_webhooks incorrect: wrong number of deliveries"
+    );
+    assert!(
+        results[2][0]["error"].is_string(),
+        "case feat_028_webhooks incorrect: the failed delivery has no error"
+    );
+}
+
+#[test]
+fn test_feat_029_custom_runs() {
+    // check custom runs
+    // 1. run code on custom input, code that does not compile is reported
+    // 2. unknown languages and problems are not found
+    // 3. the output is truncated at the limit, runs are stopped at the time limit and cannot allocate over the memory limit
+    // 4. runs have their own rate limits, which do not apply to jobs
+    TestCase::read("feat_029_custom_runs").run();
+}
+
+#[test]
+fn test_feat_029_job_memory_limit() {
+    // check the memory limit of judged jobs
+    // 1. a job using less memory than the limit is accepted
+    // 2. a job using more memory than the limit is Memory Limit Exceeded
+    TestCase::read("feat_029_job_memory_limit").run();
+}
+
+#[test]
+fn test_feat_030_sample_cases() {
+    // check sample cases
+    // 1. only the sample cases of a problem can be downloaded
+    // 2. a job gets the input, the answer and its output on the sample case, but not on the hidden one
+    let results = TestCase::read("feat_030_sample_cases").run();
+    assert!(
+        results[2]["cases"][2].get("feedback").is_none(),
+        "case feat_030_sample_cases incorrect: a hidden case has feedback"
+    );
+}
+
+#[test]
+fn test_feat_031_sql_storage() {
+    // check that users, contests and jobs are kept in the SQL database
+    // 1. create a user, a contest and a job
+    // 2. restart the server, everything is still there
+    // 3. new users and jobs get the next ids
+    let database = fresh_database("feat_031_sql_storage");
+    TestCase::read("feat_031_sql_storage")
+        .with_arguments(&["--storage", "--database", &database])
+        .run();
+}
+
+#[test]
+fn test_feat_032_normalized_tables() {
+    // check that contests, their users, the results and the cases of jobs are kept in the SQL database
+    // 1. judge jobs of two users in a contest, restart, the ranklist and the cases of the jobs are the same
+    // 2. remove a user from the contest, restart, the user is not in the contest anymore
+    let database = fresh_database("feat_032_normalized_tables");
+    let results = TestCase::read("feat_032_normalized_tables")
+        .with_arguments(&["--storage", "--database", &database])
+        .run();
+    assert_eq!(
+        results[9].as_array().unwrap().len(),
+        1,
+        "case feat_032_normalized_tables incorrect: a removed user is in the ranklist"
+    );
+}
+
+#[test]
+fn test_feat_034_storage_backends() {
+    // check that the memory and the SQL storage behave the same
+    // 1. register a user with a password and a role, and log in
+    // 2. submit and rejudge a job, list the jobs and the audit log
+    TestCase::read("feat_034_storage_backends").run();
+    let database = fresh_database("feat_034_storage_backends");
+    TestCase::read("feat_034_storage_backends")
+        .with_arguments(&["--storage", "--database", &database])
+        .run();
+}
+
+#[test]
+fn test_feat_035_database_config() {
+    // check that the SQL database is kept where the config file says
+    // 1. judge a job, restart, the job is read back from the database
+    // 2. the database uses write-ahead logging
+    let database = fresh_database("feat_035_database_config");
+    TestCase::read("feat_035_database_config")
+        .with_arguments(&["--storage"])
+        .run();
+    assert!(
+        std::path::Path::new(&database).is_file()
+            && std::path::Path::new(&format!("{}-wal", database)).is_file(),
+        "case feat_035_database_config incorrect: the database is not at the configured path"
+    );
+}
+
+#[test]
+fn test_feat_036_dynamic_ranking_times() {
+    // check that the best case times of dynamic ranking are kept in the SQL database
+    // 1. judge a slow and a fast job in a contest, restart, the fast one still has the full score
+    // 2. judge a faster job after the restart, it takes the lead
+    let database = fresh_database("feat_036_dynamic_ranking_times");
+    TestCase::read("feat_036_dynamic_ranking_times")
+        .with_arguments(&["--storage", "--database", &database])
+        .run();
+}
+
+#[test]
+fn test_feat_037_archives() {
+    // check the export and the import of archives
+    // 1. export a user with a password and a job
+    // 2. create another user, then import the archive, which replaces everything and keeps the password
+    // 3. import the archive again with merge, the job is added with a new id for the same user
+    // 4. archives of unknown versions are rejected
+    let results = TestCase::read("feat_037_archives").run();
+    assert_eq!(
+        results[5].as_array().unwrap().len(),
+        2,
+        "case feat_037_archives incorrect: users created after the export are kept"
+    );
+}
+
+#[test]
+fn test_feat_038_job_recovery() {
+    // check what happens at startup to the jobs that were not finished when the server stopped
+    // 1. queue a job for the workers and restart, the job is queued again and a worker gets it
+    // 2. with the system_error recovery, the job is finished as System Error instead
+    // 3. new jobs get the next ids in both cases
+    let database = fresh_database("feat_038_requeue");
+    TestCase::read("feat_038_requeue")
+        .with_arguments(&["--distributed", "--storage", "--database", &database])
+        .run();
+    let database = fresh_database("feat_038_system_error");
+    TestCase::read("feat_038_system_error")
+        .with_arguments(&["--distributed", "--storage", "--database", &database])
+        .run();
+}
+
+#[test]
+fn test_feat_039_job_queries() {
+    // check the pages, the order and the filters of the job list in the SQL database
+    // 1. judge four jobs of two users
+    // 2. list them in pages in both orders, and filter them by user, result and time
+    // 3. the source code can be left out
+    let database = fresh_database("feat_039_job_queries");
+    let results = TestCase::read("feat_039_job_queries")
+        .with_arguments(&["--storage", "--database", &database])
+        .run();
+    for (index, count) in [
+        (5, 2),
+        (6, 2),
+        (7, 2),
+        (8, 2),
+        (9, 1),
+        (10, 2),
+        (11, 1),
+        (12, 2),
+    ] {
+        assert_eq!(
+            results[index].as_array().unwrap().len(),
+            count,
+            "case feat_039_job_queries incorrect: wrong number of jobs"
+        );
+    }
+    assert!(
+        results[13][0]["submission"].get("source_code").is_none(),
+        "case feat_039_job_queries incorrect: the source code is not left out"
+    );
+}
+
+#[test]
+fn test_feat_040_audit_replay() {
+    // check the replay of the audit log at a given time
+    // 1. create a user and a contest, and judge a job
+    // 2. rename the user, change the contest and rejudge the job
+    // 3. the replay at a time between them has the old values, the replay without a time the new ones
+    let results = TestCase::read("feat_040_audit_replay").run();
+    assert_ne!(
+        results[2]["updated_time"], results[6]["updated_time"],
+        "case feat_040_audit_replay incorrect: the rejudged job was not changed"
+    );
+}
+
+#[test]
+fn test_feat_041_sessions() {
+    // check registration, sessions and the attribution of jobs
+    // 1. register a user, log in with a wrong and a right password
+    // 2. read /me with the bearer token
+    // 3. submit a job anonymously and with the token, the job belongs to the user of the token
+    // 4. log out, the token is not accepted anymore
+    TestCase::read("feat_041_sessions").run();
+}
+
+#[test]
+fn test_feat_042_roles() {
+    // check that routes are guarded by the role of the caller
+    // 1. register and log in a contestant, who may not create users
+    // 2. make the user an observer, who may read jobs but not submit them
+    // 3. without a session, requests act as an observer when the config has auth without anonymous_role
+    TestCase::read("feat_042_roles").run();
+    TestCase::read("feat_042_default_role").run();
+}
+
+#[test]
+fn test_feat_043_api_keys() {
+    // check scoped API keys
+    // 1. create a key that may only submit, and submit a job with it for its user
+    // 2. the key may not read jobs, and unknown keys are rejected
+    // 3. revoke the key, it is not accepted anymore
+    TestCase::read("feat_043_api_keys").run();
+}
+
+#[test]
+fn test_feat_044_profiles() {
+    // check user profiles
+    // 1. a user changes its own profile, invalid fields are rejected and other users may not change it
+    // 2. emails are only shown to the user itself and to those managing users
+    // 3. names are unique regardless of case, but a user may be renamed to another case of its own name
+    let results = TestCase::read("feat_044_profiles").run();
+    assert!(
+        results[7].get("email").is_none(),
+        "case feat_044_profiles incorrect: the email of a user is shown to another user"
+    );
+}
+
+#[test]
+fn test_feat_045_teams() {
+    // check contests with teams
+    // 1. register two teams for a contest, the jobs of a member count for its team in the ranklist
+    // 2. a user may not be in two teams of the same contest
+    // 3. move a user to the other team, its jobs then count for the new team
+    TestCase::read("feat_045_teams").run();
+}
+
+#[test]
+fn test_feat_046_user_stats() {
+    // check the statistics of a user
+    // 1. submit an accepted job in a contest and a wrong one outside of it
+    // 2. check the counts, the solved problems and the contest history
+    TestCase::read("feat_046_user_stats").run();
+}
+
+#[test]
+fn test_feat_047_user_import() {
+    // check the import of users
+    // 1. import users with a team, a password and a role into a contest
+    // 2. only generated passwords are sent back, and the users can log in with their passwords
+    // 3. nothing is imported if a row is invalid
+    let results = TestCase::read("feat_047_user_import").run();
+    let users = results[1]["users"].as_array().unwrap();
+    assert!(
+        users[0].get("password").is_none() && users[1]["password"].is_string(),
+        "case feat_047_user_import incorrect: wrong passwords in the response"
+    );
+}
+
+#[test]
+fn test_feat_048_audit_redaction() {
+    // check that deleted users are redacted from the audit log
+    // 1. create a user and give it a profile
+    // 2. delete the user
+    // 3. list the audit log and replay it, the entries from before the deletion show the placeholder
+    let results = TestCase::read("feat_048_audit_redaction").run();
+    for entries in [&results[3], &results[4]] {
+        for entry in entries.as_array().unwrap() {
+            let user = entry["data"]["user"].as_object().unwrap();
+            assert!(
+                !user.contains_key("email") && !user.contains_key("display_name"),
+                "case feat_048_audit_redaction incorrect: the profile of a deleted user is listed"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_feat_049_rate_limits() {
+    // check the rate limits of submissions
+    // 1. a rejected job does not use up the limit of its user
+    // 2. the job after the limit is refused with Retry-After
+    // 3. other users have their own limits
+    TestCase::read("feat_049_rate_limits").run();
+}
+
+#[test]
+fn test_feat_050_quotas() {
+    // check the judging quotas
+    // 1. a user may submit one job in each contest and two jobs a day
+    // 2. the usage of the user is listed by contest
+    // 3. after a reset, the user may submit again
+    TestCase::read("feat_050_quotas").run();
+}
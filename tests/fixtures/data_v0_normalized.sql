@@ -0,0 +1,100 @@
+-- data.db written with normalized tables, before schema_version existed
+CREATE TABLE user_id_count (
+             key TEXT PRIMARY KEY,
+             value TEXT NOT NULL
+         );
+INSERT INTO user_id_count VALUES('USER_ID_COUNT','1');
+CREATE TABLE user_list (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL
+         );
+INSERT INTO user_list VALUES(0,'root');
+CREATE TABLE job_id_count (
+             key TEXT PRIMARY KEY,
+             value TEXT NOT NULL
+         );
+INSERT INTO job_id_count VALUES('JOB_ID_COUNT','1');
+CREATE TABLE contests (
+             id INTEGER PRIMARY KEY,
+             name TEXT NOT NULL,
+             from_time TEXT NOT NULL,
+             to_time TEXT NOT NULL,
+             submission_limit INTEGER NOT NULL
+         );
+INSERT INTO contests VALUES(0,'root','+262142-12-31T23:59:59.999999999+00:00','-262143-01-01T00:00:00+00:00',0);
+CREATE TABLE contest_id_count (
+             key TEXT PRIMARY KEY,
+             value TEXT NOT NULL
+         );
+INSERT INTO contest_id_count VALUES('CONTEST_ID_COUNT','1');
+CREATE TABLE contest_problems (
+             contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+             position INTEGER NOT NULL,
+             problem_id INTEGER NOT NULL,
+             PRIMARY KEY (contest_id, position)
+         );
+INSERT INTO contest_problems VALUES(0,0,0);
+CREATE TABLE contest_participants (
+             contest_id INTEGER NOT NULL REFERENCES contests(id) ON DELETE CASCADE,
+             user_id INTEGER NOT NULL REFERENCES user_list(id),
+             position INTEGER NOT NULL,
+             rank INTEGER NOT NULL,
+             score INTEGER NOT NULL,
+             submission_count INTEGER NOT NULL,
+             competitive_score_sum REAL NOT NULL,
+             latest_submission TEXT NOT NULL,
+             PRIMARY KEY (contest_id, user_id)
+         );
+INSERT INTO contest_participants VALUES(0,0,0,0,0,1,0.0,'2026-10-19T05:11:27.057320738+00:00');
+CREATE TABLE participant_results (
+             contest_id INTEGER NOT NULL,
+             user_id INTEGER NOT NULL,
+             problem_index INTEGER NOT NULL,
+             score REAL NOT NULL,
+             highest_score REAL NOT NULL,
+             latest_score REAL NOT NULL,
+             PRIMARY KEY (contest_id, user_id, problem_index),
+             FOREIGN KEY (contest_id, user_id) REFERENCES contest_participants(contest_id, user_id) ON DELETE CASCADE
+         );
+INSERT INTO participant_results VALUES(0,0,0,0.0,100.0,100.0);
+CREATE TABLE participant_case_times (
+             contest_id INTEGER NOT NULL,
+             user_id INTEGER NOT NULL,
+             problem_index INTEGER NOT NULL,
+             case_index INTEGER NOT NULL,
+             time INTEGER NOT NULL,
+             PRIMARY KEY (contest_id, user_id, problem_index, case_index),
+             FOREIGN KEY (contest_id, user_id) REFERENCES contest_participants(contest_id, user_id) ON DELETE CASCADE
+         );
+CREATE TABLE jobs (
+             id INTEGER PRIMARY KEY,
+             created_time TEXT NOT NULL,
+             updated_time TEXT NOT NULL,
+             source_code TEXT NOT NULL,
+             language TEXT NOT NULL,
+             user_id INTEGER NOT NULL REFERENCES user_list(id),
+             contest_id INTEGER NOT NULL REFERENCES contests(id),
+             problem_id INTEGER NOT NULL,
+             state TEXT NOT NULL,
+             result TEXT NOT NULL,
+             score REAL NOT NULL
+         );
+INSERT INTO jobs VALUES(0,'2026-10-19T05:11:27.057320738+00:00','2026-10-19T05:11:27.195170848+00:00','fn main(){println!("Hello World!");}','Rust',0,0,0,'Finished','Accepted',100.0);
+CREATE TABLE job_cases (
+             job_id INTEGER NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+             id INTEGER NOT NULL,
+             result TEXT NOT NULL,
+             info TEXT NOT NULL,
+             time INTEGER NOT NULL,
+             memory INTEGER NOT NULL,
+             input TEXT,
+             answer TEXT,
+             output TEXT,
+             stderr TEXT,
+             PRIMARY KEY (job_id, id)
+         );
+INSERT INTO job_cases VALUES(0,0,'Compilation Success','',0,0,NULL,NULL,NULL,NULL);
+INSERT INTO job_cases VALUES(0,1,'Accepted','',1267,19364,'','Hello World!'||char(10),'Hello World!'||char(10),'');
+CREATE INDEX jobs_user_id ON jobs (user_id);
+CREATE INDEX jobs_contest_id ON jobs (contest_id, problem_id);
+CREATE INDEX jobs_problem_id ON jobs (problem_id);
\ No newline at end of file
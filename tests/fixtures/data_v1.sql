@@ -0,0 +1,51 @@
+-- data.db at schema version 1
+CREATE TABLE user_id_count (
+     key TEXT PRIMARY KEY,
+     value TEXT NOT NULL
+);
+CREATE TABLE user_list (
+     id INTEGER PRIMARY KEY,
+     name TEXT NOT NULL
+);
+CREATE TABLE job_id_count (
+     key TEXT PRIMARY KEY,
+     value TEXT NOT NULL
+);
+CREATE TABLE response_content (
+     id INTEGER PRIMARY KEY,
+     created_time TEXT NOT NULL,
+     updated_time TEXT NOT NULL,
+     submission TEXT NOT NULL,
+     state TEXT NOT NULL,
+     result TEXT NOT NULL,
+     score REAL NOT NULL,
+     cases TEXT NOT NULL
+);
+CREATE TABLE contest_id_count (
+     key TEXT PRIMARY KEY,
+     value TEXT NOT NULL
+);
+CREATE TABLE contest (
+     id INTEGER PRIMARY KEY,
+     name TEXT NOT NULL,
+     from_time TEXT NOT NULL,
+     to_time TEXT NOT NULL,
+     problem_ids TEXT NOT NULL,
+     user_ids TEXT NOT NULL,
+     submission_limit INTEGER NOT NULL,
+     users TEXT NOT NULL
+);
+CREATE TABLE schema_version (
+     version INTEGER PRIMARY KEY,
+     applied_time TEXT NOT NULL
+);
+INSERT INTO schema_version VALUES (1, '2022-08-27T00:00:00+00:00');
+INSERT INTO user_id_count VALUES ('USER_ID_COUNT', '2');
+INSERT INTO user_list VALUES (0, 'root');
+INSERT INTO user_list VALUES (1, 'alice');
+INSERT INTO job_id_count VALUES ('JOB_ID_COUNT', '2');
+INSERT INTO response_content VALUES (0, '2022-08-27T02:05:30.123+00:00', '2022-08-27T02:05:31.456+00:00', '{"source_code":"fn main() {}","language":"Rust","user_id":1,"contest_id":1,"problem_id":0}', 'Finished', 'Accepted', 100.0, '[{"id":0,"result":"Compilation Success","info":"","time":0},{"id":1,"result":"Accepted","info":"","time":1500}]');
+INSERT INTO response_content VALUES (1, '2022-08-27T02:06:00+00:00', '2022-08-27T02:06:01+00:00', '{"source_code":"fn main() { panic!() }","language":"Rust","user_id":0,"contest_id":0,"problem_id":0}', 'Finished', 'WrongAnswer', 0.0, '[{"id":0,"result":"Compilation Success","info":"","time":0},{"id":1,"result":"Wrong Answer","info":"","time":0}]');
+INSERT INTO contest_id_count VALUES ('CONTEST_ID_COUNT', '2');
+INSERT INTO contest VALUES (0, 'root', '+262142-12-31T23:59:59.999999999+00:00', '-262143-01-01T00:00:00+00:00', '[0]', '[0]', 0, '[{"user":{"id":0,"name":"root"},"rank":0,"scores":[0.0],"highest_scores":[0.0],"latest_scores":[0.0],"competitive_score_sum":0.0,"shortest_times":[[9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807]],"latest_submission":"+262142-12-31T23:59:59.999999999Z","score":0,"submission_count":0}]');
+INSERT INTO contest VALUES (1, 'first contest', '2022-08-27T02:05:29+00:00', '2030-08-27T02:05:29+00:00', '[0,1]', '[0,1]', 5, '[{"user":{"id":0,"name":"root"},"rank":0,"scores":[0.0,0.0],"highest_scores":[0.0,0.0],"latest_scores":[0.0,0.0],"competitive_score_sum":0.0,"shortest_times":[[9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807],[9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807]],"latest_submission":"+262142-12-31T23:59:59.999999999Z","score":0,"submission_count":0},{"user":{"id":1,"name":"alice"},"rank":0,"scores":[0.0,0.0],"highest_scores":[100.0,0.0],"latest_scores":[100.0,0.0],"competitive_score_sum":0.0,"shortest_times":[[1500,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807],[9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807,9223372036854775807]],"latest_submission":"2022-08-27T02:05:30.123Z","score":0,"submission_count":1}]');
\ No newline at end of file